@@ -0,0 +1,421 @@
+use crate::instruction::Instruction;
+use crate::disasm::ENTRY_POINT;
+use crate::memory::MAX_ROM_SIZE;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+// Assembler for the mnemonics printed by `--trace` and `chip8-disasm`, so their output can be
+// assembled back into the same bytes. Source is line based:
+//
+//   ; comment                    everything after ; is ignored
+//   name:                        label for the address of the next instruction or data
+//   name = expr                  constant
+//   ld v1, name + 2              instruction, see `Instruction::mnemonic` for the syntax
+//   db 0x3c, 0b01000010, 66      bytes
+//   dw 0x1234, name              big endian words
+//   include "sprites.asm"        source from another file, relative to the current one
+//
+// Numbers are decimal, 0x hexadecimal or 0b binary, and expressions are sums of numbers and
+// symbols. Labels may be used before they are defined, constants only after. The program is
+// assembled to run from 0x200, the address `Memory::load` puts it at.
+
+/// Nesting limit for include, catches files including each other
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Error with the source location it was found at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.msg)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// A source line with includes already expanded
+struct Line {
+    file: String,
+    line: usize,
+    text: String,
+}
+
+impl Line {
+    fn error<S: Into<String>>(&self, msg: S) -> AsmError {
+        AsmError { file: self.file.clone(), line: self.line, msg: msg.into() }
+    }
+}
+
+/// Something that ends up in the output, placed at `addr`
+struct Statement<'a> {
+    line: &'a Line,
+    addr: u16,
+    mnemonic: String,
+    operands: Vec<String>,
+}
+
+/// Assemble `source`, includes are resolved relative to the working directory
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut lines = Vec::new();
+    expand("<input>", Path::new(""), source, 0, &mut lines)?;
+
+    assemble_lines(&lines)
+}
+
+/// Assemble the file at `path`
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AsmError> {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let source = std::fs::read_to_string(path).map_err(|e| AsmError { file: name.clone(), line: 0, msg: e.to_string() })?;
+
+    let mut lines = Vec::new();
+    expand(&name, path.parent().unwrap_or_else(|| Path::new("")), &source, 0, &mut lines)?;
+
+    assemble_lines(&lines)
+}
+
+/// Append the lines of `source` to `out`, replacing include directives with the included lines
+fn expand(file: &str, dir: &Path, source: &str, depth: usize, out: &mut Vec<Line>) -> Result<(), AsmError> {
+    for (n, text) in source.lines().enumerate() {
+        let line = Line { file: file.to_string(), line: n + 1, text: strip_comment(text).trim().to_string() };
+
+        let (word, rest) = split_word(&line.text);
+        if !word.eq_ignore_ascii_case("include") {
+            out.push(line);
+            continue;
+        }
+
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(line.error("includes nested too deeply"));
+        }
+
+        let name = rest
+            .strip_prefix('"')
+            .and_then(|r| r.strip_suffix('"'))
+            .ok_or_else(|| line.error("include expects a quoted file name"))?;
+
+        let path = dir.join(name);
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| line.error(format!("cannot include '{}': {}", path.display(), e)))?;
+
+        let inner_dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        expand(&path.display().to_string(), &inner_dir, &source, depth + 1, out)?;
+    }
+
+    Ok(())
+}
+
+fn assemble_lines(lines: &[Line]) -> Result<Vec<u8>, AsmError> {
+    let mut symbols: HashMap<String, i64> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = ENTRY_POINT as usize;
+
+    // First pass, find the address of every label and the value of every constant
+    for line in lines {
+        let mut text = line.text.as_str();
+
+        while let Some((name, rest)) = split_label(text) {
+            if symbols.insert(name.to_string(), addr as i64).is_some() {
+                return Err(line.error(format!("'{}' is already defined", name)));
+            }
+            text = rest;
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        if let Some((name, expr)) = split_constant(text) {
+            let value = eval(expr, &symbols).map_err(|e| line.error(e))?;
+            if symbols.insert(name.to_string(), value).is_some() {
+                return Err(line.error(format!("'{}' is already defined", name)));
+            }
+            continue;
+        }
+
+        let (mnemonic, rest) = split_word(text);
+        let mnemonic = mnemonic.to_ascii_lowercase();
+        let operands = split_operands(rest);
+
+        let size = match mnemonic.as_str() {
+            "db" => operands.len(),
+            "dw" => operands.len() * 2,
+            "ld" if operands.get(1).is_some_and(|op| split_word(op).0.eq_ignore_ascii_case("long")) => 4,
+            _ => 2,
+        };
+
+        statements.push(Statement { line, addr: addr as u16, mnemonic, operands });
+
+        addr += size;
+        if addr > ENTRY_POINT as usize + MAX_ROM_SIZE {
+            return Err(line.error(format!("program is larger than the {} bytes available", MAX_ROM_SIZE)));
+        }
+    }
+
+    // Second pass, every symbol is known now
+    let mut rom = Vec::with_capacity(addr - ENTRY_POINT as usize);
+
+    for stmt in statements.iter() {
+        debug_assert_eq!(rom.len(), (stmt.addr - ENTRY_POINT) as usize);
+
+        let ops: Vec<&str> = stmt.operands.iter().map(String::as_str).collect();
+        match stmt.mnemonic.as_str() {
+            "db" => {
+                for op in ops {
+                    rom.push(value(op, &symbols, -0x80, 0xff).map_err(|e| stmt.line.error(e))? as u8);
+                }
+            },
+            "dw" => {
+                for op in ops {
+                    let word = value(op, &symbols, -0x8000, 0xffff).map_err(|e| stmt.line.error(e))? as u16;
+                    rom.extend_from_slice(&word.to_be_bytes());
+                }
+            },
+            mnemonic => {
+                let instruction = parse_instruction(mnemonic, &ops, &symbols).map_err(|e| stmt.line.error(e))?;
+                rom.extend(instruction.encode());
+            },
+        }
+    }
+
+    Ok(rom)
+}
+
+/// Turn a mnemonic and its operands into an instruction
+fn parse_instruction(mnemonic: &str, ops: &[&str], symbols: &HashMap<String, i64>) -> Result<Instruction, String> {
+    use Instruction::*;
+
+    let addr = |op: &str| value(op, symbols, 0, 0xfff).map(|v| v as u16);
+    let byte = |op: &str| value(op, symbols, -0x80, 0xff).map(|v| v as u8);
+    let nibble = |op: &str| value(op, symbols, 0, 0xf).map(|v| v as u8);
+    let vx = |op: &str| reg(op).ok_or(format!("expected a register, found '{}'", op));
+
+    let lower: Vec<String> = ops.iter().map(|op| op.to_ascii_lowercase()).collect();
+    let lower: Vec<&str> = lower.iter().map(String::as_str).collect();
+
+    let inst = match (mnemonic, ops) {
+        ("sys", [a]) => Sys(addr(a)?),
+        ("cls", []) => Cls,
+        ("ret", []) => Ret,
+        ("scd", [n]) => ScrollDown(nibble(n)?),
+        ("scu", [n]) => ScrollUp(nibble(n)?),
+        ("scr", []) => ScrollRight,
+        ("scl", []) => ScrollLeft,
+        ("exit", []) => Exit,
+        ("low", []) => Low,
+        ("high", []) => High,
+        ("jp", [a]) => Jump(addr(a)?),
+        // jp vx, xnn is how Bxnn is traced with the jump_vx quirk, x has to match the address
+        ("jp", [v, a]) if reg(v).is_some() => {
+            let a = addr(a)?;
+            if reg(v) != Some(0) && reg(v) != Some((a >> 8) as u8) {
+                return Err(format!("'jp {}' needs an address starting with {}", v, &v[1..]));
+            }
+            JumpOffset((a >> 8) as u8, a)
+        },
+        ("call", [a]) => Call(addr(a)?),
+        ("se", [x, y]) => match reg(y) {
+            Some(y) => SkipEqReg(vx(x)?, y),
+            None => SkipEqByte(vx(x)?, byte(y)?),
+        },
+        ("sne", [x, y]) => match reg(y) {
+            Some(y) => SkipNeReg(vx(x)?, y),
+            None => SkipNeByte(vx(x)?, byte(y)?),
+        },
+        ("ld", [dst, src]) => match (lower[0], lower[1]) {
+            ("[i]", _) => match range(src) {
+                Some((x, y)) => StoreRange(x, y),
+                None => Store(vx(src)?),
+            },
+            (_, "[i]") => match range(dst) {
+                Some((x, y)) => LoadRange(x, y),
+                None => Load(vx(dst)?),
+            },
+            ("i", _) => match split_word(src) {
+                (long, rest) if long.eq_ignore_ascii_case("long") => {
+                    LoadILong(value(rest, symbols, 0, 0xffff)? as u16)
+                },
+                _ => LoadI(addr(src)?),
+            },
+            ("dt", _) => SetDelay(vx(src)?),
+            ("st", _) => SetSound(vx(src)?),
+            ("f", _) => Font(vx(src)?),
+            ("hf", _) => BigFont(vx(src)?),
+            ("b", _) => Bcd(vx(src)?),
+            ("r", _) => StoreFlags(vx(src)?),
+            (_, "dt") => LoadDelay(vx(dst)?),
+            (_, "k") => WaitKey(vx(dst)?),
+            (_, "r") => LoadFlags(vx(dst)?),
+            _ => match reg(src) {
+                Some(y) => LoadReg(vx(dst)?, y),
+                None => LoadByte(vx(dst)?, byte(src)?),
+            },
+        },
+        ("add", [_, src]) if lower[0] == "i" => AddI(vx(src)?),
+        ("add", [x, y]) => match reg(y) {
+            Some(y) => AddReg(vx(x)?, y),
+            None => AddByte(vx(x)?, byte(y)?),
+        },
+        ("or", [x, y]) => Or(vx(x)?, vx(y)?),
+        ("and", [x, y]) => And(vx(x)?, vx(y)?),
+        ("xor", [x, y]) => Xor(vx(x)?, vx(y)?),
+        ("sub", [x, y]) => Sub(vx(x)?, vx(y)?),
+        ("subn", [x, y]) => SubN(vx(x)?, vx(y)?),
+        // Vy is optional, written as `shr vx {, vy}` in the trace
+        ("shr", [x]) => ShiftRight(vx(x)?, vx(x)?),
+        ("shr", [x, y]) => ShiftRight(vx(x)?, vx(y)?),
+        ("shl", [x]) => ShiftLeft(vx(x)?, vx(x)?),
+        ("shl", [x, y]) => ShiftLeft(vx(x)?, vx(y)?),
+        ("rnd", [x, kk]) => Random(vx(x)?, byte(kk)?),
+        ("drw", [x, y, n]) => Draw(vx(x)?, vx(y)?, nibble(n)?),
+        ("skp", [x]) => SkipKey(vx(x)?),
+        ("sknp", [x]) => SkipNotKey(vx(x)?),
+        ("plane", [n]) => Plane(nibble(n)?),
+        ("audio", []) => Audio,
+        ("pitch", [x]) => Pitch(vx(x)?),
+        _ if !MNEMONICS.contains(&mnemonic) => {
+            return Err(format!("unknown instruction '{}'", mnemonic));
+        },
+        _ => return Err(format!("invalid operands for '{}'", mnemonic)),
+    };
+
+    Ok(inst)
+}
+
+/// Every mnemonic `parse_instruction` knows, to tell typos from bad operands
+const MNEMONICS: &[&str] = &[
+    "sys", "cls", "ret", "scd", "scu", "scr", "scl", "exit", "low", "high", "jp", "call", "se", "sne", "ld", "add",
+    "or", "and", "xor", "sub", "subn", "shr", "shl", "rnd", "drw", "skp", "sknp", "plane", "audio", "pitch",
+];
+
+/// Register number of `v0` through `vf`
+fn reg(op: &str) -> Option<u8> {
+    let digit = op.strip_prefix('v').or_else(|| op.strip_prefix('V'))?;
+
+    if digit.len() != 1 {
+        return None;
+    }
+
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// Registers of a `vx - vy` range
+fn range(op: &str) -> Option<(u8, u8)> {
+    let (x, y) = op.split_once('-')?;
+
+    Some((reg(x.trim())?, reg(y.trim())?))
+}
+
+/// Evaluate `expr` and check it lies within `min..=max`
+fn value(expr: &str, symbols: &HashMap<String, i64>, min: i64, max: i64) -> Result<i64, String> {
+    let val = eval(expr, symbols)?;
+
+    if val < min || val > max {
+        return Err(format!("value {} of '{}' is out of range", val, expr));
+    }
+
+    Ok(val)
+}
+
+/// Evaluate a sum of numbers and symbols
+fn eval(expr: &str, symbols: &HashMap<String, i64>) -> Result<i64, String> {
+    let mut total: i64 = 0;
+    let mut sign = 1;
+    let mut term = String::new();
+    let overflow = || format!("value of '{}' is too large", expr.trim());
+
+    for c in expr.chars().chain(std::iter::once('\0')) {
+        if c != '+' && c != '-' && c != '\0' {
+            term.push(c);
+            continue;
+        }
+
+        let t = term.trim();
+        if t.is_empty() {
+            match c {
+                '-' => sign = -sign,
+                '+' => (),
+                _ => return Err(format!("missing value in '{}'", expr.trim())),
+            }
+            continue;
+        }
+
+        let val = term_value(t, symbols)?.checked_mul(sign).ok_or_else(overflow)?;
+        total = total.checked_add(val).ok_or_else(overflow)?;
+        term.clear();
+        sign = if c == '-' { -1 } else { 1 };
+    }
+
+    Ok(total)
+}
+
+/// Value of a single number or symbol
+fn term_value(t: &str, symbols: &HashMap<String, i64>) -> Result<i64, String> {
+    let number = if let Some(hex) = t.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = t.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()
+    } else if t.starts_with(|c: char| c.is_ascii_digit()) {
+        t.parse().ok()
+    } else {
+        return symbols.get(t).copied().ok_or(format!("unknown symbol '{}'", t));
+    };
+
+    number.ok_or(format!("invalid number '{}'", t))
+}
+
+/// Remove a trailing `;` comment
+fn strip_comment(text: &str) -> &str {
+    text.split(';').next().unwrap_or_default()
+}
+
+/// First whitespace separated word and the trimmed rest
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+
+    match text.find(char::is_whitespace) {
+        Some(pos) => (&text[..pos], text[pos..].trim()),
+        None => (text, ""),
+    }
+}
+
+/// Comma separated operands, the braces in `shr vx {, vy}` are dropped
+fn split_operands(text: &str) -> Vec<String> {
+    let text: String = text.chars().filter(|c| *c != '{' && *c != '}').collect();
+
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+
+    text.split(',').map(|op| op.trim().to_string()).collect()
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Leading `name:` label and the rest of the line
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.split_once(':')?;
+    let name = name.trim();
+
+    if is_identifier(name) { Some((name, rest.trim())) } else { None }
+}
+
+/// `name = expr` constant definition
+fn split_constant(text: &str) -> Option<(&str, &str)> {
+    let (name, expr) = text.split_once('=')?;
+    let name = name.trim();
+
+    if is_identifier(name) { Some((name, expr.trim())) } else { None }
+}
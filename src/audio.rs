@@ -0,0 +1,36 @@
+// XO-CHIP replaces the fixed buzzer with a 16 byte pattern buffer of 1-bit samples, loaded with F002.
+// While the sound timer is active the 128 samples are played in a loop at a rate set by the pitch
+// register, loaded with Fx3A: 4000 * 2 ^ ((pitch - 64) / 48) samples per second.
+
+/// Default pitch, plays the pattern at 4000 samples per second
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// XO-CHIP audio state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Audio {
+    /// 128 1-bit samples, `None` until the program loads a pattern and the classic buzzer is used
+    pub pattern: Option<[u8; 16]>,
+
+    /// Playback pitch register
+    pub pitch: u8,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Audio {
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+
+    /// Samples of the pattern played per second
+    pub fn playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+}
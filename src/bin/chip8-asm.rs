@@ -0,0 +1,68 @@
+use chip8_emu::asm::{assemble_file};
+use chip8_emu::octo;
+
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: chip8-asm [OPTIONS] <SOURCE>
+
+Arguments:
+  <SOURCE>               Assembly source to build, .8o files are compiled as Octo
+
+Options:
+  -o, --output <FILE>    Write the ROM to FILE [default: SOURCE with a .ch8 extension]
+  -h, --help             Print this help";
+
+fn main() {
+    let mut source = None;
+    let mut output = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => usage_error(&format!("option '{}' requires a value", arg)),
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option '{}'", arg)),
+            _ if source.is_none() => source = Some(PathBuf::from(arg)),
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let source = source.unwrap_or_else(|| usage_error("missing <SOURCE> argument"));
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+
+    if output == source {
+        usage_error("output would overwrite the source file");
+    }
+
+    let result = if source.extension().is_some_and(|ext| ext == "8o") {
+        octo::compile_file(&source).map(|program| program.rom)
+    } else {
+        assemble_file(&source)
+    };
+
+    let rom = match result {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = std::fs::write(&output, rom) {
+        eprintln!("error: cannot write '{}': {}", output.display(), e);
+        std::process::exit(1);
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    eprintln!("Try 'chip8-asm --help' for more information.");
+    std::process::exit(2);
+}
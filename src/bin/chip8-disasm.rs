@@ -0,0 +1,59 @@
+use chip8_emu::disasm::{Disassembly, ENTRY_POINT};
+
+const USAGE: &str = "\
+Usage: chip8-disasm [OPTIONS] <ROM>
+
+Arguments:
+  <ROM>                  Chip-8 program to disassemble
+
+Options:
+  -o, --output <FILE>    Write the listing to FILE instead of stdout
+  -h, --help             Print this help";
+
+fn main() {
+    let mut rom = None;
+    let mut output = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(path),
+                None => usage_error(&format!("option '{}' requires a value", arg)),
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option '{}'", arg)),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rom = rom.unwrap_or_else(|| usage_error("missing <ROM> argument"));
+
+    if let Err(e) = run(&rom, output.as_deref()) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    eprintln!("Try 'chip8-disasm --help' for more information.");
+    std::process::exit(2);
+}
+
+fn run(rom: &str, output: Option<&str>) -> Result<(), String> {
+    let data = std::fs::read(rom).map_err(|e| format!("cannot read ROM '{}': {}", rom, e))?;
+    let listing = Disassembly::new(&data, ENTRY_POINT).listing();
+
+    match output {
+        Some(path) => std::fs::write(path, listing).map_err(|e| format!("cannot write '{}': {}", path, e)),
+        None => {
+            print!("{}", listing);
+            Ok(())
+        },
+    }
+}
@@ -0,0 +1,219 @@
+use chip8_emu::golden::{self, InputEvent};
+use chip8_emu::ocr;
+use chip8_emu::quirks::{Quirks};
+use chip8_emu::rng::{Algorithm, Rng};
+
+use std::path::{Path};
+
+const USAGE: &str = "\
+Usage: chip8-test [OPTIONS] <MANIFEST>
+       chip8-test [OPTIONS] --rom <ROM>
+
+Arguments:
+  <MANIFEST>             List of ROMs with their expected framebuffer hashes
+
+Options:
+      --rom <ROM>        Run a single ROM and print its hash instead of checking a manifest
+  -f, --frames <N>       Frames to run with --rom [default: 300]
+  -q, --quirks <PROFILE> Interpreter quirks for --rom [default: default]
+      --seed <N>         Random number generator seed for --rom [default: 1]
+      --rng <ALGORITHM>  Random number generator for --rom: xorshift, vip [default: xorshift]
+  -i, --input <EVENTS>   Key presses for --rom, e.g. 30:+5,35:-5 presses 5 before frame 30
+      --dump             Print the final framebuffer of every ROM
+  -u, --update           Write the actual hashes into the manifest
+  -h, --help             Print this help
+
+Exits with 0 when every ROM matches, 1 on mismatches and 2 on errors.";
+
+/// Runtime options parsed from the command line
+struct Options {
+    manifest: Option<String>,
+    rom: Option<String>,
+    frames: u64,
+    quirks: Quirks,
+    seed: u64,
+    rng: Algorithm,
+    input: Vec<InputEvent>,
+    dump: bool,
+    update: bool,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut opts = Options {
+            manifest: None,
+            rom: None,
+            frames: 300,
+            quirks: Quirks::default(),
+            seed: golden::SEED,
+            rng: Algorithm::Xorshift,
+            input: Vec::new(),
+            dump: false,
+            update: false,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                },
+                "--rom" => opts.rom = Some(parse_value(&arg, args.next())?),
+                "-f" | "--frames" => opts.frames = parse_value(&arg, args.next())?,
+                "-q" | "--quirks" => opts.quirks = parse_value(&arg, args.next())?,
+                "--seed" => opts.seed = parse_value(&arg, args.next())?,
+                "--rng" => opts.rng = parse_value(&arg, args.next())?,
+                "-i" | "--input" => {
+                    let value: String = parse_value(&arg, args.next())?;
+                    opts.input = value.split(',').map(str::parse).collect::<Result<_, _>>()?;
+                },
+                "--dump" => opts.dump = true,
+                "-u" | "--update" => opts.update = true,
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ if opts.manifest.is_none() => opts.manifest = Some(arg),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        match (&opts.rom, &opts.manifest) {
+            (Some(_), Some(_)) => Err("--rom does not take a manifest".to_string()),
+            (None, None) => Err("missing <MANIFEST> argument".to_string()),
+            _ => Ok(opts),
+        }
+    }
+}
+
+/// Parse the value following `flag`
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("option '{}' requires a value", flag))?;
+
+    value.parse().map_err(|_| format!("invalid value '{}' for option '{}'", value, flag))
+}
+
+fn main() {
+    let opts = match Options::parse(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("Try 'chip8-test --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+
+    let result = match &opts.rom {
+        Some(rom) => run_rom(rom, &opts).map(|_| true),
+        None => run_manifest(opts.manifest.as_deref().unwrap_or_default(), &opts),
+    };
+
+    match result {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        },
+    }
+}
+
+/// Run a single ROM and print its hash
+fn run_rom(path: &str, opts: &Options) -> Result<(), String> {
+    let rom = std::fs::read(path).map_err(|e| format!("cannot read ROM '{}': {}", path, e))?;
+    let emu = golden::run(&rom, opts.frames, opts.quirks, Rng::with_algorithm(opts.rng, opts.seed), &opts.input).map_err(|e| format!("{}: {}", path, e))?;
+
+    if opts.dump {
+        print!("{}", golden::dump(&emu.display));
+    }
+    for line in ocr::read_text(&emu.display) {
+        println!("text: {}", line);
+    }
+    println!("{:016x}", golden::framebuffer_hash(&emu.display));
+
+    Ok(())
+}
+
+/// Check every ROM of the manifest, returns whether all of them matched
+fn run_manifest(path: &str, opts: &Options) -> Result<bool, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read manifest '{}': {}", path, e))?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let cases = golden::parse_manifest(&text, dir).map_err(|e| format!("{}: {}", path, e))?;
+
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    let mut failed = 0;
+
+    for case in &cases {
+        let name = case.rom.display();
+        let rom = std::fs::read(&case.rom).map_err(|e| format!("cannot read ROM '{}': {}", name, e))?;
+
+        // An emulation error is a failure of the ROM, not of the runner
+        let emu = match golden::run(&rom, case.frames, case.quirks, case.rng, &case.input) {
+            Ok(emu) => emu,
+            Err(e) => {
+                println!("FAIL {}: {}", name, e);
+                failed += 1;
+                continue;
+            },
+        };
+
+        let hash = golden::framebuffer_hash(&emu.display);
+        let text = ocr::read_text(&emu.display);
+        let words: Vec<&str> = text.iter().flat_map(|line| line.split(' ')).collect();
+        let wrong_text: Vec<String> = case
+            .text
+            .iter()
+            .filter(|(word, present)| words.contains(&word.as_str()) != *present)
+            .map(|(word, present)| format!("'{}' {}", word, if *present { "missing" } else { "present" }))
+            .collect();
+
+        // Text checks hold whatever the hash, they are what the ROM itself reports
+        let passed = wrong_text.is_empty() && (hash == case.hash || opts.update);
+
+        if passed && hash == case.hash {
+            println!("ok   {}", name);
+        } else if passed {
+            println!("upd  {}: hash {:016x}, was {:016x}", name, hash, case.hash);
+        } else if !wrong_text.is_empty() {
+            println!("FAIL {}: text {}", name, wrong_text.join(", "));
+        } else {
+            println!("FAIL {}: hash {:016x}, expected {:016x}", name, hash, case.hash);
+        }
+
+        if !passed {
+            failed += 1;
+        }
+
+        if opts.dump || !passed {
+            print!("{}", golden::dump(&emu.display));
+            for line in &text {
+                println!("text: {}", line);
+            }
+            if let Some((code, description)) = ocr::bc_test_error(&text) {
+                println!("BC_test error {:02}: {}", code, description);
+            }
+        }
+
+        if opts.update {
+            lines[case.line - 1] = replace_column(&lines[case.line - 1], 3, &format!("{:016x}", hash));
+        }
+    }
+
+    println!("{} passed, {} failed", cases.len() - failed, failed);
+
+    // Text checks and emulation errors fail even while updating, new hashes can not fix them
+    if opts.update {
+        std::fs::write(path, lines.join("\n") + "\n").map_err(|e| format!("cannot write manifest '{}': {}", path, e))?;
+    }
+
+    Ok(failed == 0)
+}
+
+/// Replace the whitespace separated column `index` of `line`, keeping the spacing around it
+fn replace_column(line: &str, index: usize, value: &str) -> String {
+    match line.split_whitespace().nth(index) {
+        Some(column) => {
+            // The column is a subslice of `line`, its offset follows from the pointers
+            let start = column.as_ptr() as usize - line.as_ptr() as usize;
+            format!("{}{}{}", &line[..start], value, &line[start + column.len()..])
+        },
+        None => line.to_string(),
+    }
+}
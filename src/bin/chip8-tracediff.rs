@@ -0,0 +1,120 @@
+use chip8_emu::emulator::{Emulator};
+use chip8_emu::quirks::{Quirks};
+use chip8_emu::rng::{Rng};
+use chip8_emu::tracediff::{compare_traces, lockstep, Divergence};
+
+const USAGE: &str = "\
+Usage: chip8-tracediff [OPTIONS] <TRACE_A> <TRACE_B>
+       chip8-tracediff [OPTIONS] --lockstep <ROM>
+
+Arguments:
+  <TRACE_A> <TRACE_B>    Logs written by chip8-emu --trace, in any --trace-format
+
+Options:
+  -C, --context <N>      Instructions shown before the divergence [default: 5]
+      --lockstep <ROM>   Run ROM in two emulators side by side instead of comparing logs
+  -a, --quirks-a <PROFILE>
+                         Quirks of the first emulator in lockstep [default: default]
+  -b, --quirks-b <PROFILE>
+                         Quirks of the second emulator in lockstep [default: default]
+  -n, --cycles <N>       Stop lockstep after N instructions [default: 1000000]
+  -h, --help             Print this help
+
+Exits with 0 when the runs match, 1 when they diverge and 2 on errors.";
+
+/// Runtime options parsed from the command line
+struct Options {
+    traces: Vec<String>,
+    context: usize,
+    lockstep: Option<String>,
+    quirks_a: Quirks,
+    quirks_b: Quirks,
+    cycles: u64,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut opts = Options {
+            traces: Vec::new(),
+            context: 5,
+            lockstep: None,
+            quirks_a: Quirks::default(),
+            quirks_b: Quirks::default(),
+            cycles: 1_000_000,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                },
+                "-C" | "--context" => opts.context = parse_value(&arg, args.next())?,
+                "--lockstep" => opts.lockstep = Some(parse_value(&arg, args.next())?),
+                "-a" | "--quirks-a" => opts.quirks_a = parse_value(&arg, args.next())?,
+                "-b" | "--quirks-b" => opts.quirks_b = parse_value(&arg, args.next())?,
+                "-n" | "--cycles" => opts.cycles = parse_value(&arg, args.next())?,
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ if opts.traces.len() < 2 => opts.traces.push(arg),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        match (&opts.lockstep, opts.traces.len()) {
+            (Some(_), 0) | (None, 2) => Ok(opts),
+            (Some(_), _) => Err("--lockstep does not take trace files".to_string()),
+            (None, _) => Err("missing <TRACE_A> <TRACE_B> arguments".to_string()),
+        }
+    }
+}
+
+/// Parse the value following `flag`
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("option '{}' requires a value", flag))?;
+
+    value.parse().map_err(|_| format!("invalid value '{}' for option '{}'", value, flag))
+}
+
+fn main() {
+    let opts = match Options::parse(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("Try 'chip8-tracediff --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+
+    match run(opts) {
+        Ok(None) => println!("no divergence"),
+        Ok(Some(divergence)) => {
+            print!("{}", divergence);
+            std::process::exit(1);
+        },
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        },
+    }
+}
+
+fn run(opts: Options) -> Result<Option<Divergence>, String> {
+    if let Some(rom) = &opts.lockstep {
+        let mut a = Emulator::new();
+        a.memory.load(rom).map_err(|e| format!("cannot load ROM '{}': {}", rom, e))?;
+
+        // Identical start including the random numbers, only the quirks differ
+        a.rng = Rng::new(0);
+        let mut b = a.clone();
+        a.quirks = opts.quirks_a;
+        b.quirks = opts.quirks_b;
+
+        return Ok(lockstep(&mut a, &mut b, opts.cycles, opts.context));
+    }
+
+    let read = |path: &String| std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e));
+    let a = read(&opts.traces[0])?;
+    let b = read(&opts.traces[1])?;
+
+    Ok(compare_traces(&a, &b, opts.context))
+}
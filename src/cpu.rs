@@ -1,3 +1,5 @@
+use crate::error::Chip8Error;
+
 // 16 x 8-bit GPIO Registers
 // 1  x 16-bit I Register
 
@@ -24,6 +26,15 @@ pub struct Register {
     pub  i:  u16,
     pub pc:  u16,
     pub sp:  u16,
+
+    /// SUPER-CHIP RPL user flags, saved and restored by Fx75/Fx85
+    pub flags: [u8; 8],
+}
+
+impl Default for Register {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Register {
@@ -50,11 +61,12 @@ impl Register {
              i: 0,
             pc: 0x200, // Game Data
             sp: 0,
+            flags: [0; 8],
         }
     }
 
     /// Write to specific V register
-    pub fn reg_write(&mut self, reg:u8, val: u8) {
+    pub fn reg_write(&mut self, reg:u8, val: u8) -> Result<(), Chip8Error> {
         match reg {
             0 => self.v0 = val,
             1 => self.v1 = val,
@@ -73,34 +85,36 @@ impl Register {
             14 => self.ve = val,
             15 => self.vf = val,            
             _ => {
-                panic!("Invalid GPIO Register!");
+                return Err(Chip8Error::InvalidRegister(reg));
             }
         }
+
+        Ok(())
     }
 
     /// Read from specific V register
-    pub fn reg_read(&mut self, reg:u8) -> u8 {
-        match reg {
-            0 =>  return self.v0,
-            1 =>  return self.v1,
-            2 =>  return self.v2,
-            3 =>  return self.v3,
-            4 =>  return self.v4,
-            5 =>  return self.v5,
-            6 =>  return self.v6,
-            7 =>  return self.v7,
-            8 =>  return self.v8,
-            9 =>  return self.v9,
-            10 => return self.va,
-            11 => return self.vb,
-            12 => return self.vc,
-            13 => return self.vd,
-            14 => return self.ve,
-            15 => return self.vf,            
+    pub fn reg_read(&self, reg:u8) -> Result<u8, Chip8Error> {
+        Ok(match reg {
+            0 => self.v0,
+            1 => self.v1,
+            2 => self.v2,
+            3 => self.v3,
+            4 => self.v4,
+            5 => self.v5,
+            6 => self.v6,
+            7 => self.v7,
+            8 => self.v8,
+            9 => self.v9,
+            10 => self.va,
+            11 => self.vb,
+            12 => self.vc,
+            13 => self.vd,
+            14 => self.ve,
+            15 => self.vf,            
             _ => {
-                panic!("Invalid GPIO Register!");
+                return Err(Chip8Error::InvalidRegister(reg));
             }
-        }
+        })
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,302 @@
+use crate::emulator::Emulator;
+use crate::disasm::{disassemble_at};
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::io;
+use std::io::prelude::*;
+
+// Command-line debugger. The emulator loop asks `should_break` before every instruction and
+// hands control to `repl` whenever it returns true. The REPL reads commands from stdin until
+// one of them resumes execution.
+
+const HELP: &str = "\
+Addresses and values are hexadecimal, counts are decimal.
+
+  s, step [n]             Execute n instructions [default: 1]
+  n, next                 Execute one instruction, stepping over CALL
+  c, continue             Run until the next breakpoint
+  b, break [addr]         Set a breakpoint, or list them without an address
+  d, delete <addr>        Remove a breakpoint
+  r, regs                 Show the registers
+  m, mem <addr> [len]     Hex dump of memory [default: 64 bytes]
+  l, list [addr] [n]      Disassemble n instructions [default: around PC]
+      set <reg> <val>     Change v0-vf, i, pc, dt or st
+  w, write <addr> <b>...  Write bytes to memory
+  h, help                 Show this help
+  q, quit                 Stop the emulator
+
+An empty line repeats the last command.";
+
+/// What the emulator should do once the REPL returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Continue executing instructions
+    Resume,
+
+    /// Stop the emulator
+    Quit,
+}
+
+/// Memory region shown every time execution stops
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    pub name: String,
+    pub addr: u16,
+    pub len: usize,
+}
+
+/// Breakpoints and stepping state
+#[derive(Debug, Default)]
+pub struct Debugger {
+    /// Addresses that stop execution before the instruction there runs
+    pub breakpoints: BTreeSet<u16>,
+
+    /// Memory regions printed whenever the REPL is entered
+    pub monitors: Vec<Monitor>,
+
+    /// Stop before the next instruction once `steps` has run out
+    active: bool,
+
+    /// Instructions left to execute before stopping again
+    steps: u64,
+
+    /// Return address and stack pointer of a CALL being stepped over
+    step_over: Option<(u16, usize)>,
+
+    /// Last command entered, repeated by an empty line
+    last: String,
+
+    /// Stdin was closed, there is no way to enter commands anymore
+    detached: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger::default()
+    }
+
+    /// Commands can still be read from stdin
+    pub fn attached(&self) -> bool {
+        !self.detached
+    }
+
+    /// Stop before the next instruction
+    pub fn break_now(&mut self) {
+        self.active = !self.detached;
+        self.steps = 0;
+        self.step_over = None;
+    }
+
+    /// Check whether execution should stop before the instruction at PC
+    pub fn should_break(&mut self, emu: &Emulator) -> bool {
+        let pc = emu.registers.pc;
+
+        if self.step_over == Some((pc, emu.memory.sp)) || self.breakpoints.contains(&pc) {
+            self.break_now();
+        }
+
+        if !self.active {
+            return false;
+        }
+
+        if self.steps > 0 {
+            self.steps -= 1;
+            return false;
+        }
+
+        true
+    }
+
+    /// Read and run commands from stdin until execution is resumed
+    pub fn repl(&mut self, emu: &mut Emulator) -> Action {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+
+        for monitor in self.monitors.iter() {
+            let end = (monitor.addr as usize + monitor.len).min(emu.memory.memory.len());
+            let bytes: Vec<String> = emu.memory.memory[monitor.addr as usize..end].iter().map(|b| format!("{:02x}", b)).collect();
+            println!("{}: {}", monitor.name, bytes.join(" "));
+        }
+
+        println!("{:04x}: {}", emu.registers.pc, disassemble_at(&emu.memory, &emu.quirks, emu.registers.pc as usize).0);
+
+        loop {
+            print!("(chip8) ");
+            let _ = stdout.flush();
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                // End of input, nobody is left to type commands so let the program run
+                Ok(0) | Err(_) => {
+                    println!();
+                    self.detached = true;
+                    self.active = false;
+                    self.breakpoints.clear();
+                    return Action::Resume;
+                },
+                Ok(_) => (),
+            }
+
+            let line = match line.trim() {
+                "" => self.last.clone(),
+                line => line.to_string(),
+            };
+            self.last = line.clone();
+
+            match self.command(emu, &line) {
+                Ok(Some(action)) => return action,
+                Ok(None) => (),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
+    /// Run a single command, returns the action to take when it leaves the REPL
+    pub fn command(&mut self, emu: &mut Emulator, line: &str) -> Result<Option<Action>, String> {
+        let mut args = line.split_whitespace();
+        let cmd = match args.next() {
+            Some(cmd) => cmd,
+            None => return Ok(None),
+        };
+        let args: Vec<&str> = args.collect();
+
+        match cmd {
+            "s" | "step" => {
+                let n: u64 = match args.first() {
+                    Some(n) => n.parse().map_err(|_| format!("invalid count '{}'", n))?,
+                    None => 1,
+                };
+                if n == 0 {
+                    return Err("count must be greater than 0".to_string());
+                }
+
+                self.active = true;
+                self.steps = n - 1;
+                return Ok(Some(Action::Resume));
+            },
+            "n" | "next" => {
+                let pc = emu.registers.pc;
+                let inst = emu.memory.read_inst(pc as usize).map_err(|e| e.to_string())?;
+
+                if inst >> 12 == 0x2 {
+                    // Run freely until the call returns to the same stack depth
+                    self.active = false;
+                    self.step_over = Some((pc.wrapping_add(2), emu.memory.sp));
+                } else {
+                    self.active = true;
+                    self.steps = 0;
+                }
+                return Ok(Some(Action::Resume));
+            },
+            "c" | "continue" => {
+                self.active = false;
+                self.step_over = None;
+                return Ok(Some(Action::Resume));
+            },
+            "b" | "break" => match args.first() {
+                Some(addr) => {
+                    let addr = parse_hex(addr)?;
+                    self.breakpoints.insert(addr);
+                    println!("breakpoint at {:04x}", addr);
+                },
+                None if self.breakpoints.is_empty() => println!("no breakpoints"),
+                None => {
+                    for addr in self.breakpoints.iter() {
+                        println!("{:04x}: {}", addr, disassemble_at(&emu.memory, &emu.quirks, *addr as usize).0);
+                    }
+                },
+            },
+            "d" | "delete" => {
+                let addr = parse_hex(args.first().ok_or("missing address")?)?;
+                if !self.breakpoints.remove(&addr) {
+                    return Err(format!("no breakpoint at {:04x}", addr));
+                }
+            },
+            "r" | "regs" => print!("{}", emu.registers),
+            "m" | "mem" => {
+                let addr = parse_hex(args.first().ok_or("missing address")?)? as usize;
+                let len = match args.get(1) {
+                    Some(len) => len.parse().map_err(|_| format!("invalid length '{}'", len))?,
+                    None => 64,
+                };
+
+                let end = addr.saturating_add(len).min(emu.memory.memory.len());
+                for (row, chunk) in emu.memory.memory[addr.min(end)..end].chunks(16).enumerate() {
+                    let bytes: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                    println!("{:04x}: {}", addr + row * 16, bytes.join(" "));
+                }
+            },
+            "l" | "list" => {
+                let pc = emu.registers.pc as usize;
+                let mut addr = match args.first() {
+                    Some(addr) => parse_hex(addr)? as usize,
+                    None => pc.saturating_sub(8),
+                };
+                let count: usize = match args.get(1) {
+                    Some(n) => n.parse().map_err(|_| format!("invalid count '{}'", n))?,
+                    None => 10,
+                };
+
+                for _ in 0..count {
+                    let (text, len) = disassemble_at(&emu.memory, &emu.quirks, addr);
+                    let marker = if addr == pc { '>' } else { ' ' };
+                    let bp = if self.breakpoints.contains(&(addr as u16)) { '*' } else { ' ' };
+                    println!("{}{} {:04x}: {}", marker, bp, addr, text);
+                    addr += len;
+                }
+            },
+            "set" => {
+                let (name, val) = match args.as_slice() {
+                    [name, val] => (*name, *val),
+                    _ => return Err("usage: set <reg> <val>".to_string()),
+                };
+
+                let regs = &mut emu.registers;
+                match name {
+                    "i" => regs.i = parse_hex(val)?,
+                    "pc" => regs.pc = parse_hex(val)?,
+                    "dt" => regs.dt = parse_byte(val)?,
+                    "st" => regs.st = parse_byte(val)?,
+                    _ => {
+                        let reg = name
+                            .strip_prefix('v')
+                            .and_then(|x| u8::from_str_radix(x, 16).ok())
+                            .ok_or(format!("unknown register '{}'", name))?;
+                        regs.reg_write(reg, parse_byte(val)?).map_err(|e| e.to_string())?;
+                    },
+                }
+            },
+            "w" | "write" => {
+                let addr = parse_hex(args.first().ok_or("missing address")?)? as usize;
+                if args.len() < 2 {
+                    return Err("missing bytes".to_string());
+                }
+
+                for (offset, byte) in args[1..].iter().enumerate() {
+                    let byte = parse_byte(byte)?;
+                    emu.memory.write(addr + offset, byte).map_err(|e| e.to_string())?;
+                }
+            },
+            "h" | "help" => println!("{}", HELP),
+            "q" | "quit" => return Ok(Some(Action::Quit)),
+            _ => return Err(format!("unknown command '{}', try 'help'", cmd)),
+        }
+
+        Ok(None)
+    }
+}
+
+/// Parse a hexadecimal number with an optional 0x prefix
+fn parse_hex(s: &str) -> Result<u16, String> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid number '{}'", s))
+}
+
+/// Parse a hexadecimal number that has to fit in a byte
+fn parse_byte(s: &str) -> Result<u8, String> {
+    let val = parse_hex(s)?;
+
+    u8::try_from(val).map_err(|_| format!("value '{}' doesn't fit in a byte", s))
+}
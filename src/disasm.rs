@@ -0,0 +1,207 @@
+use crate::instruction::Instruction;
+use crate::memory::Memory;
+use crate::quirks::Quirks;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+// Static disassembly of a ROM. Code is found by following every path from the entry point:
+// jumps continue at their target, calls at both the target and the following instruction,
+// skips at both following instructions, and RET, EXIT and computed jumps end a path. Anything
+// never reached this way is data, which keeps sprites from being listed as nonsense code.
+
+/// Address programs are loaded at and start executing from
+pub const ENTRY_POINT: u16 = 0x200;
+
+/// Disassemble the instruction at `addr` as it runs with `quirks`, unknown opcodes are shown as data
+pub fn disassemble_at(memory: &Memory, quirks: &Quirks, addr: usize) -> (String, usize) {
+    let inst = memory.read_inst(addr).unwrap_or_default();
+    let next = memory.read_inst(addr + 2).unwrap_or_default();
+
+    match Instruction::decode(inst, next) {
+        Some(instruction) => (instruction.display_with(quirks), instruction.size()),
+        None => (format!("dw {:#06x}", inst), 2),
+    }
+}
+
+/// Result of analyzing a ROM
+pub struct Disassembly<'a> {
+    rom: &'a [u8],
+
+    /// Address of the first ROM byte
+    origin: u16,
+
+    /// Addresses where a reachable instruction starts
+    code: BTreeMap<u16, Instruction>,
+
+    /// Names given to jump, call and I targets
+    labels: BTreeMap<u16, String>,
+}
+
+impl<'a> Disassembly<'a> {
+    /// Analyze `rom` loaded at `origin`, following the code from `ENTRY_POINT`
+    pub fn new(rom: &'a [u8], origin: u16) -> Self {
+        let mut dis = Disassembly {
+            rom,
+            origin,
+            code: BTreeMap::new(),
+            labels: BTreeMap::new(),
+        };
+
+        dis.trace(ENTRY_POINT);
+        dis.label();
+
+        dis
+    }
+
+    /// Byte of the ROM at `addr`, if it is part of the ROM
+    fn byte(&self, addr: u16) -> Option<u8> {
+        let offset = addr.checked_sub(self.origin)?;
+
+        self.rom.get(offset as usize).copied()
+    }
+
+    /// Instruction at `addr`, if all of its bytes are part of the ROM
+    fn decode(&self, addr: u16) -> Option<Instruction> {
+        let word = |a: u16| Some(((self.byte(a)? as u16) << 8) | self.byte(a.wrapping_add(1))? as u16);
+
+        let opcode = word(addr)?;
+        let next = if opcode == 0xf000 { word(addr.wrapping_add(2))? } else { 0 };
+
+        Instruction::decode(opcode, next)
+    }
+
+    /// Mark everything reachable from `entry` as code
+    fn trace(&mut self, entry: u16) {
+        let mut pending = vec![entry];
+
+        while let Some(addr) = pending.pop() {
+            if self.code.contains_key(&addr) {
+                continue;
+            }
+
+            let instruction = match self.decode(addr) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            self.code.insert(addr, instruction);
+
+            let next = addr.wrapping_add(instruction.size() as u16);
+
+            match instruction {
+                Instruction::Jump(target) => pending.push(target),
+                Instruction::Call(target) => {
+                    pending.push(target);
+                    pending.push(next);
+                },
+                Instruction::Ret | Instruction::Exit | Instruction::JumpOffset(..) => (),
+                _ if instruction.is_skip() => {
+                    pending.push(next);
+
+                    // The skipped instruction may be the 4 byte F000 nnnn
+                    let skipped = self.decode(next).map_or(2, |i| i.size() as u16);
+                    pending.push(next.wrapping_add(skipped));
+                },
+                _ => pending.push(next),
+            }
+        }
+    }
+
+    /// Name the targets of jumps, calls and I loads
+    fn label(&mut self) {
+        let mut targets = BTreeSet::new();
+        let mut data = BTreeSet::new();
+
+        for instruction in self.code.values() {
+            match *instruction {
+                Instruction::Jump(addr) | Instruction::Call(addr) => {
+                    targets.insert(addr);
+                },
+                Instruction::LoadI(addr) | Instruction::LoadILong(addr) | Instruction::JumpOffset(_, addr) => {
+                    data.insert(addr);
+                },
+                _ => (),
+            }
+        }
+
+        for addr in data {
+            if self.byte(addr).is_some() {
+                self.labels.insert(addr, format!("data_{:03x}", addr));
+            }
+        }
+
+        // Code labels win over data labels for the same address
+        for addr in targets {
+            self.labels.insert(addr, format!("label_{:03x}", addr));
+        }
+    }
+
+    /// Whether a reachable instruction starts at `addr`
+    pub fn is_code(&self, addr: u16) -> bool {
+        self.code.contains_key(&addr)
+    }
+
+    /// Label for `addr`, if anything refers to it
+    pub fn label_at(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    /// Full listing, each line shows the address and raw bytes in a trailing comment
+    pub fn listing(&self) -> String {
+        let mut out = String::new();
+        let end = self.origin as usize + self.rom.len();
+        let mut addr = self.origin as usize;
+
+        // Labels that point outside the ROM, e.g. into the font, can not be placed in the listing
+        for (target, name) in self.labels.iter() {
+            if self.byte(*target).is_none() || !self.placeable(*target) {
+                out.push_str(&format!("{} = {:#05x}\n", name, target));
+            }
+        }
+
+        while addr < end {
+            let a = addr as u16;
+
+            if let Some(name) = self.label_at(a) {
+                out.push_str(&format!("\n{}:\n", name));
+            }
+
+            if let Some(instruction) = self.code.get(&a) {
+                let size = instruction.size();
+                let text = instruction.mnemonic(|target| self.labels.get(&target).cloned());
+                out.push_str(&line(&text, a, &self.rom[addr - self.origin as usize..addr - self.origin as usize + size]));
+                addr += size;
+                continue;
+            }
+
+            // Data runs up to the next instruction or label, 8 bytes per line
+            let mut len = 1;
+            while addr + len < end && len < 8 {
+                let b = (addr + len) as u16;
+                if self.code.contains_key(&b) || self.labels.contains_key(&b) {
+                    break;
+                }
+                len += 1;
+            }
+
+            let bytes = &self.rom[addr - self.origin as usize..addr - self.origin as usize + len];
+            let values: Vec<String> = bytes.iter().map(|b| format!("{:#04x}", b)).collect();
+            out.push_str(&line(&format!("db {}", values.join(", ")), a, bytes));
+            addr += len;
+        }
+
+        out
+    }
+
+    /// Whether the listing reaches `addr` at the start of a line
+    fn placeable(&self, addr: u16) -> bool {
+        // Only a target inside an instruction is skipped over, data lines stop at every label
+        !self.code.iter().any(|(start, i)| *start < addr && addr < start.wrapping_add(i.size() as u16))
+    }
+}
+
+/// Format one listing line
+fn line(text: &str, addr: u16, bytes: &[u8]) -> String {
+    let raw: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    format!("    {:<32}; {:04x}: {}\n", text, addr, raw.join(" "))
+}
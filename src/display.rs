@@ -1,5 +1,3 @@
-extern crate sdl2;
-
 // The original implementation of the Chip-8 language used a 64x32-pixel monochrome display with this format:
 // ___________________
 // |                 |
@@ -7,127 +5,125 @@ extern crate sdl2;
 // |                 |
 // | (0,31)	 (63,31) |
 // |_________________|
-
-// The sound timer is active whenever the sound timer register (ST) is non-zero. 
-// This timer also decrements at a rate of 60Hz, however, as long as ST's value is greater than zero, the Chip-8 buzzer will sound. 
-// When ST reaches zero, the sound timer deactivates.
-
-// The audio code is essentially the rust sdl2 audio squarewave example
-pub struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
-
-use sdl2::{Sdl, render::Canvas, video::Window, pixels::Color, rect::Rect};
-use sdl2::audio::{AudioCallback, AudioSpecDesired, AudioDevice};
-use sdl2::pixels;
+//
+// SUPER-CHIP adds a 128x64-pixel high resolution mode, selected with 00FF and left with 00FE.
+// Pixels are stored row by row using the width of the current mode.
+//
+// XO-CHIP adds a second bitplane. Each pixel holds one bit per plane, bit 0 for plane 1 and bit 1 for plane 2,
+// giving four colors. Fn01 selects which planes are affected by clearing, drawing and scrolling.
 
 pub const SCREEN_HEIGHT: u16 = 32;
 pub const SCREEN_WIDTH: u16 = 64;
-const SCREEN_SCALE: u16 = 16;
+
+pub const HIRES_SCREEN_HEIGHT: u16 = 64;
+pub const HIRES_SCREEN_WIDTH: u16 = 128;
+
+/// Number of XO-CHIP bitplanes
+pub const PLANES: u8 = 2;
 
 /// Chip-8 display memory
+#[derive(Debug, Clone, Copy)]
 pub struct Display {
-    /// 64x32 pixel memory region
-    pub memory: [u8; 2048],
+    /// 64x32 or 128x64 pixel memory region
+    pub memory: [u8; 8192],
 
-    /// SDL2 Context
-    pub context: Sdl,
+    /// SUPER-CHIP high resolution mode
+    pub hires: bool,
 
-    /// SDL2 Canvas
-    pub canvas: Canvas<Window>,
-
-    /// SDL2 Audio device
-    pub audio_device: AudioDevice<SquareWave>,
+    /// XO-CHIP bitmask of the planes selected by Fn01
+    pub planes: u8,
 }
 
-impl AudioCallback for SquareWave {
-    type Channel = f32;
-
-    fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Display {
     pub fn new() -> Self {
-
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsys = sdl_context.video().unwrap();
-        let audio_subsys = sdl_context.audio().unwrap();
-
-        let desired_spec = AudioSpecDesired {
-            freq: Some(44_100),
-            channels: Some(1), // mono
-            samples: None,     // default sample size
-        };
-
-        let audio_device = audio_subsys.open_playback(None, &desired_spec, |spec| {
-            // initialize the audio callback
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.10,
-            }
-        }).unwrap();
-
-        let window = video_subsys
-            .window(
-                "CHIP-8 Emulator",
-                SCREEN_WIDTH as u32 * SCREEN_SCALE as u32,
-                SCREEN_HEIGHT as u32 * SCREEN_SCALE as u32,
-            )
-            .position_centered()
-            .opengl()
-            .build()
-            .map_err(|e| e.to_string()).unwrap();
-    
-        let mut canvas = window.into_canvas().build().unwrap();
-
-        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.present();
-    
         Display {
-            memory: [0; 2048],
-            context: sdl_context,
-            canvas: canvas,
-            audio_device: audio_device,
+            memory: [0; 8192],
+            hires: false,
+            planes: 1,
         }
     }
 
+    /// Width in pixels of the current mode
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_SCREEN_WIDTH as usize } else { SCREEN_WIDTH as usize }
+    }
+
+    /// Height in pixels of the current mode
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_SCREEN_HEIGHT as usize } else { SCREEN_HEIGHT as usize }
+    }
+
+    /// Pixels of the current mode
+    pub fn pixels(&self) -> &[u8] {
+        &self.memory[..self.width() * self.height()]
+    }
+
+    /// Clear every plane
     pub fn clear(&mut self) {
-        for i in 0..self.memory.len() {
-            self.memory[i] = 0;
+        for px in self.memory.iter_mut() {
+            *px = 0;
         }
     }
 
-    pub fn update(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-    
-        for idx in 0..self.memory.len() {
-            if self.memory[idx] != 0 {
-                self.canvas.fill_rect(Rect::new(
-                    (SCREEN_SCALE as u32 * (idx as u32 & 0x3f)) as i32,
-                    (SCREEN_SCALE as u32 * (idx as u32 >> 6)) as i32,
-                    SCREEN_SCALE as u32,
-                    SCREEN_SCALE as u32,
-                )).unwrap();
-            }
+    /// Clear the selected planes
+    pub fn clear_planes(&mut self) {
+        let planes = self.planes;
+
+        for px in self.memory.iter_mut() {
+            *px &= !planes;
         }
-    
-        self.canvas.present();
+    }
+
+    /// Switch between the 64x32 and 128x64 modes, clearing the screen
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear();
+    }
+
+    /// Scroll the screen down by `n` rows
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll(0, n as isize);
+    }
+
+    /// Scroll the screen up by `n` rows
+    pub fn scroll_up(&mut self, n: usize) {
+        self.scroll(0, -(n as isize));
+    }
 
-    }    
-}
\ No newline at end of file
+    /// Scroll the screen right by `n` columns
+    pub fn scroll_right(&mut self, n: usize) {
+        self.scroll(n as isize, 0);
+    }
+
+    /// Scroll the screen left by `n` columns
+    pub fn scroll_left(&mut self, n: usize) {
+        self.scroll(-(n as isize), 0);
+    }
+
+    /// Move the selected planes by (`dx`, `dy`), filling the uncovered area with blank pixels
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let planes = self.planes;
+        let old = self.memory;
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let src = if sx >= 0 && sx < width && sy >= 0 && sy < height {
+                    old[(sx + sy * width) as usize] & planes
+                } else {
+                    0
+                };
+
+                let idx = (x + y * width) as usize;
+                self.memory[idx] = (self.memory[idx] & !planes) | src;
+            }
+        }
+    }
+}
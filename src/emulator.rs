@@ -1,25 +1,26 @@
-extern crate sdl2;
-
-use crate::memory::{Memory};
-use crate::display::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::memory::{Memory, FONT_OFFSET, BIG_FONT_OFFSET};
+use crate::display::{Display, PLANES};
 use crate::input::{Input};
 use crate::cpu::{Register};
-
-use std::io;
-use std::io::prelude::*; 
+use crate::error::{Chip8Error};
+use crate::quirks::{Quirks, LoadStore};
+use crate::audio::{Audio};
+use crate::rng::{Rng};
+use crate::instruction::{Instruction};
 
 use std::fmt;
-use rand::Rng;
-use std::time::{Duration};
 
-use sdl2::{event::Event, keyboard::Keycode, EventPump, audio::AudioStatus};
+/// Default instruction clock in Hz
+pub const CLOCK_HZ: u32 = 600;
 
-/// Enable Debug printing of disassembly during execution
-const DEBUG_PRINT: bool = true;
+/// Rate of the delay and sound timers, and of frames in general
+pub const FRAME_HZ: u32 = 60;
+
+/// Default instructions executed per frame
+pub const CYCLES_PER_FRAME: u32 = CLOCK_HZ / FRAME_HZ;
 
-/// Enable single step debugging
-const DEBUG_STEP: bool = false;
 /// State of the emulated system
+#[derive(Clone)]
 pub struct Emulator {
     /// Memory mapping for the emulator
     pub memory: Memory,
@@ -32,9 +33,36 @@ pub struct Emulator {
 
     /// Input
     pub input: Input,
+
+    /// XO-CHIP audio pattern and pitch
+    pub audio: Audio,
+
+    /// Random number generator for Cxkk
+    pub rng: Rng,
     
-    /// Clock tick count
-    pub tick_cnt: u8,
+    /// Instructions executed in the current frame
+    pub tick_cnt: u32,
+
+    /// Instructions per frame, the timers tick once at the end of every frame
+    pub cycles_per_frame: u32,
+
+    /// A sprite has been drawn since the last timer tick
+    pub frame_drawn: bool,
+
+    /// The program executed 00FD - EXIT
+    pub exited: bool,
+
+    /// Key pressed while Fx0A waits, it completes once the key is released
+    pub key_wait: Option<u8>,
+
+    /// Interpreter behavior for instructions that differ between platforms
+    pub quirks: Quirks,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Emulator {
@@ -44,816 +72,677 @@ impl Emulator {
             registers: Register::new(),
             display: Display::new(),
             input: Input::new(),
+            audio: Audio::new(),
+            rng: Rng::from_entropy(),
             tick_cnt: 0,
+            cycles_per_frame: CYCLES_PER_FRAME,
+            frame_drawn: false,
+            exited: false,
+            key_wait: None,
+            quirks: Quirks::default(),
         }
     }
 
-    pub fn run(&mut self) {
+    /// Fetch and execute the instruction at PC, then update the timers
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.exited {
+            return Ok(());
+        }
+
+        let pc = self.registers.pc;
+        let inst: u16 = self.memory.read_inst(pc as usize)?;
+
+        self.execute_instruction(inst)?;
+
+        self.update_timers();
+
+        Ok(())
+    }
+    
+    /// Run instructions until the current frame ends or the program exits
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
         loop {
-            let emu_exit = self.enter_emu().expect("Failed to execute emulator <enter_emu>!");
+            self.step()?;
 
-            match emu_exit {
-                _ => { break }
+            if self.tick_cnt == 0 || self.exited {
+                return Ok(());
             }
         }
     }
 
-    fn enter_emu(&mut self) -> Option<()> {
-        let mut event_pump = self.display.context.event_pump().unwrap();
-
-        while self.process_events(&mut event_pump) {
-            // Fetch the current instruction
-            let pc = self.registers.pc;
-            let inst: u16 = self.memory.read_inst(pc as usize);
-
-            self.execute_instruction(inst);
+    /// Count an executed instruction, the last one of a frame ticks the timers
+    fn update_timers(&mut self) {
+        self.tick_cnt += 1;
+        if self.tick_cnt < self.cycles_per_frame {
+            return;
+        }
 
-            self.update_timers();
+        if self.registers.dt > 0 {
+            self.registers.dt -= 1;
+        }
 
-            self.display.update();
+        if self.registers.st > 0 {
+            self.registers.st -= 1;
+        }
 
-            // maybe move this to an audio update function
-            if self.registers.st > 0 {
-                if self.display.audio_device.status() == AudioStatus::Paused {
-                    self.display.audio_device.resume();
-                }
-            } else {
-                if self.display.audio_device.status() == AudioStatus::Playing {
-                    self.display.audio_device.pause();
-                }
-            }
+        self.rng.tick_frame();
+        self.tick_cnt = 0;
+        self.frame_drawn = false;
+    }
 
-            // CPU is executing at 1/600th of a second, we update the timers every 10th cycle
-            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
+    /// Fx55/Fx65 leave I pointing past the copied registers on some interpreters
+    fn increment_i_after_load_store(&mut self, reg: u8) {
+        match self.quirks.load_store {
+            LoadStore::Unchanged => (),
+            LoadStore::IncrementX => self.registers.i = self.registers.i.wrapping_add(reg as u16),
+            LoadStore::IncrementX1 => self.registers.i = self.registers.i.wrapping_add(reg as u16 + 1),
         }
+    }
+
+    /// Store the result of an 8xyn instruction in Vx, then its flag in VF
+    ///
+    /// The flag is written last so it wins when x is F, both are computed from the original values.
+    fn set_result_and_flag(&mut self, reg: u8, result: u8, flag: u8) -> Result<(), Chip8Error> {
+        self.registers.reg_write(reg, result)?;
+        self.registers.vf = flag;
 
-        Some(())
+        Ok(())
     }
-    
-    fn update_timers(&mut self) {
 
-        if self.tick_cnt == 10 {
-            if self.registers.dt > 0 {
-                self.registers.dt -= 1;
-            }
-    
-            if self.registers.st > 0 {
-                self.registers.st -= 1;
-            }
+    /// Skip the next instruction, stepping over both halves of the 4 byte XO-CHIP F000 nnnn
+    fn skip_next(&mut self) -> Result<(), Chip8Error> {
+        let next = self.memory.read_inst(self.registers.pc.wrapping_add(2) as usize)?;
 
-            self.tick_cnt = 0;
-        } else {
-            self.tick_cnt += 1;
-        }
+        self.registers.pc = self.registers.pc.wrapping_add(if next == 0xf000 { 4 } else { 2 });
 
+        Ok(())
     }
 
-    fn process_events(&mut self, event_pump: &mut EventPump) -> bool {
-        for event in event_pump.poll_iter() {
+    pub fn execute_instruction(&mut self, inst: u16) -> Result<(), Chip8Error> {
+        // Only F000 nnnn reads past its first word, fetch the second half for it alone
+        let next = if inst == 0xf000 { self.memory.read_inst(self.registers.pc.wrapping_add(2) as usize)? } else { 0 };
 
-            match event {
-                Event::Quit { .. } => { return false },
-    
-                Event::KeyDown {
-                    keycode: Some(kc), ..
-                } => self.input.set(
-                match kc {
-                        //  1	2	3	C
-                        //  4	5	6	D
-                        //  7	8	9	E
-                        //  A	0	B	F
-
-                        Keycode::Num1   => 0x01,
-                        Keycode::Num2   => 0x02,
-                        Keycode::Num3   => 0x03,
-                        Keycode::Num4   => 0x0C,
-                        Keycode::Q      => 0x04,
-                        Keycode::W      => 0x05,
-                        Keycode::E      => 0x06,
-                        Keycode::R      => 0x0D,
-                        Keycode::A      => 0x07,
-                        Keycode::S      => 0x08,
-                        Keycode::D      => 0x09,
-                        Keycode::F      => 0x0E,
-                        Keycode::Z      => 0x0A,
-                        Keycode::X      => 0x00,
-                        Keycode::C      => 0x0B,
-                        Keycode::V      => 0x0F,
-                        _ => { break },
-                    }, true),
-    
-                Event::KeyUp {
-                    keycode: Some(kc), ..
-                } => self.input.set(
-                match kc {
-                        Keycode::Num1   => 0x01,
-                        Keycode::Num2   => 0x02,
-                        Keycode::Num3   => 0x03,
-                        Keycode::Num4   => 0x0C,
-                        Keycode::Q      => 0x04,
-                        Keycode::W      => 0x05,
-                        Keycode::E      => 0x06,
-                        Keycode::R      => 0x0D,
-                        Keycode::A      => 0x07,
-                        Keycode::S      => 0x08,
-                        Keycode::D      => 0x09,
-                        Keycode::F      => 0x0E,
-                        Keycode::Z      => 0x0A,
-                        Keycode::X      => 0x00,
-                        Keycode::C      => 0x0B,
-                        Keycode::V      => 0x0F,
-                        _ => { break },
-                    }, false),
-            _ => (),
-            }
-        }
+        let instruction = match Instruction::decode(inst, next) {
+            Some(instruction) => instruction,
+            None => return Err(Chip8Error::UnknownOpcode { pc: self.registers.pc, opcode: inst }),
+        };
 
-        true
-    }
-    
-    fn execute_instruction(&mut self, inst: u16) -> Option<()> {
-        match (inst >> 12) & 0xff {
-            0x0 => {
-                match inst & 0xff
-                {
-                    0x0e0 => {
-                        // 00E0 - CLS
-                        // Clear the display.
-
-                        if DEBUG_PRINT {
-                            println!("cls");
-                        }
+        match instruction {
+            Instruction::Sys(_) => {
+                // 0nnn - SYS addr
+                // Jump to a machine code routine at nnn.
+                // This instruction is only used on the old computers on which Chip-8 was originally implemented. It is ignored by modern interpreters.
+            },
+            Instruction::Cls => {
+                // 00E0 - CLS
+                // Clear the display.
 
-                        self.display.clear();
+                // XO-CHIP only clears the planes selected by Fn01.
 
-                    },
-                    0xee => {
-                        // 00EE - RET
-                        // Return from a subroutine.
-                        // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
-    
-                        if DEBUG_PRINT {
-                            println!("ret");
-                        }
+                self.display.clear_planes();
 
-                        self.registers.pc = self.memory.pop();
+            },
+            Instruction::Ret => {
+                // 00EE - RET
+                // Return from a subroutine.
+                // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
+
+                self.registers.pc = self.memory.pop()?;
+                self.registers.sp = self.memory.sp as u16;
 
-                    },
-                    _ => {
-                        unimplemented!("Unknown instruction: {:#04x}", inst);
-                    }
-                }
             },
-            0x1 => {
-                // 1nnn - JP addr
-                // Jump to location nnn.                
-                // The interpreter sets the program counter to nnn.
+            Instruction::ScrollUp(n) => {
+                // 00Dn - SCU nibble (XO-CHIP)
+                // Scroll the selected planes up by n pixels.
 
-                let addr = inst & 0xfff;
+                self.display.scroll_up(n as usize);
+            },
+            Instruction::ScrollDown(n) => {
+                // 00Cn - SCD nibble (SUPER-CHIP)
+                // Scroll the display down by n pixels.
 
-                if DEBUG_PRINT {
-                    println!("jp {:#04x}", addr);
-                }
+                self.display.scroll_down(n as usize);
+            },
+            Instruction::ScrollRight => {
+                // 00FB - SCR (SUPER-CHIP)
+                // Scroll the display right by 4 pixels.
+
+                self.display.scroll_right(4);
+            },
+            Instruction::ScrollLeft => {
+                // 00FC - SCL (SUPER-CHIP)
+                // Scroll the display left by 4 pixels.
+
+                self.display.scroll_left(4);
+            },
+            Instruction::Exit => {
+                // 00FD - EXIT (SUPER-CHIP)
+                // Exit the interpreter.
+
+                self.exited = true;
+                return Ok(());
+            },
+            Instruction::Low => {
+                // 00FE - LOW (SUPER-CHIP)
+                // Disable high resolution mode, returning to 64x32.
+
+                self.display.set_hires(false);
+            },
+            Instruction::High => {
+                // 00FF - HIGH (SUPER-CHIP)
+                // Enable 128x64 high resolution mode.
+
+                self.display.set_hires(true);
+            },
+            Instruction::Jump(addr) => {
+                // 1nnn - JP addr
+                // Jump to location nnn.
+                // The interpreter sets the program counter to nnn.
 
                 self.registers.pc = addr;
-                self.registers.pc -= 2; // adjusting here due to the auto pc increase at bottom
+                self.registers.pc = self.registers.pc.wrapping_sub(2); // adjusting here due to the auto pc increase at bottom
             },
-            0x2 => {
+            Instruction::Call(addr) => {
                 // 2nnn - CALL addr
                 // Call subroutine at nnn.
                 // The interpreter increments the stack pointer, then puts the current PC on the top of the stack. The PC is then set to nnn.
-                
-                let addr = inst & 0xfff;
-
-                if DEBUG_PRINT {
-                    println!("call {:#04x}", addr);
-                }
 
-                self.memory.push(self.registers.pc);
+                self.memory.push(self.registers.pc)?;
                 self.registers.sp = self.memory.sp as u16;
                 self.registers.pc = addr;
-                self.registers.pc -= 2; // adjusting here due to the auto pc increase at bottom
+                self.registers.pc = self.registers.pc.wrapping_sub(2); // adjusting here due to the auto pc increase at bottom
             },
-            0x3 => {
+            Instruction::SkipEqByte(reg, val) => {
                 // 3xkk - SE Vx, byte
                 // Skip next instruction if Vx = kk.
                 // The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
 
-                let reg = ((inst >> 8) & 0xf) as u8;
-                let val = (inst & 0xff) as u8;
-
-                if DEBUG_PRINT {
-                    println!("se v{:x}, {:#02x}", reg, val);
-                }
-
-                if self.registers.reg_read(reg) == val {
-                    self.registers.pc += 2;
+                if self.registers.reg_read(reg)? == val {
+                    self.skip_next()?;
                 }
             },
-            0x4 => {
+            Instruction::SkipNeByte(reg, val) => {
                 // 4xkk - SNE Vx, byte
                 // Skip next instruction if Vx != kk.
                 // The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
-                let reg = ((inst >> 8) & 0xf) as u8;
-                let val = (inst & 0xff) as u8;
 
-                if DEBUG_PRINT {
-                    println!("sne v{:x}, {:#02x}", reg, val);
-                }
-
-                if self.registers.reg_read(reg) != val {
-                    self.registers.pc += 2;
+                if self.registers.reg_read(reg)? != val {
+                    self.skip_next()?;
                 }
             },
-            0x5 => {
+            Instruction::SkipEqReg(regx, regy) => {
                 // 5xy0 - SE Vx, Vy
                 // Skip next instruction if Vx = Vy.
                 // The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
-                let regx = ((inst >> 8) & 0xf) as u8;
-                let regy = ((inst >> 4) & 0xf) as u8;
 
-                if DEBUG_PRINT {
-                    println!("se v{:x}, v{:x}", regx, regy);
+                if self.registers.reg_read(regx)? == self.registers.reg_read(regy)? {
+                    self.skip_next()?;
                 }
+            },
+            Instruction::StoreRange(regx, regy) => {
+                // 5xy2 - LD [I], Vx - Vy (XO-CHIP)
+                // Store registers Vx through Vy in memory starting at location I, I is left unchanged.
+                // The range is walked backwards when x > y.
 
-                if self.registers.reg_read(regx) == self.registers.reg_read(regy) {
-                    self.registers.pc += 2;
+                for (offset, reg) in register_range(regx, regy).enumerate() {
+                    self.memory.write(self.registers.i as usize + offset, self.registers.reg_read(reg)?)?;
                 }
             },
-            0x6 => {
+            Instruction::LoadRange(regx, regy) => {
+                // 5xy3 - LD Vx - Vy, [I] (XO-CHIP)
+                // Read registers Vx through Vy from memory starting at location I, I is left unchanged.
+                // The range is walked backwards when x > y.
+
+                for (offset, reg) in register_range(regx, regy).enumerate() {
+                    self.registers.reg_write(reg, self.memory.read(self.registers.i as usize + offset)?)?;
+                }
+            },
+            Instruction::LoadByte(reg, val) => {
                 // 6xkk - LD Vx, byte
                 // Set Vx = kk.
                 // The interpreter puts the value kk into register Vx.
 
-                let reg = ((inst >> 8) & 0xf) as u8;
-                let val = (inst & 0xff) as u8;
-
-                if DEBUG_PRINT {
-                    println!("ld v{:x}, {:#02x}", reg, val);
-                }
-
-                self.registers.reg_write(reg, val);
+                self.registers.reg_write(reg, val)?;
             },
-            0x7 => {
+            Instruction::AddByte(reg, val) => {
                 // 7xkk - ADD Vx, byte
                 // Set Vx = Vx + kk.
                 // Adds the value kk to the value of register Vx, then stores the result in Vx.
 
-                let reg = ((inst >> 8) & 0xf) as u8;
-                let val = (inst & 0xff) as u8;
-
-                if DEBUG_PRINT {
-                    println!("add v{:x}, {:#02x}", reg, val);
-                }
+                let x = self.registers.reg_read(reg)?;
 
-                let x = self.registers.reg_read(reg);
-                
                 // assuming wraps but not certain
-                self.registers.reg_write(reg, x.wrapping_add(val));
+                self.registers.reg_write(reg, x.wrapping_add(val))?;
             },
-            0x8 => {
+            Instruction::LoadReg(regx, regy) => {
                 // 8xy0 - LD Vx, Vy
+                // Set Vx = Vy.
+                // Stores the value of register Vy in register Vx.
+
+                let y = self.registers.reg_read(regy)?;
+                self.registers.reg_write(regx, y)?;
+            },
+            Instruction::Or(regx, regy) => {
                 // 8xy1 - OR Vx, Vy
-                // 8xy2 - AND Vx, Vy
-                // 8xy3 - XOR Vx, Vy
-                // 8xy4 - ADD Vx, Vy
-                // 8xy5 - SUB Vx, Vy
-                // 8xy6 - SHR Vx {, Vy}
-                // 8xy7 - SUBN Vx, Vy
-                // 8xyE - SHL Vx {, Vy}
-                match inst & 0xf {
-                    0x0 => {
-                        // 8xy0 - LD Vx, Vy
-                        // Set Vx = Vy.
-                        // Stores the value of register Vy in register Vx.
-
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
-                        
-                        if DEBUG_PRINT {
-                            println!("ld v{:x}, v{:x}", regx, regy);
-                        }        
-
-                        let y = self.registers.reg_read(regy);
-                        self.registers.reg_write(regx, y);
-                    },
-                    0x1 => {
-                        // 8xy1 - OR Vx, Vy
-                        // Set Vx = Vx OR Vy.
-                        // Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx. 
-                        // A bitwise OR compares the corrseponding bits from two values, and if either bit is 1, then the same bit in the result is also 1. Otherwise, it is 0.
-                        
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("or v{:x}, v{:x}", regx, regy);
-                        }        
-
-                        let x = self.registers.reg_read(regx);
-                        let y = self.registers.reg_read(regy);
-
-                        self.registers.reg_write(regx, x | y);
-                        
-                    },
-                    0x2 => {
-                        // 8xy2 - AND Vx, Vy
-                        // Set Vx = Vx AND Vy.                        
-                        // Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx. 
-                        // A bitwise AND compares the corrseponding bits from two values, and if both bits are 1, then the same bit in the result is also 1. Otherwise, it is 0.
+                // Set Vx = Vx OR Vy.
+                // Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
+                // A bitwise OR compares the corrseponding bits from two values, and if either bit is 1, then the same bit in the result is also 1. Otherwise, it is 0.
 
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
+                let x = self.registers.reg_read(regx)?;
+                let y = self.registers.reg_read(regy)?;
 
-                        if DEBUG_PRINT {
-                            println!("and v{:x}, v{:x}", regx, regy);
-                        }        
+                self.registers.reg_write(regx, x | y)?;
 
-                        let x = self.registers.reg_read(regx);
-                        let y = self.registers.reg_read(regy);
+                if self.quirks.vf_reset {
+                    self.registers.vf = 0;
+                }
 
-                        self.registers.reg_write(regx, x & y);
+            },
+            Instruction::And(regx, regy) => {
+                // 8xy2 - AND Vx, Vy
+                // Set Vx = Vx AND Vy.
+                // Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
+                // A bitwise AND compares the corrseponding bits from two values, and if both bits are 1, then the same bit in the result is also 1. Otherwise, it is 0.
 
-                    },
-                    0x3 => {
-                        // 8xy3 - XOR Vx, Vy
-                        // Set Vx = Vx XOR Vy.
-                        // Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx. 
-                        //An exclusive OR compares the corrseponding bits from two values, and if the bits are not both the same, then the corresponding bit in the result is set to 1. Otherwise, it is 0.
-                        
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
+                let x = self.registers.reg_read(regx)?;
+                let y = self.registers.reg_read(regy)?;
+
+                self.registers.reg_write(regx, x & y)?;
+
+                if self.quirks.vf_reset {
+                    self.registers.vf = 0;
+                }
 
-                        if DEBUG_PRINT {
-                            println!("xor v{:x}, v{:x}", regx, regy);
-                        }        
+            },
+            Instruction::Xor(regx, regy) => {
+                // 8xy3 - XOR Vx, Vy
+                // Set Vx = Vx XOR Vy.
+                // Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx.
+                //An exclusive OR compares the corrseponding bits from two values, and if the bits are not both the same, then the corresponding bit in the result is set to 1. Otherwise, it is 0.
 
-                        let x = self.registers.reg_read(regx);
-                        let y = self.registers.reg_read(regy);
+                let x = self.registers.reg_read(regx)?;
+                let y = self.registers.reg_read(regy)?;
 
-                        self.registers.reg_write(regx, x ^ y);
+                self.registers.reg_write(regx, x ^ y)?;
 
-                    },
-                    0x4 => {
-                        // 8xy4 - ADD Vx, Vy
-                        // Set Vx = Vx + Vy, set VF = carry.
-                        // The values of Vx and Vy are added together. If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits of the result are kept, and stored in Vx.
-                        
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("add v{:x}, v{:x}", regx, regy);
-                        } 
-
-                        let x = self.registers.reg_read(regx);
-                        let y = self.registers.reg_read(regy);
-
-                        let res: u16 = x as u16 + y as u16;
-                        if res > 255 {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
+                if self.quirks.vf_reset {
+                    self.registers.vf = 0;
+                }
 
-                        self.registers.reg_write(regx, res as u8);
-                    },
-                    0x5 => {
-                        // 8xy5 - SUB Vx, Vy
-                        // Set Vx = Vx - Vy, set VF = NOT borrow.                        
-                        // If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
+            },
+            Instruction::AddReg(regx, regy) => {
+                // 8xy4 - ADD Vx, Vy
+                // Set Vx = Vx + Vy, set VF = carry.
+                // The values of Vx and Vy are added together. If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits of the result are kept, and stored in Vx.
 
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
+                let x = self.registers.reg_read(regx)?;
+                let y = self.registers.reg_read(regy)?;
 
-                        if DEBUG_PRINT {
-                            println!("sub v{:x}, v{:x}", regx, regy);
-                        } 
+                let (res, carry) = x.overflowing_add(y);
 
-                        let x = self.registers.reg_read(regx);
-                        let y = self.registers.reg_read(regy);
+                self.set_result_and_flag(regx, res, carry as u8)?;
+            },
+            Instruction::Sub(regx, regy) => {
+                // 8xy5 - SUB Vx, Vy
+                // Set Vx = Vx - Vy, set VF = NOT borrow.
+                // If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
 
-                        if x > y {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
+                let x = self.registers.reg_read(regx)?;
+                let y = self.registers.reg_read(regy)?;
 
-                        // assuming this is supposed to wrap, but not certain
-                        self.registers.reg_write(regx, x.wrapping_sub(y));
-                    },
-                    0x6 => {
-                        // 8xy6 - SHR Vx {, Vy}
-                        // Set Vx = Vx SHR 1.
-                        // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
+                // Equal values do not borrow
+                self.set_result_and_flag(regx, x.wrapping_sub(y), (x >= y) as u8)?;
+            },
+            Instruction::ShiftRight(regx, regy) => {
+                // 8xy6 - SHR Vx {, Vy}
+                // Set Vx = Vx SHR 1.
+                // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
 
-                        // Not sure what is supposed to happen with Vy here???
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
+                // The COSMAC VIP shifts Vy into Vx, later interpreters shift Vx in place
+                let x = if self.quirks.shift_vy {
+                    self.registers.reg_read(regy)?
+                } else {
+                    self.registers.reg_read(regx)?
+                };
 
-                        if DEBUG_PRINT {
-                            println!("shr v{:x} {{, v{:x}}}", regx, regy);
-                        } 
+                self.set_result_and_flag(regx, x >> 1, x & 0x1)?;
 
-                        let x = self.registers.reg_read(regx);
-                        let _y = self.registers.reg_read(regy);
+            },
+            Instruction::SubN(regx, regy) => {
+                // 8xy7 - SUBN Vx, Vy
+                // Set Vx = Vy - Vx, set VF = NOT borrow.
+                // If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
 
-                        self.registers.vf = x & 0x1;
-                        self.registers.reg_write(regx, x >> 1);
+                let x = self.registers.reg_read(regx)?;
+                let y = self.registers.reg_read(regy)?;
 
-                    },
-                    0x7 => {
-                        // 8xy7 - SUBN Vx, Vy
-                        // Set Vx = Vy - Vx, set VF = NOT borrow.
-                        // If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
-                        
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("subn v{:x}, v{:x}", regx, regy);
-                        } 
-
-                        let x = self.registers.reg_read(regx);
-                        let y = self.registers.reg_read(regy);
-
-                        if y > x {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
+                // Equal values do not borrow
+                self.set_result_and_flag(regx, y.wrapping_sub(x), (y >= x) as u8)?;
+
+            },
+            Instruction::ShiftLeft(regx, regy) => {
+                // 8xyE - SHL Vx {, Vy}
+                // Set Vx = Vx SHL 1.
+                // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
 
-                        // assuming this is supposed to wrap, but not certain
-                        self.registers.reg_write(regx, y.wrapping_sub(x));
+                // The COSMAC VIP shifts Vy into Vx, later interpreters shift Vx in place
+                let x = if self.quirks.shift_vy {
+                    self.registers.reg_read(regy)?
+                } else {
+                    self.registers.reg_read(regx)?
+                };
 
-                    },
-                    0xe => {
-                        // 8xyE - SHL Vx {, Vy}
-                        // Set Vx = Vx SHL 1.
-                        // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-
-                        // Not sure what is supposed to happen with Vy here???
-                        let regx = ((inst >> 8) & 0xf) as u8;
-                        let regy = ((inst >> 4) & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("shl v{:x} {{, v{:x}}}", regx, regy);
-                        } 
-
-                        let x = self.registers.reg_read(regx);
-                        let _y = self.registers.reg_read(regy);
-
-                        if x & 0x7 == 1 {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
-                        self.registers.reg_write(regx, x << 1);
-                    },
-                    _ => {
-                        println!("{}", self.registers);
-                        unimplemented!("Unknown 8 instruction {:#04x}", inst);
-                    }
-                }
+                self.set_result_and_flag(regx, x << 1, x >> 7)?;
             },
-            0x9 => {
+            Instruction::SkipNeReg(regx, regy) => {
                 // 9xy0 - SNE Vx, Vy
                 // Skip next instruction if Vx != Vy.
                 // The values of Vx and Vy are compared, and if they are not equal, the program counter is increased by 2.
 
-                let regx = ((inst >> 8) & 0xf) as u8;
-                let regy = ((inst >> 4) & 0xf) as u8;
-
-                if DEBUG_PRINT {
-                    println!("sne v{:x} {{, v{:x}}}", regx, regy);
-                } 
-
-                let x = self.registers.reg_read(regx);
-                let y = self.registers.reg_read(regy);
+                let x = self.registers.reg_read(regx)?;
+                let y = self.registers.reg_read(regy)?;
 
                 if x != y {
-                    self.registers.pc += 2;
+                    self.skip_next()?;
                 }
             },
-            0xa => { 
+            Instruction::LoadI(addr) => {
                 // Annn - LD I, addr
                 // Set I = nnn.
                 // The value of register I is set to nnn.
-                let addr = inst & 0xfff;
-
-                if DEBUG_PRINT {
-                    println!("ld i, {:#02x}", addr);
-                }
 
                 self.registers.i = addr;
             },
-            0xb => {
+            Instruction::JumpOffset(x, addr) => {
                 // Bnnn - JP V0, addr
-                // Jump to location nnn + V0.                
+                // Jump to location nnn + V0.
                 // The program counter is set to nnn plus the value of V0.
-                
-                let addr = inst & 0xfff;
 
-                if DEBUG_PRINT {
-                    println!("jp v0, {:#02x}", addr);
-                }
+                // CHIP-48 and SUPER-CHIP read this as Bxnn - JP Vx, addr and jump to xnn plus Vx.
 
-                self.registers.pc = self.registers.v0 as u16 + addr;
-                self.registers.pc -= 2; // adjusting here due to the auto pc increase at bottom
+                let reg = if self.quirks.jump_vx { x } else { 0 };
+
+                self.registers.pc = self.registers.reg_read(reg)? as u16 + addr;
+                self.registers.pc = self.registers.pc.wrapping_sub(2); // adjusting here due to the auto pc increase at bottom
             },
-            0xc => {
+            Instruction::Random(reg, val) => {
                 // Cxkk - RND Vx, byte
                 // Set Vx = random byte AND kk.
                 // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk. The results are stored in Vx.
 
-                let reg = ((inst >> 8) & 0xf) as u8;
-                let val = (inst & 0xff) as u8;
-                let rnum = rand::thread_rng().gen_range(0..=255);
-
-                if DEBUG_PRINT {
-                    println!("rnd v{:x}, {:#02x}", reg, val);
-                }
-
+                let rnum = self.rng.next_u8();
 
-                self.registers.reg_write(reg, val & rnum);
+                self.registers.reg_write(reg, val & rnum)?;
             },
-            0xd => {
+            Instruction::Draw(x, y, n) => {
                 // Dxyn - DRW Vx, Vy, nibble
                 // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-                // The interpreter reads n bytes from memory, starting at the address stored in I. 
-                // These bytes are then displayed as sprites on screen at coordinates (Vx, Vy). 
-                // Sprites are XORed onto the existing screen. If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0. 
-                // If the sprite is positioned so part of it is outside the coordinates of the display, it wraps around to the opposite side of the screen. 
-                
-                let x = ((inst >> 8) & 0xf) as u8;
-                let y = ((inst >> 4) & 0xf) as u8;
-                let n = (inst & 0xf) as u8;
-
-                if DEBUG_PRINT {
-                    println!("drw v{:x}, v{:x}, {}", x, y, n);
-                }        
+                // The interpreter reads n bytes from memory, starting at the address stored in I.
+                // These bytes are then displayed as sprites on screen at coordinates (Vx, Vy).
+                // Sprites are XORed onto the existing screen. If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0.
+                // If the sprite is positioned so part of it is outside the coordinates of the display, it wraps around to the opposite side of the screen.
 
-                let begin_addr = self.registers.i as usize;
-                let end_addr = begin_addr + n as usize;
-                if begin_addr >= self.memory.memory.len() || end_addr > self.memory.memory.len() {
-                    println!("begin addr issue!");
+                // Dxy0 - DRW Vx, Vy, 0 (SUPER-CHIP)
+                // Display a 16x16 sprite made of 32 bytes, two per row, starting at memory location I.
+
+                // XO-CHIP draws the sprite on each plane selected by Fn01, with the data for each
+                // selected plane following the previous one in memory.
+
+                if self.quirks.display_wait && self.frame_drawn {
+                    // Leave PC alone so this instruction runs again until the next frame starts
+                    return Ok(());
                 }
+                self.frame_drawn = true;
+
+                let begin_addr = self.registers.i as usize;
+                let width = self.display.width();
+                let height = self.display.height();
+
+                // 16x16 sprites are stored as two bytes per row
+                let (rows, cols) = if n == 0 { (16, 16) } else { (n as usize, 8) };
+                let row_bytes = cols / 8;
 
-                let x_begin = std::cmp::min(self.registers.reg_read(x) as usize & 0x3f, (SCREEN_WIDTH - 1) as usize);
-                let y_begin = std::cmp::min(self.registers.reg_read(y) as usize & 0x1f, (SCREEN_HEIGHT - 1) as usize);
+                let x_begin = self.registers.reg_read(x)? as usize % width;
+                let y_begin = self.registers.reg_read(y)? as usize % height;
                 let mut collision = 0;
-        
-                for dy in 0..n as usize {
-                    let yc = y_begin + dy;
-                    if yc >= SCREEN_HEIGHT as usize {
-                        break;
-                    }
-        
-                    for dx in 0..8 {
-                        let xc = x_begin + dx;
-                        if xc >= SCREEN_WIDTH as usize {
-                            break;
+
+                let selected = self.display.planes;
+                let planes = (0..PLANES).map(|p| 1 << p).filter(|mask| selected & mask != 0);
+                for (k, mask) in planes.enumerate() {
+                    let plane_addr = begin_addr + k * rows * row_bytes;
+
+                    for dy in 0..rows {
+                        let mut yc = y_begin + dy;
+                        if yc >= height {
+                            if !self.quirks.wrap {
+                                break;
+                            }
+                            yc %= height;
                         }
-        
-                        if self.memory.memory[begin_addr + dy] & (0x80 >> dx) != 0 {
-                            let offset = xc + SCREEN_WIDTH as usize * yc;
-                            collision |= self.display.memory[offset];
-                            self.display.memory[offset] = !self.display.memory[offset];
+
+                        let mut row: u16 = 0;
+                        for b in 0..row_bytes {
+                            row = (row << 8) | self.memory.read(plane_addr + dy * row_bytes + b)? as u16;
+                        }
+
+                        for dx in 0..cols {
+                            let mut xc = x_begin + dx;
+                            if xc >= width {
+                                if !self.quirks.wrap {
+                                    break;
+                                }
+                                xc %= width;
+                            }
+
+                            if row & (1 << (cols - 1 - dx)) != 0 {
+                                let offset = xc + width * yc;
+                                collision |= self.display.memory[offset] & mask;
+                                self.display.memory[offset] ^= mask;
+                            }
                         }
                     }
                 }
-        
-                self.registers.vf = if collision != 0 { 1 } else { 0 };        
+
+                self.registers.vf = if collision != 0 { 1 } else { 0 };
 
             },
-            0xe => {
-                match inst & 0xff {
-                    0x9e => {
-                        // Ex9E - SKP Vx
-                        // Skip next instruction if key with the value of Vx is pressed.
-                        // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
+            Instruction::SkipKey(reg) => {
+                // Ex9E - SKP Vx
+                // Skip next instruction if key with the value of Vx is pressed.
+                // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
 
-                        let reg = ((inst >> 8) & 0xf) as u8;
-                        let val = self.registers.reg_read(reg);
+                // Only the low nibble of Vx selects a key, only Vx is read and nothing is written
+                let val = self.registers.reg_read(reg)?;
 
-                        if DEBUG_PRINT {
-                            println!("skp v{:x}", reg);
-                        }
+                if self.input.poll(val as usize & 0xf) == 1 {
+                    self.skip_next()?;
+                }
+            },
+            Instruction::SkipNotKey(reg) => {
+                // ExA1 - SKNP Vx
+                // Skip next instruction if key with the value of Vx is not pressed.
+                // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
 
-                        if self.input.poll(val as usize) == 1 {
-                            self.registers.pc += 2;
-                        }
-                    },
-                    0xa1 => {
-                        // ExA1 - SKNP Vx
-                        // Skip next instruction if key with the value of Vx is not pressed.
-                        // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
-                        
-                        let reg = ((inst >> 8) & 0xf) as u8;
-                        let val = self.registers.reg_read(reg);
-
-                        if DEBUG_PRINT {
-                            println!("sknp v{:x}", reg);
-                        }        
-
-                        if self.input.poll(val as usize) == 0 {
-                            self.registers.pc += 2;
-                        }
+                // Only the low nibble of Vx selects a key, only Vx is read and nothing is written
+                let val = self.registers.reg_read(reg)?;
 
-                    },
-                    _ => {
-                        println!("{}", self.registers);
-                        unimplemented!("Unknown E instruction {:#04x}", inst);        
-                    }
+                if self.input.poll(val as usize & 0xf) == 0 {
+                    self.skip_next()?;
                 }
+
             },
-            0xf => {
-                match inst & 0xff {
-                    // Fx07 - LD Vx, DT
-                    // Fx0A - LD Vx, K
-                    // Fx15 - LD DT, Vx
-                    // Fx18 - LD ST, Vx
-                    // Fx1E - ADD I, Vx
-                    // Fx29 - LD F, Vx
-                    // Fx33 - LD B, Vx
-                    // Fx55 - LD [I], Vx
-                    // Fx65 - LD Vx, [I]
-                    0x07 => {
-                        // Fx07 - LD Vx, DT
-                        // Set Vx = delay timer value.
-                        // The value of DT is placed into Vx.
-                        
-                        let reg = (inst >> 8 & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("ld v{:x}, dt", reg);
-                        }
+            Instruction::LoadILong(addr) => {
+                // F000 nnnn - LD I, long addr (XO-CHIP)
+                // Set I = nnnn, the 16-bit address stored in the two bytes following the instruction.
 
-                        self.registers.reg_write(reg, self.registers.dt);
-                    },
-                    0xa => {
-                        // Fx0A - LD Vx, K
-                        // Wait for a key press, store the value of the key in Vx.
-                        // All execution stops until a key is pressed, then the value of that key is stored in Vx.
-                        let mut keypress: bool = false;
-                        let reg = ((inst >> 8) & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("ld v{:x}, k", reg);
-                        }
+                self.registers.i = addr;
+                self.registers.pc = self.registers.pc.wrapping_add(2);
+            },
+            Instruction::Plane(n) => {
+                // Fn01 - PLANE n (XO-CHIP)
+                // Select the bitplanes n affected by clearing, drawing and scrolling.
 
-                        for i in 0..self.input.input.len() {
-                            if self.input.poll(i) == 1 {
-                                self.registers.reg_write(reg, i as u8);
-                                keypress = true;
-                            }
-                        }
+                self.display.planes = n & ((1 << PLANES) - 1);
+            },
+            Instruction::Audio => {
+                // F002 - AUDIO (XO-CHIP)
+                // Load the 16 byte audio pattern buffer from memory starting at location I.
 
-                        // this will essentially loop while polling input for change, so PC does not update
-                        if !keypress {
-                            self.registers.pc -= 2;
-                        }
-                    },
-                    0x15 => {
-                        // Fx15 - LD DT, Vx
-                        // Set delay timer = Vx.
-                        // DT is set equal to the value of Vx.
-                        
-                        let reg = (inst >> 8 & 0xf) as u8;
-                        
-                        if DEBUG_PRINT {
-                            println!("ld dt, v{:x}", reg);
-                        }
+                let mut pattern = [0; 16];
+                for (offset, sample) in pattern.iter_mut().enumerate() {
+                    *sample = self.memory.read(self.registers.i as usize + offset)?;
+                }
 
-                        self.registers.dt = self.registers.reg_read(reg);
-                    },
-                    0x18 => {
-                        // Fx18 - LD ST, Vx
-                        // Set sound timer = Vx.
-                        // ST is set equal to the value of Vx.
-                        
-                        let reg = ((inst >>8) & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("ld st, v{:x}", reg);
-                        }
+                self.audio.pattern = Some(pattern);
+            },
+            Instruction::Pitch(reg) => {
+                // Fx3A - PITCH Vx (XO-CHIP)
+                // Set the audio pitch register = Vx.
 
-                        self.registers.st = self.registers.reg_read(reg);
-                    },
-                    0x1e => {
-                        // Fx1E - ADD I, Vx
-                        // Set I = I + Vx.
-                        // The values of I and Vx are added, and the results are stored in I.
-                        
-                        let reg = ((inst >>8) & 0xf) as u8;
-
-                        if DEBUG_PRINT {
-                            println!("add i, v{:x}", reg);
-                        }
+                self.audio.pitch = self.registers.reg_read(reg)?;
+            },
+            Instruction::LoadDelay(reg) => {
+                // Fx07 - LD Vx, DT
+                // Set Vx = delay timer value.
+                // The value of DT is placed into Vx.
 
-                        self.registers.i = self.registers.i + self.registers.reg_read(reg) as u16;
+                self.registers.reg_write(reg, self.registers.dt)?;
+            },
+            Instruction::WaitKey(reg) => {
+                // Fx0A - LD Vx, K
+                // Wait for a key press, store the value of the key in Vx.
+                // All execution stops until a key is pressed, then the value of that key is stored in Vx.
+
+                // Like the COSMAC VIP, the key only counts once it is released again, otherwise a
+                // key held down would satisfy several waits in a row. Timers keep running meanwhile.
+                match self.key_wait {
+                    None => {
+                        self.key_wait = (0..16).find(|key| self.input.poll(*key as usize) == 1);
                     },
-                    0x29 => {
-                        // Fx29 - LD F, Vx
-                        // Set I = location of sprite for digit Vx.
-                        // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx. 
+                    Some(key) if self.input.poll(key as usize) == 0 => {
+                        self.registers.reg_write(reg, key)?;
+                        self.key_wait = None;
+                        self.registers.pc = self.registers.pc.wrapping_add(2);
+                    },
+                    Some(_) => (),
+                }
 
-                        // I believe this is essentially using reg# as an index into the FONTS region which starts at 0x00.
-                        // So for each Register value 0..F we index into FONTS by that value * 5.
-                        let reg = (inst >> 8 & 0xf) as u8;
+                // Leave PC alone so this instruction runs again until the key is released
+                return Ok(());
+            },
+            Instruction::SetDelay(reg) => {
+                // Fx15 - LD DT, Vx
+                // Set delay timer = Vx.
+                // DT is set equal to the value of Vx.
 
-                        if DEBUG_PRINT {
-                            println!("ld f, v{:x}", reg);
-                        }
+                self.registers.dt = self.registers.reg_read(reg)?;
+            },
+            Instruction::SetSound(reg) => {
+                // Fx18 - LD ST, Vx
+                // Set sound timer = Vx.
+                // ST is set equal to the value of Vx.
 
-                        self.registers.i = self.registers.reg_read(reg) as u16 * 5;
-                    },
-                    0x33 => {
-                        // Fx33 - LD B, Vx
-                        // Store BCD representation of Vx in memory locations I, I+1, and I+2.
-                        // The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I, 
-                        // the tens digit at location I+1, and the ones digit at location I+2.
-                        let reg = (inst >> 8 & 0xf) as u8;
-                        let val = self.registers.reg_read(reg);
-                        let h = (val / 100) % 10;
-                        let t = (val / 10) % 10;
-                        let o = val % 10;
-                        if DEBUG_PRINT {
-                            println!("ld b, v{:x}", reg);
-                        }
+                self.registers.st = self.registers.reg_read(reg)?;
+            },
+            Instruction::AddI(reg) => {
+                // Fx1E - ADD I, Vx
+                // Set I = I + Vx.
+                // The values of I and Vx are added, and the results are stored in I.
 
-                        self.memory.write(self.registers.i as usize, h);
-                        self.memory.write((self.registers.i+1) as usize, t);
-                        self.memory.write((self.registers.i+2) as usize, o);
+                let res = self.registers.i.wrapping_add(self.registers.reg_read(reg)? as u16);
 
-                    },
-                    0x55 => {
-                        // Fx55 - LD [I], Vx
-                        // Store registers V0 through Vx in memory starting at location I.                        
-                        // The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
+                // The Amiga interpreter flags I leaving the 4K address space, Spacefight 2091! relies on it
+                if self.quirks.add_i_overflow {
+                    self.registers.vf = if res > 0xfff { 1 } else { 0 };
+                }
 
-                        let reg = ((inst >> 8) & 0xf) as u8;
+                self.registers.i = res;
+            },
+            Instruction::Font(reg) => {
+                // Fx29 - LD F, Vx
+                // Set I = location of sprite for digit Vx.
+                // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx.
 
-                        if DEBUG_PRINT {
-                            println!("ld [i], v{:x}", reg);
-                        }
+                // I believe this is essentially using reg# as an index into the FONTS region which starts at 0x00.
+                // So for each Register value 0..F we index into FONTS by that value * 5.
 
-                        for i in 0..=reg {
-                            self.memory.memory[self.registers.i as usize + i as usize] = self.registers.reg_read(i);
-                        }
+                self.registers.i = (FONT_OFFSET + self.registers.reg_read(reg)? as usize * 5) as u16;
+            },
+            Instruction::BigFont(reg) => {
+                // Fx30 - LD HF, Vx (SUPER-CHIP)
+                // Set I = location of the 8x10 sprite for digit Vx.
 
-                    },
-                    0x65 => {
-                        // Fx65 - LD Vx, [I]
-                        // Read registers V0 through Vx from memory starting at location I.
-                        // The interpreter reads values from memory starting at location I into registers V0 through Vx.
+                self.registers.i = (BIG_FONT_OFFSET + (self.registers.reg_read(reg)? & 0xf) as usize * 10) as u16;
+            },
+            Instruction::Bcd(reg) => {
+                // Fx33 - LD B, Vx
+                // Store BCD representation of Vx in memory locations I, I+1, and I+2.
+                // The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I,
+                // the tens digit at location I+1, and the ones digit at location I+2.
+                let val = self.registers.reg_read(reg)?;
+                let h = (val / 100) % 10;
+                let t = (val / 10) % 10;
+                let o = val % 10;
+
+                self.memory.write(self.registers.i as usize, h)?;
+                self.memory.write(self.registers.i as usize + 1, t)?;
+                self.memory.write(self.registers.i as usize + 2, o)?;
+
+            },
+            Instruction::Store(reg) => {
+                // Fx55 - LD [I], Vx
+                // Store registers V0 through Vx in memory starting at location I.
+                // The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
 
-                        let reg = (inst >> 8 & 0xf) as u8;
+                for i in 0..=reg {
+                    self.memory.write(self.registers.i as usize + i as usize, self.registers.reg_read(i)?)?;
+                }
 
-                        if DEBUG_PRINT {
-                            println!("ld  v{:x}, [I]", reg);
-                        }
+                self.increment_i_after_load_store(reg);
 
-                        for i in 0..=reg {
-                            self.registers.reg_write(i, self.memory.read((self.registers.i + i as u16) as usize));
-                        }
-                    },
-                    _ => {
-                        println!("{}", self.registers);
-                        unimplemented!("Unknown F instruction {:#04x}", inst);
-                    }
-        
+            },
+            Instruction::Load(reg) => {
+                // Fx65 - LD Vx, [I]
+                // Read registers V0 through Vx from memory starting at location I.
+                // The interpreter reads values from memory starting at location I into registers V0 through Vx.
+
+                for i in 0..=reg {
+                    self.registers.reg_write(i, self.memory.read(self.registers.i as usize + i as usize)?)?;
                 }
-            }
-            _ => {
-                println!("{}", self.registers);
-                unimplemented!("Unknown instruction {:#04x}", inst);
-            }
-        }
 
-        self.registers.pc += 2;
+                self.increment_i_after_load_store(reg);
+            },
+            Instruction::StoreFlags(reg) => {
+                // Fx75 - LD R, Vx (SUPER-CHIP)
+                // Store registers V0 through Vx in the RPL user flags, x < 8.
+
+                for i in 0..=reg.min(7) {
+                    self.registers.flags[i as usize] = self.registers.reg_read(i)?;
+                }
+            },
+            Instruction::LoadFlags(reg) => {
+                // Fx85 - LD Vx, R (SUPER-CHIP)
+                // Read registers V0 through Vx from the RPL user flags, x < 8.
 
-        if DEBUG_STEP {
-            println!("{}", self.registers);
-            println!("{:?}", &self.memory.memory[self.registers.i as usize..self.registers.i as usize + 10]);
-            pause();
+                for i in 0..=reg.min(7) {
+                    self.registers.reg_write(i, self.registers.flags[i as usize])?;
+                }
+            },
         }
 
-        Some(())
+        self.registers.pc = self.registers.pc.wrapping_add(2);
+
+        Ok(())
     }
 }
 
-// used for single step debugging
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+/// Registers Vx through Vy, walking backwards when x > y
+fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = u8>> {
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
+    }
 }
 
 impl fmt::Display for Register {
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::io;
+
+/// Errors raised while loading or executing a Chip-8 program
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The instruction at `pc` is not a known opcode
+    UnknownOpcode { pc: u16, opcode: u16 },
+
+    /// CALL with all 16 stack slots in use
+    StackOverflow,
+
+    /// RET with an empty stack
+    StackUnderflow,
+
+    /// Access outside of the 64K memory region
+    MemoryFault(usize),
+
+    /// Access to a V register above VF
+    InvalidRegister(u8),
+
+    /// Game file does not fit in the game data memory region
+    RomTooLarge { size: usize, max: usize },
+
+    /// Save state with a bad header, version or checksum
+    InvalidState(&'static str),
+
+    /// Failed reading a file
+    Io(io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { pc, opcode } => write!(f, "unknown instruction {:#06x} at {:#05x}", opcode, pc),
+            Chip8Error::StackOverflow => write!(f, "stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "stack underflow"),
+            Chip8Error::MemoryFault(addr) => write!(f, "memory access out of bounds at {:#x}", addr),
+            Chip8Error::InvalidRegister(reg) => write!(f, "invalid register v{:x}", reg),
+            Chip8Error::RomTooLarge { size, max } => write!(f, "ROM is {} bytes, larger than the {} bytes available", size, max),
+            Chip8Error::InvalidState(reason) => write!(f, "invalid save state: {}", reason),
+            Chip8Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Chip8Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
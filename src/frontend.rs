@@ -0,0 +1,775 @@
+extern crate sdl2;
+
+use crate::emulator::{Emulator, FRAME_HZ};
+use crate::display::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::input::{Input};
+use crate::audio::{Audio};
+use crate::rewind::{Rewind};
+use crate::movie::{Player, Recorder};
+use crate::debugger::{Debugger, Action};
+use crate::trace::{Tracer};
+use crate::keymap::{KeyConfig, KeyMap, Rebinding, KEYPAD_LAYOUT};
+use crate::keypad::{self, VirtualKeypad};
+use crate::speed::Speed;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use sdl2::{Sdl, EventPump, render::Canvas, video::Window, pixels::Color, rect::Rect};
+use sdl2::{event::Event, keyboard::{Keycode, Mod, Scancode}};
+use sdl2::audio::{AudioCallback, AudioSpecDesired, AudioDevice, AudioStatus};
+use sdl2::pixels;
+
+// The sound timer is active whenever the sound timer register (ST) is non-zero.
+// This timer also decrements at a rate of 60Hz, however, as long as ST's value is greater than zero, the Chip-8 buzzer will sound.
+// When ST reaches zero, the sound timer deactivates.
+
+// Each pass of the main loop is one 60Hz frame: input is read, the emulator runs the frame's
+// instructions, the screen is presented once and the loop sleeps until the next frame is due.
+// Frames are scheduled on a monotonic clock, so time lost to slow frames or oversleeping is made
+// up in the frames that follow instead of slowing the game down.
+
+/// Time between the start of two frames
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / FRAME_HZ as u64);
+
+/// Frames the schedule may fall behind before it restarts from the current time
+pub const MAX_LAG: u32 = 4;
+
+/// Colors of the key rebinding screen: waiting for a host key, bound and not reached yet
+const REBIND_CURRENT: Color = Color::RGB(255, 102, 0);
+const REBIND_DONE: Color = Color::RGB(255, 255, 255);
+const REBIND_PENDING: Color = Color::RGB(96, 96, 96);
+
+/// Colors for each combination of the two XO-CHIP bitplanes
+const PALETTE: [Color; 4] = [
+    Color::RGB(0, 0, 0),        // no plane
+    Color::RGB(255, 255, 255),  // plane 1
+    Color::RGB(255, 102, 0),    // plane 2
+    Color::RGB(102, 34, 0),     // both planes
+];
+
+// The audio code is essentially the rust sdl2 audio squarewave example, extended to loop
+// the 128 sample XO-CHIP pattern buffer once a program loads one.
+pub struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+
+    /// Output sample rate of the audio device
+    freq: f32,
+
+    /// XO-CHIP pattern and pitch currently playing
+    audio: Audio,
+}
+
+/// SDL2 window and audio frontend for the emulator
+pub struct Frontend {
+    /// SDL2 Context
+    pub context: Sdl,
+
+    /// SDL2 Canvas
+    pub canvas: Canvas<Window>,
+
+    /// SDL2 Audio device
+    pub audio_device: AudioDevice<SquareWave>,
+
+    /// Size in window pixels of a single Chip-8 pixel
+    scale: u32,
+
+    /// Keep the buzzer silent
+    pub mute: bool,
+
+    /// Save state slots are stored next to this path, usually the ROM
+    pub state_path: PathBuf,
+
+    /// Execution stopped on an error
+    halted: bool,
+
+    /// History of recent machine states
+    pub rewind: Rewind,
+
+    /// The rewind key is held
+    rewinding: bool,
+
+    /// Command-line debugger, entered with F10 or when an instruction fails
+    pub debugger: Debugger,
+
+    /// Trace of executed instructions
+    pub tracer: Option<Tracer>,
+
+    /// Input movie being recorded, saved to `movie_path` when the window closes
+    pub recorder: Option<Recorder>,
+    pub movie_path: PathBuf,
+
+    /// Input movie being played back, the keypad is ignored until it ends
+    pub player: Option<Player>,
+
+    /// Slow motion, fast forward while Tab is held, and pausing
+    speed: Speed,
+
+    /// Message shown in the window title after the speed
+    status: String,
+
+    /// Chip-8 key of each bound host key
+    keys: HashMap<Scancode, u8>,
+
+    /// Key bindings file contents, changed by the rebinding screen
+    key_config: KeyConfig,
+
+    /// Where the rebinding screen saves the key bindings
+    keymap_path: Option<PathBuf>,
+
+    /// File name of the ROM, selects its own key bindings
+    rom_name: Option<String>,
+
+    /// The key rebinding screen is shown instead of the game
+    rebinding: Option<Rebinding>,
+
+    /// Clickable keypad to the right of the screen
+    keypad: Option<VirtualKeypad>,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            let high = match self.audio.pattern {
+                // Generate a square wave
+                None => self.phase <= 0.5,
+
+                // Step through the 128 1-bit samples of the pattern
+                Some(pattern) => {
+                    let bit = (self.phase * 128.0) as usize % 128;
+                    pattern[bit / 8] & (0x80 >> (bit % 8)) != 0
+                },
+            };
+
+            *x = if high { self.volume } else { -self.volume };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+impl SquareWave {
+    /// Silent buzzer for a device playing `freq` samples per second
+    pub fn new(freq: f32) -> Self {
+        SquareWave {
+            phase_inc: 440.0 / freq,
+            phase: 0.0,
+            volume: 0.10,
+            freq,
+            audio: Audio::new(),
+        }
+    }
+
+    /// Switch to the pattern and pitch of `audio`
+    pub fn set_audio(&mut self, audio: Audio) {
+        self.audio = audio;
+        self.phase_inc = match audio.pattern {
+            None => 440.0 / self.freq,
+            Some(_) => audio.playback_rate() / 128.0 / self.freq,
+        };
+    }
+}
+
+impl Frontend {
+    /// Open a window for `scale` sized pixels, with room for a clickable keypad if `keypad` is set
+    pub fn new(scale: u32, keypad: bool) -> Result<Self, String> {
+
+        let sdl_context = sdl2::init()?;
+        let video_subsys = sdl_context.video()?;
+        let audio_subsys = sdl_context.audio()?;
+
+        let desired_spec = AudioSpecDesired {
+            freq: Some(44_100),
+            channels: Some(1), // mono
+            samples: None,     // default sample size
+        };
+
+        let audio_device = audio_subsys.open_playback(None, &desired_spec, |spec| SquareWave::new(spec.freq as f32))?;
+
+        // The keypad is a square as high as the screen
+        let (width, height) = (SCREEN_WIDTH as u32 * scale, SCREEN_HEIGHT as u32 * scale);
+        let keypad = if keypad {
+            Some(VirtualKeypad::new(Rect::new(width as i32, 0, height, height), (width + height, height)))
+        } else {
+            None
+        };
+
+        let window = video_subsys
+            .window(
+                "CHIP-8 Emulator",
+                width + keypad.as_ref().map_or(0, |keypad| keypad.area.width()),
+                height,
+            )
+            .position_centered()
+            .opengl()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+
+        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        canvas.clear();
+        canvas.present();
+
+        Ok(Frontend {
+            context: sdl_context,
+            canvas,
+            audio_device,
+            scale,
+            mute: false,
+            state_path: PathBuf::from("chip8"),
+            halted: false,
+            rewind: Rewind::default(),
+            rewinding: false,
+            debugger: Debugger::new(),
+            tracer: None,
+            recorder: None,
+            movie_path: PathBuf::from("chip8.movie"),
+            player: None,
+            speed: Speed::new(),
+            status: String::new(),
+            keys: scancodes(&KeyMap::new())?,
+            key_config: KeyConfig::default(),
+            keymap_path: None,
+            rom_name: None,
+            rebinding: None,
+            keypad,
+        })
+    }
+
+    /// Use the key bindings of `config` for the ROM file `rom_name`, fails on unknown key names
+    ///
+    /// Bindings changed on the rebinding screen are saved to `path`.
+    pub fn set_key_config(&mut self, config: KeyConfig, path: Option<PathBuf>, rom_name: Option<String>) -> Result<(), String> {
+        self.keys = scancodes(&config.keymap(rom_name.as_deref()))?;
+        self.key_config = config;
+        self.keymap_path = path;
+        self.rom_name = rom_name;
+
+        Ok(())
+    }
+
+    pub fn run(&mut self, emu: &mut Emulator) {
+        self.enter_emu(emu).expect("Failed to execute emulator <enter_emu>!");
+    }
+
+    fn enter_emu(&mut self, emu: &mut Emulator) -> Option<()> {
+        let mut event_pump = self.context.event_pump().ok()?;
+        let mut deadline = Instant::now();
+        if self.recorder.is_some() {
+            self.set_status("recording movie");
+        } else if self.player.is_some() {
+            self.set_status("playing movie");
+        }
+        self.update_title();
+
+        while self.process_events(emu, &mut event_pump) && !emu.exited {
+            if self.rewinding {
+                // Walk backwards at twice the normal speed, the movie follows only if it moved
+                let position = self.rewind.position();
+                if self.rewind.rewind(emu, 2 * emu.cycles_per_frame as u64) {
+                    self.halted = false;
+                }
+                if self.rewind.position() != position {
+                    self.seek_movie(emu);
+                }
+            } else if !self.halted && self.rebinding.is_none() && !self.run_frames(emu) {
+                break;
+            }
+
+            match self.rebinding.take() {
+                Some(rebinding) => {
+                    self.draw_keypad(&rebinding);
+                    self.rebinding = Some(rebinding);
+                },
+                None => self.update(&emu.display, &emu.input),
+            }
+
+            let silent = self.halted || self.speed.paused || self.rebinding.is_some();
+            self.update_audio(&emu.audio, if silent { 0 } else { emu.registers.st });
+
+            if self.speed.fast_forward {
+                deadline = Instant::now();
+            } else {
+                wait_for_frame(&mut deadline, FRAME_TIME * 100 / self.speed.percent);
+            }
+        }
+
+        if let Some(Err(e)) = self.tracer.as_mut().map(Tracer::flush) {
+            eprintln!("cannot write trace: {}", e);
+        }
+
+        if let Some(recorder) = self.recorder.take() {
+            match recorder.finish(emu).save(&self.movie_path) {
+                Ok(()) => println!("movie saved to {}", self.movie_path.display()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        Some(())
+    }
+
+    /// Run the frames due before the next present, returns false when the debugger quits
+    ///
+    /// That is one frame normally, none while paused unless advancing, and as many as fit in
+    /// the time of a normal frame while fast forwarding.
+    fn run_frames(&mut self, emu: &mut Emulator) -> bool {
+        if self.speed.paused {
+            if !self.speed.take_advance() {
+                return true;
+            }
+
+            return self.run_frame(emu);
+        }
+
+        let start = Instant::now();
+        loop {
+            if !self.run_frame(emu) {
+                return false;
+            }
+
+            if !self.speed.fast_forward || self.halted || emu.exited || start.elapsed() >= FRAME_TIME {
+                return true;
+            }
+        }
+    }
+
+    /// Run the instructions of one frame, returns false when the debugger quits
+    fn run_frame(&mut self, emu: &mut Emulator) -> bool {
+        loop {
+            if self.debugger.should_break(emu) && self.debugger.repl(emu) == Action::Quit {
+                return false;
+            }
+
+            if !self.play_movie(emu) {
+                return true;
+            }
+
+            self.rewind.record(emu);
+            if let Some(recorder) = self.recorder.as_mut() {
+                recorder.record(emu);
+            }
+            self.trace(emu);
+
+            if let Err(e) = emu.step() {
+                // Keep the window and last frame around so the failure can be inspected
+                eprintln!("emulation halted: {}", e);
+                eprintln!("{}", emu.registers);
+                self.set_status(&format!("halted: {}", e));
+
+                if let Some(Err(e)) = self.tracer.as_mut().map(Tracer::crash) {
+                    eprintln!("cannot write trace: {}", e);
+                }
+
+                if self.debugger.attached() {
+                    self.debugger.break_now();
+                } else {
+                    self.halted = true;
+                }
+
+                return true;
+            }
+
+            if emu.tick_cnt == 0 || emu.exited {
+                return true;
+            }
+        }
+    }
+
+    /// Feed the movie input to the next instruction, returns false and pauses when the movie ends
+    fn play_movie(&mut self, emu: &mut Emulator) -> bool {
+        let player = match self.player.as_mut() {
+            Some(player) => player,
+            None => return true,
+        };
+
+        if player.play(emu) {
+            return true;
+        }
+
+        let status = match player.verify(emu) {
+            Ok(()) => "movie ended, state hash matches".to_string(),
+            Err(e) => format!("movie ended out of sync: {}", e),
+        };
+        println!("{}", status);
+
+        self.player = None;
+        self.speed.paused = true;
+        self.set_status(&status);
+
+        false
+    }
+
+    /// Move the movie being recorded or played to where rewinding left the emulator
+    fn seek_movie(&mut self, emu: &Emulator) {
+        let position = self.rewind.position();
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.truncate(position, emu);
+        }
+        if let Some(player) = self.player.as_mut() {
+            player.seek(position);
+        }
+    }
+
+    /// Trace the next instruction, tracing stops if the trace can not be written
+    fn trace(&mut self, emu: &Emulator) {
+        if let Some(Err(e)) = self.tracer.as_mut().map(|tracer| tracer.record(emu)) {
+            eprintln!("cannot write trace: {}", e);
+            self.tracer = None;
+        }
+    }
+
+    /// Path of save state `slot`
+    fn slot_path(&self, slot: u8) -> PathBuf {
+        let mut path = self.state_path.clone().into_os_string();
+        path.push(format!(".state{}", slot));
+
+        PathBuf::from(path)
+    }
+
+    fn save_slot(&mut self, emu: &Emulator, slot: u8) {
+        match emu.save_state_file(self.slot_path(slot)) {
+            Ok(()) => self.set_status(&format!("saved slot {}", slot)),
+            Err(e) => eprintln!("failed saving slot {}: {}", slot, e),
+        }
+    }
+
+    fn load_slot(&mut self, emu: &mut Emulator, slot: u8) {
+        // A movie only reproduces runs that started at power on
+        if self.recorder.is_some() || self.player.is_some() {
+            self.set_status("save states can not be loaded during a movie");
+            return;
+        }
+
+        match emu.load_state_file(self.slot_path(slot)) {
+            Ok(()) => {
+                self.halted = false;
+                self.rewind.clear();
+                self.set_status(&format!("loaded slot {}", slot));
+            },
+            Err(e) => eprintln!("failed loading slot {}: {}", slot, e),
+        }
+    }
+
+    /// Show `status` in the window title until the next one
+    fn set_status(&mut self, status: &str) {
+        self.status = status.to_string();
+        self.update_title();
+    }
+
+    /// Window title with the current speed and status
+    fn update_title(&mut self) {
+        let mut title = format!("CHIP-8 Emulator [{}]", self.speed);
+        if !self.status.is_empty() {
+            title = format!("{} - {}", title, self.status);
+        }
+
+        // Titles never contain interior NUL bytes, so this can not fail
+        let _ = self.canvas.window_mut().set_title(&title);
+    }
+
+    /// Show the rebinding screen, for the current ROM only or for all ROMs
+    fn start_rebinding(&mut self, emu: &mut Emulator, for_rom: bool) {
+        // Keys held now would stay pressed, their release is not passed on to the game
+        if self.player.is_none() {
+            emu.input = Input::new();
+        }
+
+        let rom = if for_rom { self.rom_name.clone() } else { None };
+        let keymap = self.key_config.keymap(rom.as_deref());
+        self.rebinding = Some(Rebinding::new(keymap, rom));
+        self.update_rebinding_title();
+    }
+
+    fn update_rebinding_title(&mut self) {
+        let prompt = match &self.rebinding {
+            Some(rebinding) => format!(
+                "press a key for {:X}{}, Escape cancels",
+                rebinding.current().unwrap_or_default(),
+                rebinding.rom.as_ref().map(|rom| format!(" in {}", rom)).unwrap_or_default(),
+            ),
+            None => return,
+        };
+
+        self.set_status(&prompt);
+    }
+
+    /// Take the host key pressed on the rebinding screen, saving the bindings after the last key
+    fn rebind(&mut self, scancode: Scancode) {
+        let mut rebinding = match self.rebinding.take() {
+            Some(rebinding) => rebinding,
+            None => return,
+        };
+
+        if scancode == Scancode::Escape {
+            self.set_status("key bindings unchanged");
+            return;
+        }
+
+        rebinding.bind(scancode.name());
+        if rebinding.current().is_some() {
+            self.rebinding = Some(rebinding);
+            self.update_rebinding_title();
+            return;
+        }
+
+        self.key_config.set_keymap(rebinding.rom.as_deref(), &rebinding.keymap);
+        let status = self.save_key_config();
+        self.set_status(&status);
+    }
+
+    /// Apply and save the key bindings, returns the outcome to show
+    fn save_key_config(&mut self) -> String {
+        // Names come from SDL itself, but a ROM section may still hold unknown ones
+        match scancodes(&self.key_config.keymap(self.rom_name.as_deref())) {
+            Ok(keys) => self.keys = keys,
+            Err(e) => return e,
+        }
+
+        match &self.keymap_path {
+            Some(path) => match self.key_config.save(path) {
+                Ok(()) => format!("key bindings saved to {}", path.display()),
+                Err(e) => e,
+            },
+            None => "key bindings changed, no configuration directory to save them".to_string(),
+        }
+    }
+
+    fn update_audio(&mut self, audio: &Audio, st: u8) {
+        {
+            let mut callback = self.audio_device.lock();
+            if callback.audio != *audio {
+                callback.set_audio(*audio);
+            }
+        }
+
+        if st > 0 && !self.mute {
+            if self.audio_device.status() == AudioStatus::Paused {
+                self.audio_device.resume();
+            }
+        } else if self.audio_device.status() == AudioStatus::Playing {
+            self.audio_device.pause();
+        }
+    }
+
+    fn process_events(&mut self, emu: &mut Emulator, event_pump: &mut EventPump) -> bool {
+        for event in event_pump.poll_iter() {
+
+            // The rebinding screen takes every key, including hotkeys
+            if self.rebinding.is_some() {
+                match event {
+                    Event::Quit { .. } => return false,
+                    Event::KeyDown { scancode: Some(sc), repeat: false, .. } => self.rebind(sc),
+                    _ => (),
+                }
+                continue;
+            }
+
+            // The movie holds the input while it plays
+            let playing = self.player.is_some();
+            if let Some(keypad) = self.keypad.as_mut().filter(|_| !playing) {
+                if keypad.handle(&event, &mut emu.input) {
+                    continue;
+                }
+            }
+
+            match event {
+                Event::Quit { .. } => { return false },
+
+                // Keys bound to the keypad go to the game, even when they are also hotkeys
+                Event::KeyDown {
+                    scancode: Some(sc), ..
+                } if self.keys.contains_key(&sc) => self.set_key(emu, sc, true),
+
+                Event::KeyUp {
+                    scancode: Some(sc), ..
+                } if self.keys.contains_key(&sc) => self.set_key(emu, sc, false),
+
+                // F10 stops in the command-line debugger
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10), repeat: false, ..
+                } => self.debugger.break_now(),
+
+                // F12 rebinds the keypad for every ROM, Shift+F12 for this ROM only
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12), keymod, repeat: false, ..
+                } => self.start_rebinding(emu, keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)),
+
+                // Hold Tab to fast forward, M cycles slow motion, P pauses and N advances one frame
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab), repeat: false, ..
+                } => {
+                    self.speed.fast_forward = true;
+                    self.update_title();
+                },
+
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab), ..
+                } => {
+                    self.speed.fast_forward = false;
+                    self.update_title();
+                },
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::M), repeat: false, ..
+                } => {
+                    self.speed.cycle_slow_motion();
+                    self.update_title();
+                },
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::P), repeat: false, ..
+                } => {
+                    self.speed.toggle_pause();
+                    self.update_title();
+                },
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::N), repeat: false, ..
+                } => {
+                    self.speed.frame_advance();
+                    self.update_title();
+                },
+
+                // Hold Backspace to rewind
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace), ..
+                } => self.rewinding = true,
+
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace), ..
+                } => self.rewinding = false,
+
+                // F1-F9 load a save state slot, Shift+F1-F9 save to it
+                Event::KeyDown {
+                    keycode: Some(kc), keymod, repeat: false, ..
+                } if state_slot(kc).is_some() => {
+                    let slot = state_slot(kc).unwrap_or_default();
+
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        self.save_slot(emu, slot);
+                    } else {
+                        self.load_slot(emu, slot);
+                    }
+                },
+
+            _ => (),
+            }
+        }
+
+        true
+    }
+
+    /// Press or release the Chip-8 key bound to `scancode`, unless a movie holds the input
+    fn set_key(&self, emu: &mut Emulator, scancode: Scancode, pressed: bool) {
+        if let (Some(key), None) = (self.keys.get(&scancode), &self.player) {
+            emu.input.set(*key as usize, pressed);
+        }
+    }
+
+    /// Render the hex keypad of the rebinding screen, highlighting the key waiting for a host key
+    fn draw_keypad(&mut self, rebinding: &Rebinding) {
+        self.canvas.set_draw_color(PALETTE[0]);
+        self.canvas.clear();
+
+        let (width, height) = self.canvas.output_size().unwrap_or((SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32));
+        let cell = width.min(height) / 4;
+        let left = (width - cell * 4) / 2;
+        let top = (height - cell * 4) / 2;
+
+        for (idx, key) in KEYPAD_LAYOUT.iter().enumerate() {
+            let x = (left + cell * (idx as u32 % 4)) as i32;
+            let y = (top + cell * (idx as u32 / 4)) as i32;
+
+            let color = if rebinding.current() == Some(*key) {
+                REBIND_CURRENT
+            } else if rebinding.is_done(*key) {
+                REBIND_DONE
+            } else {
+                REBIND_PENDING
+            };
+
+            keypad::draw_key(&mut self.canvas, Rect::new(x, y, cell, cell), *key, color, false);
+        }
+
+        self.canvas.present();
+    }
+
+    /// Render the framebuffer to the window, and the keys of `input` on the keypad if shown
+    pub fn update(&mut self, display: &Display, input: &Input) {
+        self.canvas.set_draw_color(PALETTE[0]);
+        self.canvas.clear();
+
+        // The window is sized for 64x32, high resolution pixels are drawn at half the size, which
+        // needs a scale of at least 2 to fit
+        let width = display.width() as u32;
+        let px_size = (self.scale * SCREEN_WIDTH as u32 / width).max(1);
+
+        for (idx, px) in display.pixels().iter().enumerate() {
+            if *px != 0 {
+                self.canvas.set_draw_color(PALETTE[*px as usize & 0x3]);
+                self.canvas.fill_rect(Rect::new(
+                    (px_size * (idx as u32 % width)) as i32,
+                    (px_size * (idx as u32 / width)) as i32,
+                    px_size,
+                    px_size,
+                )).unwrap();
+            }
+        }
+
+        if let Some(keypad) = &self.keypad {
+            keypad.draw(&mut self.canvas, input);
+        }
+
+        self.canvas.present();
+
+    }
+}
+
+/// Chip-8 key of each host key bound in `keymap`
+fn scancodes(keymap: &KeyMap) -> Result<HashMap<Scancode, u8>, String> {
+    let mut keys = HashMap::new();
+
+    for (key, names) in keymap.keys.iter().enumerate() {
+        for name in names {
+            let scancode = Scancode::from_name(name).ok_or(format!("unknown key '{}'", name))?;
+            keys.insert(scancode, key as u8);
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Save state slot bound to a function key
+fn state_slot(kc: Keycode) -> Option<u8> {
+    match kc {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        Keycode::F5 => Some(5),
+        Keycode::F6 => Some(6),
+        Keycode::F7 => Some(7),
+        Keycode::F8 => Some(8),
+        Keycode::F9 => Some(9),
+        _ => None,
+    }
+}
+
+/// Move `deadline` on by `frame_time` to the start of the next frame and sleep until then
+pub fn wait_for_frame(deadline: &mut Instant, frame_time: Duration) {
+    *deadline += frame_time;
+
+    let now = Instant::now();
+    if *deadline > now {
+        std::thread::sleep(*deadline - now);
+    } else if now - *deadline > frame_time * MAX_LAG {
+        // Far behind, e.g. after the debugger, rushing through the missed frames would not help
+        *deadline = now;
+    }
+}
@@ -0,0 +1,176 @@
+use crate::emulator::Emulator;
+use crate::display::Display;
+use crate::error::Chip8Error;
+use crate::quirks::Quirks;
+use crate::rng::{Algorithm, Rng};
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Regression checks for whole ROMs. A ROM runs headless for a number of frames, optionally with
+// scripted key presses, and the final framebuffer is reduced to a hash that is compared against
+// the one recorded in a manifest. The random number generator is seeded with a constant so every
+// run of a ROM ends on the same picture.
+//
+// Manifest lines hold whitespace separated columns, `#` starts a comment:
+//
+//     # rom                 frames  quirks  hash              input
+//     BC_test.ch8           200     default 1a2b3c4d5e6f7a8b
+//     keypad_test.ch8       120     default 0123456789abcdef  30:+5 35:-5
+//     BC_test.ch8           200     default 1a2b3c4d5e6f7a8b  text=BON
+//
+// ROM paths are relative to the manifest. Input events are `frame:+key` to press and
+// `frame:-key` to release a hexadecimal key before that frame runs. `text=WORD` requires the
+// word to be read off the final screen and `!text=WORD` requires it to be absent. `seed=N`
+// runs the ROM with another random number generator seed and `rng=vip` with the COSMAC VIP
+// generator.
+
+/// Default seed for Cxkk, fixed so results are reproducible
+pub const SEED: u64 = 1;
+
+/// Scripted key press or release
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// Frame before which the key changes
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+impl FromStr for InputEvent {
+    type Err = String;
+
+    /// Parse `frame:+key` or `frame:-key`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid input event '{}', expected frame:+key or frame:-key", s);
+
+        let (frame, key) = s.split_once(':').ok_or_else(invalid)?;
+        let (pressed, key) = match key.strip_prefix('+') {
+            Some(key) => (true, key),
+            None => (false, key.strip_prefix('-').ok_or_else(invalid)?),
+        };
+
+        let frame = frame.parse().map_err(|_| invalid())?;
+        let key = u8::from_str_radix(key, 16).ok().filter(|k| *k < 16).ok_or_else(invalid)?;
+
+        Ok(InputEvent { frame, key, pressed })
+    }
+}
+
+/// One ROM of a manifest
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub rom: PathBuf,
+    pub frames: u64,
+    pub quirks: Quirks,
+
+    /// Random number generator at power on
+    pub rng: Rng,
+
+    /// Expected framebuffer hash
+    pub hash: u64,
+    pub input: Vec<InputEvent>,
+
+    /// Words that must, or with false must not, be on the final screen
+    pub text: Vec<(String, bool)>,
+
+    /// Manifest line, for reporting and updating hashes
+    pub line: usize,
+}
+
+/// Parse the manifest `text`, ROM paths are taken relative to `dir`
+pub fn parse_manifest(text: &str, dir: &Path) -> Result<Vec<Case>, String> {
+    let mut cases = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line_no = n + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let columns: Vec<&str> = line.split_whitespace().collect();
+
+        if columns.is_empty() {
+            continue;
+        }
+
+        if columns.len() < 4 {
+            return Err(format!("line {}: expected rom, frames, quirks and hash", line_no));
+        }
+
+        let frames = columns[1].parse().map_err(|_| format!("line {}: invalid frame count '{}'", line_no, columns[1]))?;
+        let quirks = columns[2].parse().map_err(|e| format!("line {}: {}", line_no, e))?;
+        let hash = u64::from_str_radix(columns[3], 16).map_err(|_| format!("line {}: invalid hash '{}'", line_no, columns[3]))?;
+        let mut input = Vec::new();
+        let mut text = Vec::new();
+        let mut seed = SEED;
+        let mut algorithm = Algorithm::Xorshift;
+        for column in &columns[4..] {
+            if let Some(value) = column.strip_prefix("seed=") {
+                seed = value.parse().map_err(|_| format!("line {}: invalid seed '{}'", line_no, value))?;
+            } else if let Some(value) = column.strip_prefix("rng=") {
+                algorithm = value.parse().map_err(|e| format!("line {}: {}", line_no, e))?;
+            } else if let Some(word) = column.strip_prefix("text=") {
+                text.push((word.to_string(), true));
+            } else if let Some(word) = column.strip_prefix("!text=") {
+                text.push((word.to_string(), false));
+            } else {
+                input.push(column.parse().map_err(|e| format!("line {}: {}", line_no, e))?);
+            }
+        }
+
+        let rng = Rng::with_algorithm(algorithm, seed);
+        cases.push(Case { rom: dir.join(columns[0]), frames, quirks, rng, hash, input, text, line: line_no });
+    }
+
+    Ok(cases)
+}
+
+/// Run `rom` for `frames` frames with the random numbers of `rng`, feeding `input` along the way
+pub fn run(rom: &[u8], frames: u64, quirks: Quirks, rng: Rng, input: &[InputEvent]) -> Result<Emulator, Chip8Error> {
+    let mut emu = Emulator::new();
+    emu.quirks = quirks;
+    emu.rng = rng;
+    emu.memory.load_bytes(rom)?;
+
+    for frame in 0..frames {
+        for event in input.iter().filter(|e| e.frame == frame) {
+            emu.input.set(event.key as usize, event.pressed);
+        }
+
+        emu.run_frame()?;
+    }
+
+    Ok(emu)
+}
+
+/// FNV-1a hash of the pixels and resolution of `display`
+pub fn framebuffer_hash(display: &Display) -> u64 {
+    fnv1a(std::iter::once(display.hires as u8).chain(display.pixels().iter().copied()))
+}
+
+/// 64-bit FNV-1a hash of `bytes`
+pub fn fnv1a<I: IntoIterator<Item = u8>>(bytes: I) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+/// Text picture of `display`, one character per pixel
+pub fn dump(display: &Display) -> String {
+    let mut out = String::new();
+
+    for row in display.pixels().chunks(display.width()) {
+        out.extend(row.iter().map(|px| match px {
+            0 => '.',
+            1 => '#',
+            2 => '+',
+            _ => '@',
+        }));
+        out.push('\n');
+    }
+
+    out
+}
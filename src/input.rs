@@ -14,6 +14,12 @@ pub struct Input {
     pub input: [u8; 16],
 }
 
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Input {
     pub fn new() -> Self {
         Input {
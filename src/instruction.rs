@@ -0,0 +1,372 @@
+use crate::quirks::Quirks;
+
+use std::fmt;
+
+// Decoded form of every opcode the interpreter understands. Both `Emulator::execute_instruction`
+// and the disassembler go through `Instruction::decode`, so an opcode is either understood by
+// both or by neither, and the trace output always matches the disassembly.
+//
+// x and y are register numbers, n a nibble, kk a byte and nnn an address.
+
+/// A single decoded Chip-8, SUPER-CHIP or XO-CHIP instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn - SYS addr, a machine code routine on the original hardware
+    Sys(u16),
+    /// 00E0 - CLS
+    Cls,
+    /// 00EE - RET
+    Ret,
+    /// 00Cn - SCD nibble (SUPER-CHIP)
+    ScrollDown(u8),
+    /// 00Dn - SCU nibble (XO-CHIP)
+    ScrollUp(u8),
+    /// 00FB - SCR (SUPER-CHIP)
+    ScrollRight,
+    /// 00FC - SCL (SUPER-CHIP)
+    ScrollLeft,
+    /// 00FD - EXIT (SUPER-CHIP)
+    Exit,
+    /// 00FE - LOW (SUPER-CHIP)
+    Low,
+    /// 00FF - HIGH (SUPER-CHIP)
+    High,
+    /// 1nnn - JP addr
+    Jump(u16),
+    /// 2nnn - CALL addr
+    Call(u16),
+    /// 3xkk - SE Vx, byte
+    SkipEqByte(u8, u8),
+    /// 4xkk - SNE Vx, byte
+    SkipNeByte(u8, u8),
+    /// 5xy0 - SE Vx, Vy
+    SkipEqReg(u8, u8),
+    /// 5xy2 - LD [I], Vx - Vy (XO-CHIP)
+    StoreRange(u8, u8),
+    /// 5xy3 - LD Vx - Vy, [I] (XO-CHIP)
+    LoadRange(u8, u8),
+    /// 6xkk - LD Vx, byte
+    LoadByte(u8, u8),
+    /// 7xkk - ADD Vx, byte
+    AddByte(u8, u8),
+    /// 8xy0 - LD Vx, Vy
+    LoadReg(u8, u8),
+    /// 8xy1 - OR Vx, Vy
+    Or(u8, u8),
+    /// 8xy2 - AND Vx, Vy
+    And(u8, u8),
+    /// 8xy3 - XOR Vx, Vy
+    Xor(u8, u8),
+    /// 8xy4 - ADD Vx, Vy
+    AddReg(u8, u8),
+    /// 8xy5 - SUB Vx, Vy
+    Sub(u8, u8),
+    /// 8xy6 - SHR Vx {, Vy}
+    ShiftRight(u8, u8),
+    /// 8xy7 - SUBN Vx, Vy
+    SubN(u8, u8),
+    /// 8xyE - SHL Vx {, Vy}
+    ShiftLeft(u8, u8),
+    /// 9xy0 - SNE Vx, Vy
+    SkipNeReg(u8, u8),
+    /// Annn - LD I, addr
+    LoadI(u16),
+    /// Bnnn - JP V0, addr, or Bxnn - JP Vx, addr with the jump_vx quirk
+    JumpOffset(u8, u16),
+    /// Cxkk - RND Vx, byte
+    Random(u8, u8),
+    /// Dxyn - DRW Vx, Vy, nibble
+    Draw(u8, u8, u8),
+    /// Ex9E - SKP Vx
+    SkipKey(u8),
+    /// ExA1 - SKNP Vx
+    SkipNotKey(u8),
+    /// F000 nnnn - LD I, long addr (XO-CHIP)
+    LoadILong(u16),
+    /// Fn01 - PLANE n (XO-CHIP)
+    Plane(u8),
+    /// F002 - AUDIO (XO-CHIP)
+    Audio,
+    /// Fx07 - LD Vx, DT
+    LoadDelay(u8),
+    /// Fx0A - LD Vx, K
+    WaitKey(u8),
+    /// Fx15 - LD DT, Vx
+    SetDelay(u8),
+    /// Fx18 - LD ST, Vx
+    SetSound(u8),
+    /// Fx1E - ADD I, Vx
+    AddI(u8),
+    /// Fx29 - LD F, Vx
+    Font(u8),
+    /// Fx30 - LD HF, Vx (SUPER-CHIP)
+    BigFont(u8),
+    /// Fx33 - LD B, Vx
+    Bcd(u8),
+    /// Fx3A - PITCH Vx (XO-CHIP)
+    Pitch(u8),
+    /// Fx55 - LD [I], Vx
+    Store(u8),
+    /// Fx65 - LD Vx, [I]
+    Load(u8),
+    /// Fx75 - LD R, Vx (SUPER-CHIP)
+    StoreFlags(u8),
+    /// Fx85 - LD Vx, R (SUPER-CHIP)
+    LoadFlags(u8),
+}
+
+impl Instruction {
+    /// Decode `opcode`, `next` is the following word and only used by the 4 byte F000 nnnn
+    pub fn decode(opcode: u16, next: u16) -> Option<Self> {
+        use Instruction::*;
+
+        let x = ((opcode >> 8) & 0xf) as u8;
+        let y = ((opcode >> 4) & 0xf) as u8;
+        let n = (opcode & 0xf) as u8;
+        let kk = (opcode & 0xff) as u8;
+        let nnn = opcode & 0xfff;
+
+        let inst = match opcode >> 12 {
+            0x0 => match opcode {
+                0x00e0 => Cls,
+                0x00ee => Ret,
+                0x00c0..=0x00cf => ScrollDown(n),
+                0x00d0..=0x00df => ScrollUp(n),
+                0x00fb => ScrollRight,
+                0x00fc => ScrollLeft,
+                0x00fd => Exit,
+                0x00fe => Low,
+                0x00ff => High,
+                _ => Sys(nnn),
+            },
+            0x1 => Jump(nnn),
+            0x2 => Call(nnn),
+            0x3 => SkipEqByte(x, kk),
+            0x4 => SkipNeByte(x, kk),
+            0x5 => match n {
+                0x0 => SkipEqReg(x, y),
+                0x2 => StoreRange(x, y),
+                0x3 => LoadRange(x, y),
+                _ => return None,
+            },
+            0x6 => LoadByte(x, kk),
+            0x7 => AddByte(x, kk),
+            0x8 => match n {
+                0x0 => LoadReg(x, y),
+                0x1 => Or(x, y),
+                0x2 => And(x, y),
+                0x3 => Xor(x, y),
+                0x4 => AddReg(x, y),
+                0x5 => Sub(x, y),
+                0x6 => ShiftRight(x, y),
+                0x7 => SubN(x, y),
+                0xe => ShiftLeft(x, y),
+                _ => return None,
+            },
+            0x9 => match n {
+                0x0 => SkipNeReg(x, y),
+                _ => return None,
+            },
+            0xa => LoadI(nnn),
+            0xb => JumpOffset(x, nnn),
+            0xc => Random(x, kk),
+            0xd => Draw(x, y, n),
+            0xe => match kk {
+                0x9e => SkipKey(x),
+                0xa1 => SkipNotKey(x),
+                _ => return None,
+            },
+            0xf => match kk {
+                0x00 if opcode == 0xf000 => LoadILong(next),
+                0x01 => Plane(x),
+                0x02 if opcode == 0xf002 => Audio,
+                0x07 => LoadDelay(x),
+                0x0a => WaitKey(x),
+                0x15 => SetDelay(x),
+                0x18 => SetSound(x),
+                0x1e => AddI(x),
+                0x29 => Font(x),
+                0x30 => BigFont(x),
+                0x33 => Bcd(x),
+                0x3a => Pitch(x),
+                0x55 => Store(x),
+                0x65 => Load(x),
+                0x75 => StoreFlags(x),
+                0x85 => LoadFlags(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(inst)
+    }
+
+    /// Size in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LoadILong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Opcode bytes, the reverse of `decode`
+    pub fn encode(&self) -> Vec<u8> {
+        use Instruction::*;
+
+        let xy = |op: u16, x: u8, y: u8, n: u16| op | (x as u16 & 0xf) << 8 | (y as u16 & 0xf) << 4 | n;
+        let xkk = |op: u16, x: u8, kk: u8| op | (x as u16 & 0xf) << 8 | kk as u16;
+        let x = |op: u16, x: u8| op | (x as u16 & 0xf) << 8;
+
+        let opcode = match *self {
+            Sys(nnn) => nnn & 0xfff,
+            Cls => 0x00e0,
+            Ret => 0x00ee,
+            ScrollDown(n) => 0x00c0 | (n as u16 & 0xf),
+            ScrollUp(n) => 0x00d0 | (n as u16 & 0xf),
+            ScrollRight => 0x00fb,
+            ScrollLeft => 0x00fc,
+            Exit => 0x00fd,
+            Low => 0x00fe,
+            High => 0x00ff,
+            Jump(nnn) => 0x1000 | (nnn & 0xfff),
+            Call(nnn) => 0x2000 | (nnn & 0xfff),
+            SkipEqByte(vx, kk) => xkk(0x3000, vx, kk),
+            SkipNeByte(vx, kk) => xkk(0x4000, vx, kk),
+            SkipEqReg(vx, vy) => xy(0x5000, vx, vy, 0x0),
+            StoreRange(vx, vy) => xy(0x5000, vx, vy, 0x2),
+            LoadRange(vx, vy) => xy(0x5000, vx, vy, 0x3),
+            LoadByte(vx, kk) => xkk(0x6000, vx, kk),
+            AddByte(vx, kk) => xkk(0x7000, vx, kk),
+            LoadReg(vx, vy) => xy(0x8000, vx, vy, 0x0),
+            Or(vx, vy) => xy(0x8000, vx, vy, 0x1),
+            And(vx, vy) => xy(0x8000, vx, vy, 0x2),
+            Xor(vx, vy) => xy(0x8000, vx, vy, 0x3),
+            AddReg(vx, vy) => xy(0x8000, vx, vy, 0x4),
+            Sub(vx, vy) => xy(0x8000, vx, vy, 0x5),
+            ShiftRight(vx, vy) => xy(0x8000, vx, vy, 0x6),
+            SubN(vx, vy) => xy(0x8000, vx, vy, 0x7),
+            ShiftLeft(vx, vy) => xy(0x8000, vx, vy, 0xe),
+            SkipNeReg(vx, vy) => xy(0x9000, vx, vy, 0x0),
+            LoadI(nnn) => 0xa000 | (nnn & 0xfff),
+            // x is the top nibble of nnn, decode always keeps the two in agreement
+            JumpOffset(_, nnn) => 0xb000 | (nnn & 0xfff),
+            Random(vx, kk) => xkk(0xc000, vx, kk),
+            Draw(vx, vy, n) => xy(0xd000, vx, vy, n as u16 & 0xf),
+            SkipKey(vx) => x(0xe09e, vx),
+            SkipNotKey(vx) => x(0xe0a1, vx),
+            LoadILong(nnnn) => return vec![0xf0, 0x00, (nnnn >> 8) as u8, nnnn as u8],
+            Plane(n) => x(0xf001, n),
+            Audio => 0xf002,
+            LoadDelay(vx) => x(0xf007, vx),
+            WaitKey(vx) => x(0xf00a, vx),
+            SetDelay(vx) => x(0xf015, vx),
+            SetSound(vx) => x(0xf018, vx),
+            AddI(vx) => x(0xf01e, vx),
+            Font(vx) => x(0xf029, vx),
+            BigFont(vx) => x(0xf030, vx),
+            Bcd(vx) => x(0xf033, vx),
+            Pitch(vx) => x(0xf03a, vx),
+            Store(vx) => x(0xf055, vx),
+            Load(vx) => x(0xf065, vx),
+            StoreFlags(vx) => x(0xf075, vx),
+            LoadFlags(vx) => x(0xf085, vx),
+        };
+
+        vec![(opcode >> 8) as u8, opcode as u8]
+    }
+
+    /// Address of the code jumped or called to, if known without running it
+    pub fn target(&self) -> Option<u16> {
+        match self {
+            Instruction::Jump(addr) | Instruction::Call(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Whether the next instruction may be skipped
+    pub fn is_skip(&self) -> bool {
+        use Instruction::*;
+
+        matches!(self, SkipEqByte(..) | SkipNeByte(..) | SkipEqReg(..) | SkipNeReg(..) | SkipKey(_) | SkipNotKey(_))
+    }
+
+    /// Mnemonic with addresses replaced by the name `label` returns for them, if any
+    pub fn mnemonic<F: Fn(u16) -> Option<String>>(&self, label: F) -> String {
+        use Instruction::*;
+
+        let addr = |addr: u16, default: String| label(addr).unwrap_or(default);
+
+        match *self {
+            Sys(nnn) => format!("sys {}", addr(nnn, format!("{:#04x}", nnn))),
+            Cls => "cls".to_string(),
+            Ret => "ret".to_string(),
+            ScrollDown(n) => format!("scd {}", n),
+            ScrollUp(n) => format!("scu {}", n),
+            ScrollRight => "scr".to_string(),
+            ScrollLeft => "scl".to_string(),
+            Exit => "exit".to_string(),
+            Low => "low".to_string(),
+            High => "high".to_string(),
+            Jump(nnn) => format!("jp {}", addr(nnn, format!("{:#04x}", nnn))),
+            Call(nnn) => format!("call {}", addr(nnn, format!("{:#04x}", nnn))),
+            SkipEqByte(x, kk) => format!("se v{:x}, {:#02x}", x, kk),
+            SkipNeByte(x, kk) => format!("sne v{:x}, {:#02x}", x, kk),
+            SkipEqReg(x, y) => format!("se v{:x}, v{:x}", x, y),
+            StoreRange(x, y) => format!("ld [i], v{:x} - v{:x}", x, y),
+            LoadRange(x, y) => format!("ld v{:x} - v{:x}, [i]", x, y),
+            LoadByte(x, kk) => format!("ld v{:x}, {:#02x}", x, kk),
+            AddByte(x, kk) => format!("add v{:x}, {:#02x}", x, kk),
+            LoadReg(x, y) => format!("ld v{:x}, v{:x}", x, y),
+            Or(x, y) => format!("or v{:x}, v{:x}", x, y),
+            And(x, y) => format!("and v{:x}, v{:x}", x, y),
+            Xor(x, y) => format!("xor v{:x}, v{:x}", x, y),
+            AddReg(x, y) => format!("add v{:x}, v{:x}", x, y),
+            Sub(x, y) => format!("sub v{:x}, v{:x}", x, y),
+            ShiftRight(x, y) => format!("shr v{:x} {{, v{:x}}}", x, y),
+            SubN(x, y) => format!("subn v{:x}, v{:x}", x, y),
+            ShiftLeft(x, y) => format!("shl v{:x} {{, v{:x}}}", x, y),
+            SkipNeReg(x, y) => format!("sne v{:x}, v{:x}", x, y),
+            LoadI(nnn) => format!("ld i, {}", addr(nnn, format!("{:#02x}", nnn))),
+            // The x nibble is part of the address, `display_with` shows Vx for the jump_vx quirk
+            JumpOffset(_, nnn) => format!("jp v0, {}", addr(nnn, format!("{:#02x}", nnn))),
+            Random(x, kk) => format!("rnd v{:x}, {:#02x}", x, kk),
+            Draw(x, y, n) => format!("drw v{:x}, v{:x}, {}", x, y, n),
+            SkipKey(x) => format!("skp v{:x}", x),
+            SkipNotKey(x) => format!("sknp v{:x}", x),
+            LoadILong(nnnn) => format!("ld i, long {}", addr(nnnn, format!("{:#06x}", nnnn))),
+            Plane(n) => format!("plane {}", n),
+            Audio => "audio".to_string(),
+            LoadDelay(x) => format!("ld v{:x}, dt", x),
+            WaitKey(x) => format!("ld v{:x}, k", x),
+            SetDelay(x) => format!("ld dt, v{:x}", x),
+            SetSound(x) => format!("ld st, v{:x}", x),
+            AddI(x) => format!("add i, v{:x}", x),
+            Font(x) => format!("ld f, v{:x}", x),
+            BigFont(x) => format!("ld hf, v{:x}", x),
+            Bcd(x) => format!("ld b, v{:x}", x),
+            Pitch(x) => format!("pitch v{:x}", x),
+            Store(x) => format!("ld [i], v{:x}", x),
+            Load(x) => format!("ld v{:x}, [i]", x),
+            StoreFlags(x) => format!("ld r, v{:x}", x),
+            LoadFlags(x) => format!("ld v{:x}, r", x),
+        }
+    }
+}
+
+impl Instruction {
+    /// Mnemonic of the instruction as it runs with `quirks`
+    ///
+    /// With the jump_vx quirk Bxnn adds Vx rather than V0, and is shown as `jp vx, xnn`.
+    pub fn display_with(&self, quirks: &Quirks) -> String {
+        match *self {
+            Instruction::JumpOffset(x, nnn) if quirks.jump_vx => format!("jp v{:x}, {:#02x}", x, nnn),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic(|_| None))
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Host keys bound to the Chip-8 keypad. Host keys are named by SDL scancode names, which stand for
+// a physical key position named after a US keyboard whatever the active layout, so the keypad
+// keeps its shape on AZERTY or Dvorak keyboards.
+//
+// Bindings are read from a file where `#` starts a comment. Each line binds a Chip-8 key to the
+// host keys listed after it, replacing the keys it had before. A section named after a ROM file
+// changes bindings for that ROM only:
+//
+//     # chip-8 key = host keys
+//     5 = W, Up
+//     8 = S, Down
+//
+//     [PONG]
+//     1 = Left Shift
+//     4 = Left Ctrl
+//
+// A host key drives a single Chip-8 key, binding it again takes it away from the previous one.
+
+/// Host keys of the Chip-8 keys 0 to F, the keypad on the left of a QWERTY keyboard
+const DEFAULT_KEYS: [&str; 16] = ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"];
+
+/// Chip-8 keys as laid out on the COSMAC VIP keypad, row by row
+pub const KEYPAD_LAYOUT: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xc,
+    0x4, 0x5, 0x6, 0xd,
+    0x7, 0x8, 0x9, 0xe,
+    0xa, 0x0, 0xb, 0xf,
+];
+
+/// Host keys bound to each Chip-8 key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    /// Scancode names for the keys 0 to F
+    pub keys: [Vec<String>; 16],
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyMap {
+    /// Default bindings
+    pub fn new() -> Self {
+        let mut keymap = KeyMap { keys: Default::default() };
+        for (key, name) in DEFAULT_KEYS.iter().enumerate() {
+            keymap.keys[key].push(name.to_string());
+        }
+
+        keymap
+    }
+
+    /// Bind `hosts` to Chip-8 `key` in place of its current keys, taking them from other keys
+    pub fn bind(&mut self, key: u8, hosts: &[String]) {
+        for bound in self.keys.iter_mut() {
+            bound.retain(|name| !hosts.iter().any(|host| host.eq_ignore_ascii_case(name)));
+        }
+
+        self.keys[key as usize & 0xf] = hosts.to_vec();
+    }
+
+    /// Chip-8 key bound to the host key `name`
+    pub fn key(&self, name: &str) -> Option<u8> {
+        self.keys.iter().position(|bound| bound.iter().any(|n| n.eq_ignore_ascii_case(name))).map(|key| key as u8)
+    }
+}
+
+/// Host keys given for a Chip-8 key on one line of a key bindings file
+pub type Binding = (u8, Vec<String>);
+
+/// Contents of a key bindings file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyConfig {
+    /// Bindings for every ROM
+    pub global: Vec<Binding>,
+
+    /// Bindings for single ROMs, by file name
+    pub roms: BTreeMap<String, Vec<Binding>>,
+}
+
+/// Error in a key bindings file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseKeyMapError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseKeyMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseKeyMapError {}
+
+impl FromStr for KeyConfig {
+    type Err = ParseKeyMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = KeyConfig::default();
+        let mut rom: Option<String> = None;
+
+        for (n, line) in s.lines().enumerate() {
+            let err = |msg: String| ParseKeyMapError { line: n + 1, msg };
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']').ok_or_else(|| err(format!("missing ']' after '{}'", line)))?;
+                rom = Some(name.trim().to_string());
+                config.roms.entry(name.trim().to_string()).or_default();
+                continue;
+            }
+
+            let (key, hosts) = line.split_once('=').ok_or_else(|| err(format!("expected 'key = host keys', found '{}'", line)))?;
+            let key = u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .filter(|k| *k < 16)
+                .ok_or_else(|| err(format!("invalid Chip-8 key '{}'", key.trim())))?;
+            let hosts = hosts.split(',').map(str::trim).filter(|h| !h.is_empty()).map(String::from).collect();
+
+            match &rom {
+                Some(rom) => config.roms.entry(rom.clone()).or_default().push((key, hosts)),
+                None => config.global.push((key, hosts)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl fmt::Display for KeyConfig {
+    /// Key bindings file that parses back into the same configuration
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_bindings = |f: &mut fmt::Formatter, bindings: &[Binding]| -> fmt::Result {
+            for (key, hosts) in bindings {
+                writeln!(f, "{:x} = {}", key, hosts.join(", "))?;
+            }
+
+            Ok(())
+        };
+
+        writeln!(f, "# Chip-8 key = host keys, as SDL scancode names")?;
+        write_bindings(f, &self.global)?;
+
+        for (rom, bindings) in &self.roms {
+            writeln!(f)?;
+            writeln!(f, "[{}]", rom)?;
+            write_bindings(f, bindings)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl KeyConfig {
+    /// Read a key bindings file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read key bindings '{}': {}", path.display(), e))?;
+
+        text.parse().map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Write the key bindings file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("cannot create '{}': {}", dir.display(), e))?;
+        }
+
+        std::fs::write(path, self.to_string()).map_err(|e| format!("cannot write key bindings '{}': {}", path.display(), e))
+    }
+
+    /// Store every binding of `keymap` for the ROM file named `rom`, or for all ROMs
+    pub fn set_keymap(&mut self, rom: Option<&str>, keymap: &KeyMap) {
+        let bindings = keymap.keys.iter().enumerate().map(|(key, hosts)| (key as u8, hosts.clone())).collect();
+
+        match rom {
+            Some(rom) => {
+                self.roms.insert(rom.to_string(), bindings);
+            },
+            None => self.global = bindings,
+        }
+    }
+
+    /// Bindings for the ROM file named `rom`, the defaults changed by the global and then the ROM bindings
+    pub fn keymap(&self, rom: Option<&str>) -> KeyMap {
+        let mut keymap = KeyMap::new();
+        let rom_bindings = rom.and_then(|rom| self.roms.get(rom)).map(Vec::as_slice).unwrap_or_default();
+
+        for (key, hosts) in self.global.iter().chain(rom_bindings) {
+            keymap.bind(*key, hosts);
+        }
+
+        keymap
+    }
+}
+
+/// Binds one host key to each Chip-8 key in turn, in keypad order
+#[derive(Debug, Clone)]
+pub struct Rebinding {
+    /// Bindings so far, keys not reached yet keep their previous host keys
+    pub keymap: KeyMap,
+
+    /// ROM file whose bindings are changed, None for all ROMs
+    pub rom: Option<String>,
+
+    /// Index into `KEYPAD_LAYOUT` of the key waiting for a host key
+    position: usize,
+}
+
+impl Rebinding {
+    pub fn new(keymap: KeyMap, rom: Option<String>) -> Self {
+        Rebinding { keymap, rom, position: 0 }
+    }
+
+    /// Chip-8 key waiting for a host key, None once every key is bound
+    pub fn current(&self) -> Option<u8> {
+        KEYPAD_LAYOUT.get(self.position).copied()
+    }
+
+    /// Whether `key` got its host key in this walk through the keypad
+    pub fn is_done(&self, key: u8) -> bool {
+        KEYPAD_LAYOUT[..self.position.min(KEYPAD_LAYOUT.len())].contains(&key)
+    }
+
+    /// Make `host` the only host key of the current key and move on to the next one
+    pub fn bind(&mut self, host: &str) {
+        if let Some(key) = self.current() {
+            self.keymap.bind(key, &[host.to_string()]);
+            self.position += 1;
+        }
+    }
+}
+
+/// Default location of the key bindings file, in the user's configuration directory
+pub fn default_path() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config.join("chip8-emu").join("keymap"))
+}
@@ -0,0 +1,146 @@
+use crate::input::Input;
+use crate::keymap::KEYPAD_LAYOUT;
+use crate::memory::FONTS;
+
+use std::collections::HashMap;
+
+use sdl2::{event::Event, mouse::MouseButton, pixels::Color, rect::Rect, render::Canvas, video::Window};
+
+// On-screen hex keypad pressed with the mouse or touch. Every pointer, the mouse or a finger,
+// holds at most one key: pressing on a key holds it until the pointer is lifted, sliding onto
+// another key moves the press there. The keypad also shows which keys the game sees as held,
+// whether pressed here or on the keyboard.
+
+/// `which` of mouse events SDL synthesizes from touches, SDL_TOUCH_MOUSEID
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
+/// Colors of keys that are held and released
+const HELD: Color = Color::RGB(255, 102, 0);
+const RELEASED: Color = Color::RGB(160, 160, 160);
+
+/// Something pressing keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Pointer {
+    Mouse,
+    Finger(i64),
+}
+
+/// Hex keypad drawn in part of the window
+pub struct VirtualKeypad {
+    /// Area of the window covered by the 4x4 keys
+    pub area: Rect,
+
+    /// Window size, touch positions are relative to it
+    window: (u32, u32),
+
+    /// Key held by each pointer
+    held: HashMap<Pointer, u8>,
+}
+
+impl VirtualKeypad {
+    pub fn new(area: Rect, window: (u32, u32)) -> Self {
+        VirtualKeypad { area, window, held: HashMap::new() }
+    }
+
+    /// Chip-8 key at window position `x`, `y`
+    pub fn key_at(&self, x: i32, y: i32) -> Option<u8> {
+        if !self.area.contains_point((x, y)) {
+            return None;
+        }
+
+        let col = (x - self.area.x()) as u32 * 4 / self.area.width();
+        let row = (y - self.area.y()) as u32 * 4 / self.area.height();
+
+        KEYPAD_LAYOUT.get((row * 4 + col) as usize).copied()
+    }
+
+    /// Press and release keys of `input` for a mouse or touch event, returns whether it was one
+    pub fn handle(&mut self, event: &Event, input: &mut Input) -> bool {
+        let finger = |x: f32, y: f32| ((x * self.window.0 as f32) as i32, (y * self.window.1 as f32) as i32);
+
+        match *event {
+            // Touches arrive as finger events, skip the mouse events SDL makes up for them
+            Event::MouseButtonDown { which, .. } | Event::MouseButtonUp { which, .. } | Event::MouseMotion { which, .. }
+                if which == TOUCH_MOUSE_ID => false,
+
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                self.press(Pointer::Mouse, self.key_at(x, y), input);
+                true
+            },
+            Event::MouseMotion { mousestate, x, y, .. } if mousestate.left() => {
+                self.press(Pointer::Mouse, self.key_at(x, y), input);
+                true
+            },
+            Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+                self.press(Pointer::Mouse, None, input);
+                true
+            },
+
+            Event::FingerDown { finger_id, x, y, .. } | Event::FingerMotion { finger_id, x, y, .. } => {
+                let (x, y) = finger(x, y);
+                self.press(Pointer::Finger(finger_id), self.key_at(x, y), input);
+                true
+            },
+            Event::FingerUp { finger_id, .. } => {
+                self.press(Pointer::Finger(finger_id), None, input);
+                true
+            },
+
+            _ => false,
+        }
+    }
+
+    /// Make `pointer` hold `key`, or nothing, releasing the key it held before
+    fn press(&mut self, pointer: Pointer, key: Option<u8>, input: &mut Input) {
+        let previous = match key {
+            Some(key) => self.held.insert(pointer, key),
+            None => self.held.remove(&pointer),
+        };
+
+        // Another pointer may still hold the key just left
+        if let Some(previous) = previous.filter(|p| Some(*p) != key && !self.held.values().any(|k| k == p)) {
+            input.set(previous as usize, false);
+        }
+
+        if let Some(key) = key {
+            input.set(key as usize, true);
+        }
+    }
+
+    /// Render the keys, filled where `input` has them held
+    pub fn draw(&self, canvas: &mut Canvas<Window>, input: &Input) {
+        let cell = self.area.width() / 4;
+
+        for (idx, key) in KEYPAD_LAYOUT.iter().enumerate() {
+            let x = self.area.x() + (cell * (idx as u32 % 4)) as i32;
+            let y = self.area.y() + (cell * (idx as u32 / 4)) as i32;
+
+            let held = input.poll(*key as usize) != 0;
+            draw_key(canvas, Rect::new(x, y, cell, cell), *key, if held { HELD } else { RELEASED }, held);
+        }
+    }
+}
+
+/// Draw `key` as a square outline with its digit in `color`, or as a filled square
+pub(crate) fn draw_key(canvas: &mut Canvas<Window>, cell: Rect, key: u8, color: Color, filled: bool) {
+    let margin = (cell.width() / 16) as i32;
+    let size = cell.width() - 2 * margin as u32;
+    let square = Rect::new(cell.x() + margin, cell.y() + margin, size, size);
+
+    canvas.set_draw_color(color);
+    let _ = if filled { canvas.fill_rect(square) } else { canvas.draw_rect(square) };
+
+    // The 4x5 font sprite at half the cell height
+    let px = (cell.width() / 10).max(1);
+    let left = cell.x() + ((cell.width() - px * 4) / 2) as i32;
+    let top = cell.y() + ((cell.height() - px * 5) / 2) as i32;
+
+    canvas.set_draw_color(if filled { Color::RGB(0, 0, 0) } else { color });
+    for (row, bits) in FONTS[key as usize * 5..][..5].iter().enumerate() {
+        for col in 0..4 {
+            if bits & (0x80 >> col) != 0 {
+                let _ = canvas.fill_rect(Rect::new(left + (px * col) as i32, top + (px * row as u32) as i32, px, px));
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+pub mod cpu;
+pub mod memory;
+pub mod display;
+pub mod input;
+pub mod keymap;
+pub mod emulator;
+pub mod error;
+pub mod quirks;
+pub mod audio;
+pub mod rng;
+pub mod state;
+pub mod rewind;
+pub mod movie;
+pub mod instruction;
+pub mod disasm;
+pub mod asm;
+pub mod debugger;
+pub mod trace;
+pub mod tracediff;
+pub mod golden;
+pub mod ocr;
+pub mod octo;
+pub mod speed;
+
+#[cfg(feature = "sdl")]
+pub mod frontend;
+#[cfg(feature = "sdl")]
+pub mod keypad;
@@ -1,18 +1,378 @@
-pub mod cpu;
-pub mod memory;
-pub mod display;
-pub mod input;
-pub mod emulator;
+use chip8_emu::emulator::{Emulator, CYCLES_PER_FRAME, FRAME_HZ};
+use chip8_emu::quirks::{Quirks};
+use chip8_emu::rng::{Algorithm, Rng};
+use chip8_emu::rewind::{REWIND_BUDGET, REWIND_INTERVAL};
+use chip8_emu::debugger::{Debugger, Action};
+use chip8_emu::movie::{Movie, Player, Recorder};
+use chip8_emu::octo;
+use chip8_emu::trace::{self, Tracer, TraceFormat};
+#[cfg(feature = "sdl")]
+use chip8_emu::keymap::{self, KeyConfig};
+#[cfg(feature = "sdl")]
+use chip8_emu::rewind::{Rewind};
+#[cfg(feature = "sdl")]
+use chip8_emu::frontend::{Frontend};
 
-use emulator::{Emulator};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+#[cfg(feature = "sdl")]
+use std::path::{Path, PathBuf};
 
-fn main() -> Result<(), String> {
+/// Default size in window pixels of a single Chip-8 pixel
+const SCREEN_SCALE: u32 = 16;
 
+const USAGE: &str = "\
+Usage: chip8-emu [OPTIONS] <ROM>
+
+Arguments:
+  <ROM>                  Chip-8 program to load at 0x200, .8o Octo sources are compiled first
+
+Options:
+  -c, --clock <HZ>       Instructions executed per second, a multiple of 60 [default: 600]
+      --ipf <N>          Instructions executed per 60Hz frame, the same as --clock N*60 [default: 10]
+  -s, --scale <N>        Window pixels per Chip-8 pixel, even [default: 16]
+  -q, --quirks <PROFILE> Interpreter quirks: vip, chip48, schip, xochip [default: default]
+                         Individual quirks can be toggled with ,+name or ,-name:
+                         shift-vy, load-store-x, load-store-x1, jump-vx,
+                         vf-reset, wrap, display-wait, add-i-overflow
+      --seed <N>         Seed for the Cxkk random numbers, the same seed gives the same numbers
+                         [default: random]
+      --rng <ALGORITHM>  Cxkk random number generator: xorshift, or vip for the COSMAC VIP
+                         interpreter's own routine [default: xorshift]
+  -m, --mute             Disable the buzzer
+  -k, --keymap <FILE>    Key bindings [default: ~/.config/chip8-emu/keymap, if it exists]
+      --keypad           Show a keypad beside the screen to press with the mouse or touch
+      --rewind-interval <N>
+                         Instructions between rewind snapshots [default: 60]
+      --rewind-budget <MB>
+                         Memory used for rewinding, 0 disables it [default: 8]
+  -t, --trace            Print PC, opcode, disassembly and registers of each executed instruction,
+                         also enabled by any of the --trace-* options
+      --trace-file <FILE>
+                         Write the trace to FILE instead of stdout
+      --trace-format <FORMAT>
+                         Trace line layout: default, registers [default: default]
+      --trace-range <START-END>
+                         Only trace instructions at these hex addresses, e.g. 200-2ff
+      --trace-last <N>   Only write the last N trace lines, when execution fails
+  -d, --debug            Start in the command-line debugger
+      --record <FILE>    Record the keypad input to an input movie, written when the window closes
+      --play <FILE>      Play back an input movie, its quirks, clock and generator replace the options
+      --verify           Play the --play movie headless and check it ends in the recorded state
+      --headless         Run without a window
+  -n, --cycles <N>       Stop after N instructions (headless only)
+  -h, --help             Print this help";
+
+/// Runtime options parsed from the command line
+struct Options {
+    rom: String,
+    cycles_per_frame: u32,
+    scale: u32,
+    quirks: Quirks,
+    seed: Option<u64>,
+    rng: Algorithm,
+    mute: bool,
+    keymap: Option<String>,
+    keypad: bool,
+    rewind_interval: u64,
+    rewind_budget: usize,
+    trace: bool,
+    trace_file: Option<String>,
+    trace_format: TraceFormat,
+    trace_range: Option<RangeInclusive<u16>>,
+    trace_last: usize,
+    debug: bool,
+    record: Option<String>,
+    play: Option<String>,
+    verify: bool,
+    headless: bool,
+    cycles: Option<u64>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut rom = None;
+        let mut opts = Options {
+            rom: String::new(),
+            cycles_per_frame: CYCLES_PER_FRAME,
+            scale: SCREEN_SCALE,
+            quirks: Quirks::default(),
+            seed: None,
+            rng: Algorithm::Xorshift,
+            mute: false,
+            keymap: None,
+            keypad: false,
+            rewind_interval: REWIND_INTERVAL,
+            rewind_budget: REWIND_BUDGET,
+            trace: false,
+            trace_file: None,
+            trace_format: TraceFormat::Default,
+            trace_range: None,
+            trace_last: 0,
+            debug: false,
+            record: None,
+            play: None,
+            verify: false,
+            headless: false,
+            cycles: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                },
+                "-c" | "--clock" => {
+                    let hz: u32 = parse_value(&arg, args.next())?;
+                    if !hz.is_multiple_of(FRAME_HZ) {
+                        return Err(format!("clock speed of {} is not a multiple of {} instructions per second", hz, FRAME_HZ));
+                    }
+                    opts.cycles_per_frame = hz / FRAME_HZ;
+                },
+                "--ipf" => opts.cycles_per_frame = parse_value(&arg, args.next())?,
+                "-s" | "--scale" => opts.scale = parse_value(&arg, args.next())?,
+                "-q" | "--quirks" => opts.quirks = parse_value(&arg, args.next())?,
+                "--seed" => opts.seed = Some(parse_value(&arg, args.next())?),
+                "--rng" => opts.rng = parse_value(&arg, args.next())?,
+                "-n" | "--cycles" => opts.cycles = Some(parse_value(&arg, args.next())?),
+                "-m" | "--mute" => opts.mute = true,
+                "-k" | "--keymap" => opts.keymap = Some(parse_value(&arg, args.next())?),
+                "--keypad" => opts.keypad = true,
+                "--rewind-interval" => opts.rewind_interval = parse_value(&arg, args.next())?,
+                "--rewind-budget" => {
+                    let megabytes: usize = parse_value(&arg, args.next())?;
+                    opts.rewind_budget = megabytes
+                        .checked_mul(1024 * 1024)
+                        .ok_or(format!("rewind budget of {} MB is too large", megabytes))?;
+                },
+                "-t" | "--trace" => opts.trace = true,
+                "--trace-file" => opts.trace_file = Some(parse_value(&arg, args.next())?),
+                "--trace-format" => opts.trace_format = parse_value(&arg, args.next())?,
+                "--trace-range" => {
+                    let value = args.next().ok_or(format!("option '{}' requires a value", arg))?;
+                    let range = trace::parse_range(&value).ok_or(format!("invalid value '{}' for option '{}'", value, arg))?;
+                    opts.trace_range = Some(range);
+                },
+                "--trace-last" => opts.trace_last = parse_value(&arg, args.next())?,
+                "-d" | "--debug" => opts.debug = true,
+                "--record" => opts.record = Some(parse_value(&arg, args.next())?),
+                "--play" => opts.play = Some(parse_value(&arg, args.next())?),
+                "--verify" => opts.verify = true,
+                "--headless" => opts.headless = true,
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ if rom.is_none() => rom = Some(arg),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        if opts.cycles_per_frame == 0 {
+            return Err(format!("clock speed must be at least {} instructions per second", FRAME_HZ));
+        }
+
+        if opts.rewind_interval == 0 {
+            return Err("rewind interval must be greater than 0".to_string());
+        }
+
+        // High resolution pixels are drawn at half the scale
+        if opts.scale == 0 || !opts.scale.is_multiple_of(2) {
+            return Err("window scale must be a positive even number".to_string());
+        }
+
+        // Verifying a movie is a headless playback
+        if opts.verify && opts.play.is_none() {
+            return Err("--verify requires --play".to_string());
+        }
+        opts.headless |= opts.verify;
+
+        if opts.record.is_some() && (opts.play.is_some() || opts.headless) {
+            return Err("--record needs the window and can not be combined with --play".to_string());
+        }
+
+        // Any of the trace options turns tracing on
+        opts.trace |= opts.trace_file.is_some() || opts.trace_range.is_some() || opts.trace_last > 0
+            || opts.trace_format != TraceFormat::Default;
+
+        opts.rom = rom.ok_or("missing <ROM> argument")?;
+
+        Ok(opts)
+    }
+}
+
+/// Parse the value following `flag`
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("option '{}' requires a value", flag))?;
+
+    value.parse().map_err(|_| format!("invalid value '{}' for option '{}'", value, flag))
+}
+
+fn main() {
+    let opts = match Options::parse(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("Try 'chip8-emu --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(opts) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(opts: Options) -> Result<(), String> {
     let mut emu = Emulator::new();
+    emu.quirks = opts.quirks;
+    emu.cycles_per_frame = opts.cycles_per_frame;
+    // Without a seed the generator keeps the random one it started with
+    emu.rng = Rng::with_algorithm(opts.rng, opts.seed.unwrap_or(emu.rng.state));
+
+    let mut debugger = Debugger::new();
+
+    // Octo sources are compiled on the fly, their breakpoints and monitors go to the debugger
+    let rom = if opts.rom.ends_with(".8o") {
+        let program = octo::compile_file(&opts.rom).map_err(|e| e.to_string())?;
+        debugger.breakpoints.extend(program.breakpoints.keys());
+        debugger.monitors = program.monitors;
+        program.rom
+    } else {
+        std::fs::read(&opts.rom).map_err(|e| format!("cannot read ROM '{}': {}", opts.rom, e))?
+    };
+    emu.memory.load_bytes(&rom).map_err(|e| format!("cannot load ROM '{}': {}", opts.rom, e))?;
+
+    let player = match &opts.play {
+        Some(path) => {
+            let movie = Movie::load(path)?;
+            movie.check_rom(&rom).map_err(|e| format!("{}: {}", path, e))?;
+            movie.apply(&mut emu);
+            Some(Player::new(movie))
+        },
+        None => None,
+    };
+    let recorder = opts.record.as_ref().map(|_| Recorder::new(&rom, &emu));
+
+    if opts.debug {
+        debugger.break_now();
+    }
 
-    emu.memory.load("games/PONG");
+    let tracer = if opts.trace { Some(tracer(&opts)?) } else { None };
 
-    emu.run();
+    if opts.headless {
+        return run_headless(&mut emu, debugger, tracer, player, &opts);
+    }
+
+    run_window(&mut emu, debugger, tracer, (player, recorder), &opts)
+
+}
+
+/// Set up the instruction trace selected by the --trace options
+fn tracer(opts: &Options) -> Result<Tracer, String> {
+    let out: Box<dyn Write> = match &opts.trace_file {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("cannot create trace file '{}': {}", path, e))?;
+            Box::new(BufWriter::new(file))
+        },
+        None => Box::new(io::stdout()),
+    };
+
+    let mut tracer = Tracer::new(out);
+    tracer.format = opts.trace_format;
+    tracer.range = opts.trace_range.clone();
+    tracer.keep_last(opts.trace_last);
+
+    Ok(tracer)
+}
+
+/// Execute without any display, as fast as possible
+///
+/// Errors end the run, unless it was started with --debug and the debugger can take over. A movie
+/// being played ends the run when it is over.
+fn run_headless(emu: &mut Emulator, mut debugger: Debugger, mut tracer: Option<Tracer>, mut player: Option<Player>, opts: &Options) -> Result<(), String> {
+    let mut executed = 0;
+
+    while opts.cycles.is_none_or(|n| executed < n) && !emu.exited {
+        if debugger.should_break(emu) && debugger.repl(emu) == Action::Quit {
+            break;
+        }
+
+        if player.as_mut().is_some_and(|player| !player.play(emu)) {
+            break;
+        }
+
+        if let Some(tracer) = tracer.as_mut() {
+            tracer.record(emu).map_err(|e| format!("cannot write trace: {}", e))?;
+        }
+
+        if let Err(e) = emu.step() {
+            eprintln!("{}", emu.registers);
+            if let Some(tracer) = tracer.as_mut() {
+                tracer.crash().map_err(|e| format!("cannot write trace: {}", e))?;
+            }
+            if !opts.debug || !debugger.attached() {
+                return Err(format!("emulation halted: {}", e));
+            }
+
+            eprintln!("emulation halted: {}", e);
+            debugger.break_now();
+        }
+        executed += 1;
+    }
+
+    if let Some(tracer) = tracer.as_mut() {
+        tracer.flush().map_err(|e| format!("cannot write trace: {}", e))?;
+    }
+
+    if let Some(player) = player.filter(|_| opts.verify) {
+        player.verify(emu)?;
+        println!("movie verified, {} instructions ending in state hash {:016x}", player.movie.length, player.movie.hash);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sdl")]
+fn run_window(emu: &mut Emulator, debugger: Debugger, tracer: Option<Tracer>, movie: (Option<Player>, Option<Recorder>), opts: &Options) -> Result<(), String> {
+    let mut frontend = Frontend::new(opts.scale, opts.keypad)?;
+    frontend.mute = opts.mute;
+    let (config, path) = key_config(opts)?;
+    frontend.set_key_config(config, path, rom_name(&opts.rom))?;
+    frontend.state_path = opts.rom.clone().into();
+    frontend.rewind = Rewind::new(opts.rewind_interval, opts.rewind_budget);
+    frontend.debugger = debugger;
+    frontend.tracer = tracer;
+    (frontend.player, frontend.recorder) = movie;
+    if let Some(path) = &opts.record {
+        frontend.movie_path = path.into();
+    }
+
+    frontend.run(emu);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Key bindings from --keymap, or from the default file when there is one, and where to save them
+#[cfg(feature = "sdl")]
+fn key_config(opts: &Options) -> Result<(KeyConfig, Option<PathBuf>), String> {
+    let path = opts.keymap.as_ref().map(PathBuf::from).or_else(keymap::default_path);
+
+    let config = match &path {
+        Some(path) if opts.keymap.is_some() || path.exists() => KeyConfig::load(path)?,
+        _ => KeyConfig::default(),
+    };
+
+    Ok((config, path))
+}
+
+/// File name of the ROM, which selects its key bindings
+#[cfg(feature = "sdl")]
+fn rom_name(rom: &str) -> Option<String> {
+    Path::new(rom).file_name().and_then(|name| name.to_str()).map(String::from)
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_window(_emu: &mut Emulator, _debugger: Debugger, _tracer: Option<Tracer>, _movie: (Option<Player>, Option<Recorder>), _opts: &Options) -> Result<(), String> {
+    Err("chip8-emu was built without the `sdl` feature, only --headless is available".to_string())
+}
@@ -1,6 +1,13 @@
+use crate::error::Chip8Error;
+
 use std::path::Path;
 
 // Memory Map:
+// +---------------+= 0xFFFF (65535) End of XO-CHIP RAM
+// |   XO-CHIP     |
+// |   extended    |
+// |   memory      |
+// +---------------+= 0x1000 (4096)
 // +---------------+= 0xFFF (4095) End of Chip-8 RAM
 // |               |
 // |               |
@@ -25,7 +32,7 @@ use std::path::Path;
 // +---------------+= 0x000 (0) Start of Chip-8 RAM
 
 /// Reserved Font Data for Interpreters
-const FONTS: [u8; 80] = [
+pub const FONTS: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0,   // 0
     0x20, 0x60, 0x20, 0x20, 0x70,   // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0,   // 2
@@ -44,14 +51,46 @@ const FONTS: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80,   // F
 ];
 
+/// Reserved SUPER-CHIP 8x10 Font Data for Interpreters
+pub const BIG_FONTS: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF,   // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF,   // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,   // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,   // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03,   // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,   // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,   // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,   // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,   // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,   // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,   // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC,   // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,   // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,   // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,   // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0,   // F
+];
+
+/// Offset to the 4x5 Font Data, 5 bytes per digit
+pub const FONT_OFFSET: usize = 0;
+
+/// Offset to the 8x10 Font Data, 10 bytes per digit
+pub const BIG_FONT_OFFSET: usize = FONT_OFFSET + FONTS.len();
+
 /// Offset to Game Data Region of Memory Map
 const GAME_DATA_OFFSET: usize = 512;
 
+/// Size of the XO-CHIP address space, classic programs only use the first 4K
+pub const MEMORY_SIZE: usize = 0x10000;
+
+/// Largest game that fits in the game data memory region
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - GAME_DATA_OFFSET;
+
 /// Chip-8 memory mapping
 #[derive(Debug, Clone, Copy)]
 pub struct Memory {
-    /// 4K memory region
-    pub memory: [u8; 4096],
+    /// 64K memory region
+    pub memory: [u8; MEMORY_SIZE],
 
     /// Stack
     pub stack: [u16;   16],
@@ -61,53 +100,67 @@ pub struct Memory {
 
 }
 
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Memory {
     pub fn new() -> Self {
 
         let mut memory = Memory {
-            memory: [0; 4096],
+            memory: [0; MEMORY_SIZE],
             stack: [0; 16],
             sp: usize::MAX,
         };
 
         // Copy font data into memory region
-        memory.memory[..80].copy_from_slice(&FONTS);
+        memory.memory[FONT_OFFSET..FONT_OFFSET + FONTS.len()].copy_from_slice(&FONTS);
+        memory.memory[BIG_FONT_OFFSET..BIG_FONT_OFFSET + BIG_FONTS.len()].copy_from_slice(&BIG_FONTS);
 
         memory
     }
 
     /// Read from memory
-    pub fn read(self, offset: usize) -> u8 {
-        self.memory[offset]
+    pub fn read(&self, offset: usize) -> Result<u8, Chip8Error> {
+        self.memory.get(offset).copied().ok_or(Chip8Error::MemoryFault(offset))
     }
 
     /// Write to memory
-    pub fn write(&mut self, offset: usize, data: u8) {
-        self.memory[offset] = data
+    pub fn write(&mut self, offset: usize, data: u8) -> Result<(), Chip8Error> {
+        let cell = self.memory.get_mut(offset).ok_or(Chip8Error::MemoryFault(offset))?;
+        *cell = data;
+
+        Ok(())
     }
 
     /// Pop a value off of the stack
-    pub fn pop(&mut self) -> u16 {
-        assert!(self.sp != usize::MAX);
+    pub fn pop(&mut self) -> Result<u16, Chip8Error> {
+        if self.sp == usize::MAX {
+            return Err(Chip8Error::StackUnderflow);
+        }
+
         let data = self.stack[self.sp];
         self.sp = self.sp.wrapping_sub(1);
 
-        data
+        Ok(data)
     }
 
     /// Push a value onto the stack
-    pub fn push(&mut self, val: u16) {
-        assert!(self.sp != 15);
+    pub fn push(&mut self, val: u16) -> Result<(), Chip8Error> {
+        if self.sp == self.stack.len() - 1 {
+            return Err(Chip8Error::StackOverflow);
+        }
+
         self.sp = self.sp.wrapping_add(1);
         self.stack[self.sp] = val;
+
+        Ok(())
     }
 
     /// Read the instruction at `offset`
-    pub fn read_inst(self, offset: usize) -> u16 {
-        if offset >= 4096 {
-            panic!("read_inst: out of bounds read!");
-        }
-
+    pub fn read_inst(&self, offset: usize) -> Result<u16, Chip8Error> {
         // I originally had this due to a statement in cowgod's chip-8 technical reference stating
         // that instructions need to start on even addresses, but this appears to be an inaccurate statement
 
@@ -115,18 +168,27 @@ impl Memory {
         //     panic!("Instruction not on even memory alignment");
         // }
 
-        ((self.read(offset) as u16) << 8) + self.read(offset+1) as u16
+        Ok(((self.read(offset)? as u16) << 8) + self.read(offset + 1)? as u16)
     }
 
     /// Load a game file into the game data memory region
-    pub fn load<P: AsRef<Path>>(&mut self, filename: P) -> Option<()> {
+    pub fn load<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), Chip8Error> {
 
         // Read the input file
-        let contents = std::fs::read(filename).ok().expect("Failed reading file contents");
+        let contents = std::fs::read(filename)?;
+
+        self.load_bytes(&contents)
+    }
+
+    /// Load game data already in memory into the game data memory region
+    pub fn load_bytes(&mut self, contents: &[u8]) -> Result<(), Chip8Error> {
+        if contents.len() > MAX_ROM_SIZE {
+            return Err(Chip8Error::RomTooLarge { size: contents.len(), max: MAX_ROM_SIZE });
+        }
 
         // Write game data into game data memory region
-        self.memory[GAME_DATA_OFFSET..GAME_DATA_OFFSET + contents.len()].copy_from_slice(&contents);
+        self.memory[GAME_DATA_OFFSET..GAME_DATA_OFFSET + contents.len()].copy_from_slice(contents);
 
-        Some(())
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,341 @@
+use crate::emulator::Emulator;
+use crate::golden::fnv1a;
+use crate::input::Input;
+use crate::quirks::Quirks;
+use crate::rng::{Algorithm, Rng};
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+// Input movies replay a run exactly. Everything else that affects the machine is fixed before
+// the first instruction, so a movie only holds that setup and the key presses and releases along
+// with the instruction they came before. Playing it back from power on repeats the run
+// bit for bit, and the hash of the machine state at the end proves it did.
+//
+// Movies are text files where `#` starts a comment:
+//
+//     rom     1a2b3c4d5e6f7a8b    # FNV-1a hash of the ROM
+//     rng     xorshift            # random number generator
+//     seed    0123456789abcdef    # its state at power on
+//     quirks  vip
+//     ipf     10                  # instructions per frame
+//     length  360000              # instructions in the movie
+//     hash    fedcba9876543210    # FNV-1a hash of the save state at the end
+//     1520:+5
+//     1880:-5
+//
+// Events are `cycle:+key` to press and `cycle:-key` to release a hexadecimal key before the
+// instruction numbered `cycle`, counting from 0 at power on.
+
+/// Key press or release before an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieEvent {
+    /// Instructions executed before the key changes
+    pub cycle: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+impl fmt::Display for MovieEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}{:x}", self.cycle, if self.pressed { '+' } else { '-' }, self.key)
+    }
+}
+
+/// Recorded run of a ROM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// FNV-1a hash of the ROM the movie was recorded with
+    pub rom_hash: u64,
+
+    /// Random number generator and its state at power on
+    pub rng: Algorithm,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub cycles_per_frame: u32,
+
+    /// Instructions executed during the movie
+    pub length: u64,
+
+    /// `state_hash` of the machine at the end of the movie
+    pub hash: u64,
+
+    /// Key changes in the order they happened
+    pub events: Vec<MovieEvent>,
+}
+
+/// Error in a movie file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMovieError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseMovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseMovieError {}
+
+impl FromStr for Movie {
+    type Err = ParseMovieError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut rom_hash, mut rng, mut seed, mut quirks) = (None, None, None, None);
+        let (mut cycles_per_frame, mut length, mut hash) = (None, None, None);
+        let mut events: Vec<MovieEvent> = Vec::new();
+
+        for (n, line) in s.lines().enumerate() {
+            let err = |msg: String| ParseMovieError { line: n + 1, msg };
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some((cycle, key)) = line.split_once(':') {
+                let invalid = || err(format!("invalid event '{}', expected cycle:+key or cycle:-key", line));
+                let (pressed, key) = match key.strip_prefix('+') {
+                    Some(key) => (true, key),
+                    None => (false, key.strip_prefix('-').ok_or_else(invalid)?),
+                };
+
+                let cycle = cycle.parse().map_err(|_| invalid())?;
+                let key = u8::from_str_radix(key, 16).ok().filter(|k| *k < 16).ok_or_else(invalid)?;
+
+                if events.last().is_some_and(|e| e.cycle > cycle) {
+                    return Err(err(format!("event '{}' is out of order", line)));
+                }
+
+                events.push(MovieEvent { cycle, key, pressed });
+                continue;
+            }
+
+            let (name, value) = line.split_once(char::is_whitespace).ok_or_else(|| err(format!("expected 'name value', found '{}'", line)))?;
+            let value = value.trim();
+            let invalid = || err(format!("invalid {} '{}'", name, value));
+            let hex = || u64::from_str_radix(value, 16).map_err(|_| invalid());
+
+            match name {
+                "rom" => rom_hash = Some(hex()?),
+                "rng" => rng = Some(value.parse().map_err(|e| err(format!("{}", e)))?),
+                "seed" => seed = Some(hex()?),
+                "quirks" => quirks = Some(value.parse().map_err(|e| err(format!("{}", e)))?),
+                "ipf" => cycles_per_frame = Some(value.parse().ok().filter(|ipf| *ipf > 0).ok_or_else(invalid)?),
+                "length" => length = Some(value.parse().map_err(|_| invalid())?),
+                "hash" => hash = Some(hex()?),
+                _ => return Err(err(format!("unknown setting '{}'", name))),
+            }
+        }
+
+        let line = s.lines().count();
+        let missing = |name: &str| ParseMovieError { line, msg: format!("missing {}", name) };
+        let length = length.ok_or_else(|| missing("length"))?;
+
+        if events.last().is_some_and(|e| e.cycle >= length) {
+            return Err(ParseMovieError { line, msg: "events after the end of the movie".to_string() });
+        }
+
+        Ok(Movie {
+            rom_hash: rom_hash.ok_or_else(|| missing("rom"))?,
+            rng: rng.ok_or_else(|| missing("rng"))?,
+            seed: seed.ok_or_else(|| missing("seed"))?,
+            quirks: quirks.ok_or_else(|| missing("quirks"))?,
+            cycles_per_frame: cycles_per_frame.ok_or_else(|| missing("ipf"))?,
+            length,
+            hash: hash.ok_or_else(|| missing("hash"))?,
+            events,
+        })
+    }
+}
+
+impl fmt::Display for Movie {
+    /// Movie file that parses back into the same movie
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# chip8-emu input movie")?;
+        writeln!(f, "rom     {:016x}", self.rom_hash)?;
+        writeln!(f, "rng     {}", self.rng)?;
+        writeln!(f, "seed    {:016x}", self.seed)?;
+        writeln!(f, "quirks  {}", self.quirks)?;
+        writeln!(f, "ipf     {}", self.cycles_per_frame)?;
+        writeln!(f, "length  {}", self.length)?;
+        writeln!(f, "hash    {:016x}", self.hash)?;
+
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Movie {
+    /// Empty movie of `emu`, which has `rom` loaded and is about to run its first instruction
+    pub fn new(rom: &[u8], emu: &Emulator) -> Self {
+        Movie {
+            rom_hash: fnv1a(rom.iter().copied()),
+            rng: emu.rng.algorithm,
+            seed: emu.rng.state,
+            quirks: emu.quirks,
+            cycles_per_frame: emu.cycles_per_frame,
+            length: 0,
+            hash: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Read a movie file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read movie '{}': {}", path.display(), e))?;
+
+        text.parse().map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Write the movie file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+
+        std::fs::write(path, self.to_string()).map_err(|e| format!("cannot write movie '{}': {}", path.display(), e))
+    }
+
+    /// Fails unless `rom` is the ROM the movie was recorded with
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), String> {
+        let hash = fnv1a(rom.iter().copied());
+        if hash != self.rom_hash {
+            return Err(format!("movie was recorded with ROM {:016x}, this ROM is {:016x}", self.rom_hash, hash));
+        }
+
+        Ok(())
+    }
+
+    /// Set up `emu` the way it was at the start of the recording
+    pub fn apply(&self, emu: &mut Emulator) {
+        emu.rng = Rng::with_algorithm(self.rng, self.seed);
+        emu.quirks = self.quirks;
+        emu.cycles_per_frame = self.cycles_per_frame;
+        emu.input = Input::new();
+    }
+}
+
+/// Hash of the complete machine state, equal only for identical runs
+pub fn state_hash(emu: &Emulator) -> u64 {
+    fnv1a(emu.save_state())
+}
+
+/// Builds a movie from the input seen before each instruction
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    movie: Movie,
+
+    /// Instructions executed so far
+    position: u64,
+
+    /// Input state at the last recorded instruction
+    last_input: Input,
+}
+
+impl Recorder {
+    /// Record `emu` from now on, see `Movie::new`
+    pub fn new(rom: &[u8], emu: &Emulator) -> Self {
+        Recorder { movie: Movie::new(rom, emu), position: 0, last_input: emu.input }
+    }
+
+    /// Log input changes, call before executing each instruction
+    pub fn record(&mut self, emu: &Emulator) {
+        for key in 0..16 {
+            if emu.input.input[key] != self.last_input.input[key] {
+                self.movie.events.push(MovieEvent {
+                    cycle: self.position,
+                    key: key as u8,
+                    pressed: emu.input.input[key] != 0,
+                });
+            }
+        }
+
+        self.last_input = emu.input;
+        self.position += 1;
+    }
+
+    /// Drop the recording after `position`, where `emu` was just rewound to
+    pub fn truncate(&mut self, position: u64, emu: &Emulator) {
+        self.movie.events.retain(|e| e.cycle < position);
+        self.position = position;
+        self.last_input = emu.input;
+    }
+
+    /// End the movie with the state of `emu`
+    pub fn finish(mut self, emu: &Emulator) -> Movie {
+        // Input changed after the last instruction never reached the program, leave it out
+        let mut end = emu.clone();
+        end.input = self.last_input;
+
+        self.movie.length = self.position;
+        self.movie.hash = state_hash(&end);
+
+        self.movie
+    }
+}
+
+/// Feeds the input of a movie back before each instruction
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub movie: Movie,
+
+    /// Instructions executed so far
+    position: u64,
+
+    /// Index of the next event to play
+    next: usize,
+}
+
+impl Player {
+    /// Play `movie`, the emulator has to be set up with `Movie::apply` first
+    pub fn new(movie: Movie) -> Self {
+        Player { movie, position: 0, next: 0 }
+    }
+
+    /// Apply the input due before the next instruction, returns false once the movie is over
+    pub fn play(&mut self, emu: &mut Emulator) -> bool {
+        if self.is_done() {
+            return false;
+        }
+
+        while let Some(event) = self.movie.events.get(self.next).filter(|e| e.cycle == self.position) {
+            emu.input.set(event.key as usize, event.pressed);
+            self.next += 1;
+        }
+
+        self.position += 1;
+
+        true
+    }
+
+    /// Whether every instruction of the movie has been played
+    pub fn is_done(&self) -> bool {
+        self.position >= self.movie.length
+    }
+
+    /// Continue from `position`, where the emulator was just rewound to
+    pub fn seek(&mut self, position: u64) {
+        self.position = position;
+        self.next = self.movie.events.iter().position(|e| e.cycle >= position).unwrap_or(self.movie.events.len());
+    }
+
+    /// Fails unless `emu` ended the movie in the state it was recorded in
+    pub fn verify(&self, emu: &Emulator) -> Result<(), String> {
+        if !self.is_done() {
+            return Err(format!("movie stopped after {} of {} instructions", self.position, self.movie.length));
+        }
+
+        let hash = state_hash(emu);
+        if hash != self.movie.hash {
+            return Err(format!("state hash {:016x} at the end of the movie, expected {:016x}", hash, self.movie.hash));
+        }
+
+        Ok(())
+    }
+}
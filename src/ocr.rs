@@ -0,0 +1,263 @@
+use crate::display::Display;
+use crate::memory::{FONTS, BIG_FONTS};
+
+// Reads text off the screen, so test ROMs that report their results visually can be checked
+// automatically. Glyphs are matched exactly against known sprites: the built-in fonts and the
+// characters of common test ROMs. A match also needs a blank pixel border around the glyph,
+// which keeps glyphs from being found inside larger drawings.
+//
+// Every glyph set remembers the cell width it is drawn with and how far apart its characters
+// are placed within a word, wider gaps separate words.
+
+/// Characters drawn from one set of sprites
+struct GlyphSet {
+    /// Characters in the order of their sprites
+    chars: &'static [&'static str],
+
+    /// Sprite rows, `height` bytes per character
+    sprites: &'static [u8],
+
+    /// Columns of the sprite cell, from the left
+    width: usize,
+    height: usize,
+
+    /// Blank columns between cells of the same word
+    spacing: usize,
+}
+
+/// Verdicts of corax89's test_opcode.ch8
+const CORAX_WORDS: [u8; 8] = [
+    0xea, 0xac, 0xaa, 0xea, // OK
+    0xce, 0xaa, 0xaa, 0xae, // NO
+];
+
+/// Characters of corax89's test_opcode.ch8
+const CORAX_GLYPHS: [u8; 56] = [
+    0xe0, 0xa0, 0xa0, 0xe0, // 0
+    0xc0, 0x40, 0x40, 0xe0, // 1
+    0xe0, 0x20, 0xc0, 0xe0, // 2
+    0xe0, 0x60, 0x20, 0xe0, // 3
+    0xa0, 0xe0, 0x20, 0x20, // 4
+    0x60, 0x40, 0x20, 0x40, // 5
+    0xe0, 0x80, 0xe0, 0xe0, // 6
+    0xe0, 0x20, 0x20, 0x20, // 7
+    0xe0, 0xe0, 0xa0, 0xe0, // 8
+    0xe0, 0xe0, 0x20, 0xe0, // 9
+    0x40, 0xa0, 0xe0, 0xa0, // A
+    0xe0, 0xc0, 0x80, 0xe0, // E
+    0xe0, 0x80, 0xc0, 0x80, // F
+    0xa0, 0x40, 0xa0, 0xa0, // X
+];
+
+/// Sprites used by BestCoder's BC_test.ch8
+const BC_GLYPHS: [u8; 32] = [
+    0xf0, 0x88, 0x88, 0xf0, 0x88, 0x88, 0x88, 0xf0, // B
+    0x78, 0x84, 0x84, 0x84, 0x84, 0x84, 0x84, 0x78, // O
+    0x84, 0xc4, 0xa4, 0x94, 0x8c, 0x84, 0x84, 0x84, // N
+    0xff, 0xf0, 0xf0, 0xff, 0xf0, 0xf0, 0xf0, 0xff, // E
+];
+
+const HEX: [&str; 16] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F"];
+
+/// Larger glyphs first, the verdict words win over the letters inside them
+const GLYPH_SETS: [GlyphSet; 5] = [
+    GlyphSet { chars: &HEX, sprites: &BIG_FONTS, width: 8, height: 10, spacing: 2 },
+    GlyphSet { chars: &["B", "O", "N", "E"], sprites: &BC_GLYPHS, width: 8, height: 8, spacing: 1 },
+    GlyphSet { chars: &["OK", "NO"], sprites: &CORAX_WORDS, width: 8, height: 4, spacing: 0 },
+    GlyphSet { chars: &HEX, sprites: &FONTS, width: 4, height: 5, spacing: 2 },
+    GlyphSet {
+        chars: &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "E", "F", "X"],
+        sprites: &CORAX_GLYPHS,
+        width: 4,
+        height: 4,
+        spacing: 0,
+    },
+];
+
+/// Glyph found on the screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    pub text: &'static str,
+
+    /// Sprite cell, which may extend past the lit pixels
+    pub x: isize,
+    pub y: isize,
+    pub width: usize,
+    pub height: usize,
+
+    /// Blank columns allowed before the next glyph of the same word
+    pub spacing: usize,
+
+    /// Bounding box of the lit pixels, x, y, width and height
+    lit: (usize, usize, usize, usize),
+}
+
+/// Lit pixels of a glyph, trimmed to their bounding box
+struct Shape {
+    pixels: Vec<Vec<bool>>,
+
+    /// Offset of the bounding box in the sprite cell
+    dx: usize,
+    dy: usize,
+}
+
+impl Shape {
+    fn new(rows: &[u8], width: usize) -> Option<Self> {
+        let lit = |x: usize, y: usize| rows[y] & (0x80 >> x) != 0;
+        let cols: Vec<usize> = (0..width).filter(|x| (0..rows.len()).any(|y| lit(*x, y))).collect();
+        let lines: Vec<usize> = (0..rows.len()).filter(|y| (0..width).any(|x| lit(x, *y))).collect();
+
+        let (dx, dy) = (*cols.first()?, *lines.first()?);
+        let (w, h) = (cols.last()? - dx + 1, lines.last()? - dy + 1);
+
+        let pixels = (0..h).map(|y| (0..w).map(|x| lit(dx + x, dy + y)).collect()).collect();
+
+        Some(Shape { pixels, dx, dy })
+    }
+
+    fn width(&self) -> usize {
+        self.pixels[0].len()
+    }
+
+    fn height(&self) -> usize {
+        self.pixels.len()
+    }
+
+    /// Whether the shape is at `x`, `y` with blank pixels all around it
+    fn matches(&self, display: &Display, x: usize, y: usize) -> bool {
+        let (w, h) = (display.width() as isize, display.height() as isize);
+        let pixels = display.pixels();
+        let lit = |px: isize, py: isize| px >= 0 && py >= 0 && px < w && py < h && pixels[(py * w + px) as usize] != 0;
+
+        for sy in -1..=self.height() as isize {
+            for sx in -1..=self.width() as isize {
+                let expected = sy >= 0 && sx >= 0 && (sy as usize) < self.height() && (sx as usize) < self.width()
+                    && self.pixels[sy as usize][sx as usize];
+
+                if lit(x as isize + sx, y as isize + sy) != expected {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Every known glyph on the screen, grouped into lines in reading order
+pub fn glyph_lines(display: &Display) -> Vec<Vec<Glyph>> {
+    let mut found: Vec<Glyph> = Vec::new();
+
+    for set in GLYPH_SETS.iter() {
+        for (c, rows) in set.chars.iter().zip(set.sprites.chunks(set.height)) {
+            let shape = match Shape::new(rows, set.width) {
+                Some(shape) => shape,
+                None => continue,
+            };
+
+            for y in 0..display.height().saturating_sub(shape.height() - 1) {
+                for x in 0..display.width().saturating_sub(shape.width() - 1) {
+                    if !shape.matches(display, x, y) {
+                        continue;
+                    }
+
+                    let glyph = Glyph {
+                        text: c,
+                        x: x as isize - shape.dx as isize,
+                        y: y as isize - shape.dy as isize,
+                        width: set.width,
+                        height: set.height,
+                        spacing: set.spacing,
+                        lit: (x, y, shape.width(), shape.height()),
+                    };
+
+                    // Sets are ordered so that a glyph containing another one is found first
+                    if !found.iter().any(|g| overlaps(g, &glyph)) {
+                        found.push(glyph);
+                    }
+                }
+            }
+        }
+    }
+
+    found.sort_by_key(|g| (g.y, g.x));
+    let mut lines: Vec<Vec<Glyph>> = Vec::new();
+    for glyph in found {
+        match lines.last_mut() {
+            Some(line) if same_line(&line[0], &glyph) => line.push(glyph),
+            _ => lines.push(vec![glyph]),
+        }
+    }
+
+    for line in lines.iter_mut() {
+        line.sort_by_key(|g| g.x);
+    }
+
+    lines
+}
+
+/// Whether the cells of two glyphs start within half a cell of each other vertically
+fn same_line(a: &Glyph, b: &Glyph) -> bool {
+    (b.y - a.y).unsigned_abs() <= a.height / 2
+}
+
+/// Whether the lit pixels of two glyphs share any area
+fn overlaps(a: &Glyph, b: &Glyph) -> bool {
+    let ((ax, ay, aw, ah), (bx, by, bw, bh)) = (a.lit, b.lit);
+
+    ax < bx + bw && bx < ax + aw && ay < by + bh && by < ay + ah
+}
+
+/// Text on the screen, one string per line with words separated by single spaces
+pub fn read_text(display: &Display) -> Vec<String> {
+    glyph_lines(display)
+        .iter()
+        .map(|line| {
+            let mut text = String::new();
+
+            for (i, glyph) in line.iter().enumerate() {
+                if let Some(prev) = i.checked_sub(1).map(|p| &line[p]) {
+                    // Glyphs for whole words, like OK, are words of their own however close they are
+                    let gap = glyph.x - (prev.x + prev.width as isize);
+                    if gap > prev.spacing.max(glyph.spacing) as isize || prev.text.len() > 1 || glyph.text.len() > 1 {
+                        text.push(' ');
+                    }
+                }
+                text.push_str(glyph.text);
+            }
+
+            text
+        })
+        .collect()
+}
+
+/// Description of each BC_test.ch8 error code, from BC_test.txt
+const BC_TEST_ERRORS: [&str; 17] = [
+    "3XNN verify that the jump condition is fair",
+    "5XY0 verify that the jump condition is fair",
+    "4XNN verify that the jump condition is fair",
+    "7XNN check the result of the addition",
+    "8XY5 verify that VF is set to 0 when there is a borrow",
+    "8XY5 verify that VF is set to 1 when there is no borrow",
+    "8XY7 verify that VF is set to 0 when there is a borrow",
+    "8XY7 verify that VF is set to 1 when there is no borrow",
+    "8XY1 check the result of the OR operation",
+    "8XY2 check the result of AND operation",
+    "8XY3 check the result of the XOR operation",
+    "8XYE verify that VF is set to the MSB before the shift and VF does not take value 0 every time",
+    "8XYE verify that VF is set to the MSB before the shift and VF does not take value 1 every time",
+    "8XY6 verify that VF is set to the LSB before the shift and VF does not take value 0 every time",
+    "8XY6 verify that VF is set to the LSB before the shift and VF does not take value 1 every time",
+    "FX55 and FX65 verify that these two opcodes are implemented",
+    "FX33 calculating the binary representation is mistaken or the result is poorly stored",
+];
+
+/// Error code and description of a failed BC_test.ch8, shown as `E nn` on the screen
+pub fn bc_test_error(lines: &[String]) -> Option<(u8, &'static str)> {
+    lines.iter().find_map(|line| {
+        let code: u8 = line.strip_prefix("E ")?.trim().parse().ok()?;
+        let description = BC_TEST_ERRORS.get((code as usize).checked_sub(1)?)?;
+
+        Some((code, *description))
+    })
+}
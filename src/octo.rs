@@ -0,0 +1,995 @@
+use crate::asm::AsmError;
+use crate::debugger::Monitor;
+use crate::disasm::ENTRY_POINT;
+use crate::memory::MAX_ROM_SIZE;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+// Compiler for Octo, the high level assembly language most modern Chip-8 programs are written in,
+// see https://github.com/JohnEarnest/Octo/blob/gh-pages/docs/Manual.md for the language.
+//
+// Like Octo this is a single pass over whitespace separated tokens. References to labels that are
+// not defined yet are recorded as fixups and patched once the whole program has been read.
+// Structured control flow (if/begin/else/end, loop/while/again) compiles to skips and jumps.
+// Comparisons other than == and != are built from a subtraction into the compare-temp register,
+// VF unless aliased otherwise, followed by a skip on the borrow flag in VF.
+//
+// Execution starts at the `main` label: a jump to it is placed at 0x200, unless main is the very
+// first thing in the program.
+
+/// Limit on macro expansions, catches macros that invoke themselves
+const MAX_EXPANSIONS: usize = 100_000;
+
+/// Compiled program with the debugging hints found in the source
+#[derive(Debug, Default)]
+pub struct Program {
+    /// ROM bytes to load at 0x200
+    pub rom: Vec<u8>,
+
+    /// `:breakpoint` addresses and their names
+    pub breakpoints: BTreeMap<u16, String>,
+
+    /// `:monitor` memory regions
+    pub monitors: Vec<Monitor>,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// How a label address is patched into the ROM once it is known
+#[derive(Debug, Clone, Copy)]
+enum Fixup {
+    /// Low 12 bits of the opcode at the position
+    Addr,
+    /// Both bytes at the position
+    Long,
+    /// Low nibble of the byte at the position, the high nibble is kept
+    UnpackHigh,
+    /// Byte at the position
+    UnpackLow,
+}
+
+/// Open control structure waiting for its closing keyword
+enum Control {
+    /// `if ... begin`, the jump past the body is at the position
+    If(usize),
+    /// `else`, the jump past the else branch is at the position
+    Else(usize),
+    /// `loop`, the start address and the `while` jumps out of it
+    Loop(u16, Vec<usize>),
+}
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+struct Compiler {
+    file: String,
+
+    /// Remaining tokens, reversed so the next one is popped off the end
+    tokens: Vec<Token>,
+
+    /// Line of the last token read, for error messages
+    line: usize,
+
+    rom: Vec<u8>,
+
+    /// Address the next byte is emitted at
+    here: usize,
+
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+
+    /// Uses of labels that were not defined yet, with the position to patch
+    fixups: Vec<(usize, Fixup, Token)>,
+
+    /// Monitors of labels that were not defined yet, by index
+    monitor_fixups: Vec<(usize, Token)>,
+
+    control: Vec<Control>,
+
+    /// The jump to main at 0x200 is still in place
+    main_jump: bool,
+
+    program: Program,
+}
+
+/// Compile Octo `source`
+pub fn compile(source: &str) -> Result<Program, AsmError> {
+    Compiler::new("<input>", source).run()
+}
+
+/// Compile the Octo source file at `path`
+pub fn compile_file<P: AsRef<Path>>(path: P) -> Result<Program, AsmError> {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let source = std::fs::read_to_string(path).map_err(|e| AsmError { file: name.clone(), line: 0, msg: e.to_string() })?;
+
+    Compiler::new(&name, &source).run()
+}
+
+/// Split `source` into tokens, dropping `#` comments and keeping quoted strings whole
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    for (n, line) in source.lines().enumerate() {
+        let mut chars = line.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            if c == '#' {
+                break;
+            }
+
+            let mut text = c.to_string();
+            if c == '"' {
+                for c in chars.by_ref() {
+                    text.push(c);
+                    if c == '"' {
+                        break;
+                    }
+                }
+            } else {
+                while let Some(c) = chars.peek().filter(|c| !c.is_whitespace()) {
+                    text.push(*c);
+                    chars.next();
+                }
+            }
+
+            tokens.push(Token { text, line: n + 1 });
+        }
+    }
+
+    tokens
+}
+
+/// Parse a number literal, decimal, 0x hexadecimal or 0b binary with an optional minus sign
+fn number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// Register number of `v0` through `vf`
+fn register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+
+    if digit.len() != 1 {
+        return None;
+    }
+
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// Comparison that holds exactly when `op` does not
+fn negate(op: &str) -> Option<&'static str> {
+    Some(match op {
+        "==" => "!=",
+        "!=" => "==",
+        "key" => "-key",
+        "-key" => "key",
+        "<" => ">=",
+        ">=" => "<",
+        ">" => "<=",
+        "<=" => ">",
+        _ => return None,
+    })
+}
+
+impl Compiler {
+    fn new(file: &str, source: &str) -> Self {
+        let mut tokens = tokenize(source);
+        tokens.reverse();
+
+        // The first two bytes hold the jump to main, patched at the end
+        Compiler {
+            file: file.to_string(),
+            tokens,
+            line: 0,
+            rom: vec![0x10, 0x00],
+            here: ENTRY_POINT as usize + 2,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            expansions: 0,
+            fixups: Vec::new(),
+            monitor_fixups: Vec::new(),
+            control: Vec::new(),
+            main_jump: true,
+            program: Program::default(),
+        }
+    }
+
+    fn error<S: Into<String>>(&self, msg: S) -> AsmError {
+        AsmError { file: self.file.clone(), line: self.line, msg: msg.into() }
+    }
+
+    fn run(mut self) -> Result<Program, AsmError> {
+        while let Some(token) = self.next_token() {
+            self.statement(token)?;
+        }
+
+        if !self.control.is_empty() {
+            return Err(self.error("missing 'end' or 'again' at the end of the program"));
+        }
+
+        if self.main_jump {
+            let main = *self.labels.get("main").ok_or_else(|| self.error("the program has no 'main' label"))?;
+            self.patch(0, Fixup::Addr, main)?;
+        }
+
+        for (pos, kind, token) in std::mem::take(&mut self.fixups) {
+            self.line = token.line;
+            let addr = *self.labels.get(&token.text).ok_or_else(|| self.error(format!("undefined name '{}'", token.text)))?;
+            self.patch(pos, kind, addr)?;
+        }
+
+        for (index, token) in std::mem::take(&mut self.monitor_fixups) {
+            self.line = token.line;
+            let addr = *self.labels.get(&token.text).ok_or_else(|| self.error(format!("undefined name '{}'", token.text)))?;
+            self.program.monitors[index].addr = addr;
+        }
+
+        self.program.rom = self.rom;
+
+        Ok(self.program)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.tokens.pop()?;
+        self.line = token.line;
+
+        Some(token)
+    }
+
+    fn expect_token(&mut self) -> Result<Token, AsmError> {
+        self.next_token().ok_or_else(|| self.error("unexpected end of the program"))
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.last().map(|t| t.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.expect_token()?;
+
+        if token.text != text {
+            return Err(self.error(format!("expected '{}', found '{}'", text, token.text)));
+        }
+
+        Ok(())
+    }
+
+    /// Write `byte` at the current address
+    fn emit(&mut self, byte: u8) -> Result<(), AsmError> {
+        let pos = self.here - ENTRY_POINT as usize;
+
+        if pos >= MAX_ROM_SIZE {
+            return Err(self.error(format!("program is larger than the {} bytes available", MAX_ROM_SIZE)));
+        }
+
+        if pos >= self.rom.len() {
+            self.rom.resize(pos + 1, 0);
+        }
+        self.rom[pos] = byte;
+        self.here += 1;
+
+        Ok(())
+    }
+
+    /// Emit a two byte opcode, returns its ROM position
+    fn inst(&mut self, opcode: u16) -> Result<usize, AsmError> {
+        let pos = self.here - ENTRY_POINT as usize;
+
+        self.emit((opcode >> 8) as u8)?;
+        self.emit(opcode as u8)?;
+
+        Ok(pos)
+    }
+
+    /// Put `addr` into the ROM at `pos`, only long fixups reach past 0xFFF
+    fn patch(&mut self, pos: usize, kind: Fixup, addr: u16) -> Result<(), AsmError> {
+        if !matches!(kind, Fixup::Long) && addr > 0xfff {
+            return Err(self.error(format!("address {:#x} is out of range", addr)));
+        }
+
+        match kind {
+            Fixup::Addr => {
+                self.rom[pos] = (self.rom[pos] & 0xf0) | ((addr >> 8) as u8 & 0xf);
+                self.rom[pos + 1] = addr as u8;
+            },
+            Fixup::Long => {
+                self.rom[pos] = (addr >> 8) as u8;
+                self.rom[pos + 1] = addr as u8;
+            },
+            Fixup::UnpackHigh => self.rom[pos] = (self.rom[pos] & 0xf0) | ((addr >> 8) as u8 & 0xf),
+            Fixup::UnpackLow => self.rom[pos] = addr as u8,
+        }
+
+        Ok(())
+    }
+
+    /// Register operand, either `vx` or an alias
+    fn reg(&mut self) -> Result<u8, AsmError> {
+        let token = self.expect_token()?;
+
+        self.reg_of(&token.text).ok_or_else(|| self.error(format!("expected a register, found '{}'", token.text)))
+    }
+
+    fn reg_of(&self, text: &str) -> Option<u8> {
+        register(text).or_else(|| self.aliases.get(text).copied())
+    }
+
+    fn is_reg(&self) -> bool {
+        self.peek().is_some_and(|t| self.reg_of(t).is_some())
+    }
+
+    /// Numeric operand: a number, constant, defined label or `{ expr }`
+    fn value(&mut self) -> Result<f64, AsmError> {
+        let token = self.expect_token()?;
+
+        if token.text == "{" {
+            let expr = self.braced()?;
+            return self.calc(&expr);
+        }
+
+        self.value_of(&token.text).ok_or_else(|| self.error(format!("undefined name '{}'", token.text)))
+    }
+
+    fn value_of(&self, text: &str) -> Option<f64> {
+        number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|addr| *addr as f64))
+    }
+
+    /// Value checked against `min..=max`
+    fn ranged(&mut self, min: i64, max: i64) -> Result<i64, AsmError> {
+        let value = self.value()?.floor() as i64;
+
+        if value < min || value > max {
+            return Err(self.error(format!("value {} is out of range", value)));
+        }
+
+        Ok(value)
+    }
+
+    fn byte(&mut self) -> Result<u8, AsmError> {
+        Ok(self.ranged(-0x80, 0xff)? as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, AsmError> {
+        Ok(self.ranged(0, 0xf)? as u8)
+    }
+
+    /// Address operand, labels that are not defined yet are patched in later
+    fn addr(&mut self, pos: usize, kind: Fixup) -> Result<(), AsmError> {
+        let max = match kind {
+            Fixup::Long => 0xffff,
+            _ => 0xfff,
+        };
+
+        let name = match self.peek() {
+            Some(t) if t != "{" && self.value_of(t).is_none() => self.expect_token()?,
+            _ => {
+                let addr = self.ranged(0, max)? as u16;
+                self.patch(pos, kind, addr)?;
+                return Ok(());
+            },
+        };
+
+        self.fixups.push((pos, kind, name));
+
+        Ok(())
+    }
+
+    /// Tokens up to the matching `}`, the opening brace has already been read
+    fn braced(&mut self) -> Result<Vec<Token>, AsmError> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+
+        loop {
+            let token = self.expect_token()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                },
+                _ => (),
+            }
+            body.push(token);
+        }
+    }
+
+    /// Evaluate a `:calc` expression
+    fn calc(&self, tokens: &[Token]) -> Result<f64, AsmError> {
+        let mut pos = 0;
+        let value = self.calc_expr(tokens, &mut pos)?;
+
+        if pos != tokens.len() {
+            return Err(self.error(format!("unexpected '{}' in expression", tokens[pos].text)));
+        }
+
+        Ok(value)
+    }
+
+    /// Octo evaluates expressions right to left without operator precedence
+    fn calc_expr(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, AsmError> {
+        let lhs = self.calc_term(tokens, pos)?;
+
+        let op = match tokens.get(*pos) {
+            Some(t) if t.text != ")" => t.text.as_str(),
+            _ => return Ok(lhs),
+        };
+        *pos += 1;
+
+        let rhs = self.calc_expr(tokens, pos)?;
+        let (a, b) = (lhs as i64, rhs as i64);
+
+        Ok(match op {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.checked_shl(b as u32).unwrap_or(0) as f64,
+            ">>" => a.checked_shr(b as u32).unwrap_or(0) as f64,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "<" => (lhs < rhs) as i64 as f64,
+            ">" => (lhs > rhs) as i64 as f64,
+            "<=" => (lhs <= rhs) as i64 as f64,
+            ">=" => (lhs >= rhs) as i64 as f64,
+            "==" => (lhs == rhs) as i64 as f64,
+            "!=" => (lhs != rhs) as i64 as f64,
+            _ => return Err(self.error(format!("unknown operator '{}'", op))),
+        })
+    }
+
+    fn calc_term(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, AsmError> {
+        let token = tokens.get(*pos).ok_or_else(|| self.error("incomplete expression"))?;
+        *pos += 1;
+
+        let unary = |f: fn(f64) -> f64, pos: &mut usize| -> Result<f64, AsmError> { Ok(f(self.calc_term(tokens, pos)?)) };
+
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc_expr(tokens, pos)?;
+                match tokens.get(*pos) {
+                    Some(t) if t.text == ")" => *pos += 1,
+                    _ => return Err(self.error("missing ')' in expression")),
+                }
+                Ok(value)
+            },
+            "-" => unary(|v| -v, pos),
+            "~" => unary(|v| !(v as i64) as f64, pos),
+            "!" => unary(|v| (v == 0.0) as i64 as f64, pos),
+            "sin" => unary(f64::sin, pos),
+            "cos" => unary(f64::cos, pos),
+            "tan" => unary(f64::tan, pos),
+            "exp" => unary(f64::exp, pos),
+            "log" => unary(f64::ln, pos),
+            "abs" => unary(f64::abs, pos),
+            "sqrt" => unary(f64::sqrt, pos),
+            "sign" => unary(f64::signum, pos),
+            "ceil" => unary(f64::ceil, pos),
+            "floor" => unary(f64::floor, pos),
+            "@" => {
+                let addr = self.calc_term(tokens, pos)? as usize;
+                let byte = addr.checked_sub(ENTRY_POINT as usize).and_then(|p| self.rom.get(p)).copied().unwrap_or(0);
+                Ok(byte as f64)
+            },
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            text => self.value_of(text).ok_or_else(|| self.error(format!("undefined name '{}'", text))),
+        }
+    }
+
+    /// Define a new name, labels, constants, aliases and macros share one namespace
+    fn define_check(&self, name: &str) -> Result<(), AsmError> {
+        if register(name).is_some() || number(name).is_some() {
+            return Err(self.error(format!("'{}' can not be used as a name", name)));
+        }
+
+        if self.labels.contains_key(name) || self.macros.contains_key(name) {
+            return Err(self.error(format!("'{}' is already defined", name)));
+        }
+
+        Ok(())
+    }
+
+    fn label(&mut self, name: String) -> Result<(), AsmError> {
+        self.define_check(&name)?;
+
+        // A leading main does not need the jump to it
+        if name == "main" && self.main_jump && self.here == ENTRY_POINT as usize + 2 && self.labels.is_empty() {
+            self.main_jump = false;
+            self.rom.clear();
+            self.here = ENTRY_POINT as usize;
+        }
+
+        self.labels.insert(name, self.here as u16);
+
+        Ok(())
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), AsmError> {
+        let text = token.text.as_str();
+
+        if let Some(reg) = self.reg_of(text) {
+            return self.register_statement(reg);
+        }
+
+        match text {
+            ":" => {
+                let name = self.expect_token()?.text;
+                self.label(name)?;
+            },
+            ":const" => {
+                let name = self.expect_token()?.text;
+                self.define_check(&name)?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            },
+            ":calc" => {
+                let name = self.expect_token()?.text;
+                self.define_check(&name)?;
+                self.expect("{")?;
+                let expr = self.braced()?;
+                let value = self.calc(&expr)?;
+                self.constants.insert(name, value);
+            },
+            ":alias" => {
+                let name = self.expect_token()?.text;
+                let reg = self.reg()?;
+                self.aliases.insert(name, reg);
+            },
+            ":macro" => {
+                let name = self.expect_token()?.text;
+                self.define_check(&name)?;
+
+                let mut args = Vec::new();
+                loop {
+                    let arg = self.expect_token()?;
+                    if arg.text == "{" {
+                        break;
+                    }
+                    args.push(arg.text);
+                }
+
+                let body = self.braced()?;
+                self.macros.insert(name, Macro { args, body });
+            },
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(byte)?;
+            },
+            ":org" => {
+                let addr = self.ranged(ENTRY_POINT as i64, (ENTRY_POINT as usize + MAX_ROM_SIZE) as i64)?;
+                self.here = addr as usize;
+            },
+            ":call" => {
+                let pos = self.inst(0x2000)?;
+                self.addr(pos, Fixup::Addr)?;
+            },
+            ":unpack" => {
+                // v0 := nibble with the high bits of the address, v1 := low byte of the address
+                let nibble = self.nibble()?;
+                let pos = self.inst(0x6000 | (nibble as u16) << 4)?;
+                self.inst(0x6100)?;
+
+                let name = match self.peek() {
+                    Some(t) if t != "{" && self.value_of(t).is_none() => self.expect_token()?,
+                    _ => {
+                        let addr = self.ranged(0, 0xfff)? as u16;
+                        self.patch(pos + 1, Fixup::UnpackHigh, addr)?;
+                        self.patch(pos + 3, Fixup::UnpackLow, addr)?;
+                        return Ok(());
+                    },
+                };
+                self.fixups.push((pos + 1, Fixup::UnpackHigh, name.clone()));
+                self.fixups.push((pos + 3, Fixup::UnpackLow, name));
+            },
+            ":next" => {
+                // Names the operand byte of the following instruction, for self modifying code
+                let name = self.expect_token()?.text;
+                self.define_check(&name)?;
+                self.labels.insert(name, self.here as u16 + 1);
+            },
+            ":breakpoint" => {
+                let name = self.expect_token()?.text;
+                self.program.breakpoints.insert(self.here as u16, name);
+            },
+            ":monitor" => {
+                let name = self.expect_token()?;
+
+                // Monitors commonly name data at the end of the program
+                let addr = match self.value_of(&name.text) {
+                    Some(_) => {
+                        self.tokens.push(name.clone());
+                        self.ranged(0, 0xffff)? as u16
+                    },
+                    None => {
+                        self.monitor_fixups.push((self.program.monitors.len(), name.clone()));
+                        0
+                    },
+                };
+
+                // Either a byte count or an Octo format string with one % per value
+                let len = match self.peek() {
+                    Some(t) if t.starts_with('"') => self.expect_token()?.text.matches('%').count(),
+                    _ => self.ranged(0, 0xffff)? as usize,
+                };
+
+                self.program.monitors.push(Monitor { name: name.text, addr, len });
+            },
+            "return" | ";" => { self.inst(0x00ee)?; },
+            "clear" => { self.inst(0x00e0)?; },
+            "exit" => { self.inst(0x00fd)?; },
+            "lores" => { self.inst(0x00fe)?; },
+            "hires" => { self.inst(0x00ff)?; },
+            "scroll-left" => { self.inst(0x00fc)?; },
+            "scroll-right" => { self.inst(0x00fb)?; },
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.inst(0x00c0 | n as u16)?;
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.inst(0x00d0 | n as u16)?;
+            },
+            "audio" => { self.inst(0xf002)?; },
+            "plane" => {
+                let n = self.nibble()?;
+                self.inst(0xf001 | (n as u16) << 8)?;
+            },
+            "bcd" => {
+                let x = self.reg()?;
+                self.inst(0xf033 | (x as u16) << 8)?;
+            },
+            "save" | "load" => {
+                let x = self.reg()?;
+                if self.peek() == Some("-") {
+                    self.expect("-")?;
+                    let y = self.reg()?;
+                    let n = if text == "save" { 0x2 } else { 0x3 };
+                    self.inst(0x5000 | (x as u16) << 8 | (y as u16) << 4 | n)?;
+                } else {
+                    let op = if text == "save" { 0xf055 } else { 0xf065 };
+                    self.inst(op | (x as u16) << 8)?;
+                }
+            },
+            "saveflags" | "loadflags" => {
+                let x = self.reg()?;
+                let op = if text == "saveflags" { 0xf075 } else { 0xf085 };
+                self.inst(op | (x as u16) << 8)?;
+            },
+            "sprite" => {
+                let x = self.reg()?;
+                let y = self.reg()?;
+                let n = self.nibble()?;
+                self.inst(0xd000 | (x as u16) << 8 | (y as u16) << 4 | n as u16)?;
+            },
+            "jump" | "jump0" | "native" => {
+                let op = match text {
+                    "jump" => 0x1000,
+                    "jump0" => 0xb000,
+                    _ => 0x0000,
+                };
+                let pos = self.inst(op)?;
+                self.addr(pos, Fixup::Addr)?;
+            },
+            "i" => {
+                let op = self.expect_token()?.text;
+                match op.as_str() {
+                    ":=" => match self.peek() {
+                        Some("long") => {
+                            self.expect("long")?;
+                            self.inst(0xf000)?;
+                            let pos = self.inst(0x0000)?;
+                            self.addr(pos, Fixup::Long)?;
+                        },
+                        Some("hex") | Some("bighex") => {
+                            let op = if self.expect_token()?.text == "hex" { 0xf029 } else { 0xf030 };
+                            let x = self.reg()?;
+                            self.inst(op | (x as u16) << 8)?;
+                        },
+                        _ => {
+                            let pos = self.inst(0xa000)?;
+                            self.addr(pos, Fixup::Addr)?;
+                        },
+                    },
+                    "+=" => {
+                        let x = self.reg()?;
+                        self.inst(0xf01e | (x as u16) << 8)?;
+                    },
+                    _ => return Err(self.error(format!("unknown operator 'i {}'", op))),
+                }
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.reg()?;
+                let op = match text {
+                    "delay" => 0xf015,
+                    "buzzer" => 0xf018,
+                    _ => 0xf03a,
+                };
+                self.inst(op | (x as u16) << 8)?;
+            },
+            "if" => {
+                let then = self.conditional_then()?;
+                if !then {
+                    // Skip the jump past the body when the condition holds
+                    let pos = self.inst(0x1000)?;
+                    self.control.push(Control::If(pos));
+                }
+            },
+            "else" => {
+                let pos = match self.control.pop() {
+                    Some(Control::If(pos)) => pos,
+                    _ => return Err(self.error("'else' without 'if ... begin'")),
+                };
+                let jump = self.inst(0x1000)?;
+                self.patch(pos, Fixup::Addr, self.here as u16)?;
+                self.control.push(Control::Else(jump));
+            },
+            "end" => match self.control.pop() {
+                Some(Control::If(pos)) | Some(Control::Else(pos)) => self.patch(pos, Fixup::Addr, self.here as u16)?,
+                _ => return Err(self.error("'end' without 'if ... begin'")),
+            },
+            "loop" => self.control.push(Control::Loop(self.here as u16, Vec::new())),
+            "while" => {
+                // Stay in the loop while the condition holds, otherwise take the jump out of it
+                self.conditional(true)?;
+                let pos = self.inst(0x1000)?;
+                match self.control.iter_mut().rev().find(|c| matches!(c, Control::Loop(..))) {
+                    Some(Control::Loop(_, whiles)) => whiles.push(pos),
+                    _ => return Err(self.error("'while' outside of a loop")),
+                }
+            },
+            "again" => {
+                let (start, whiles) = match self.control.pop() {
+                    Some(Control::Loop(start, whiles)) => (start, whiles),
+                    _ => return Err(self.error("'again' without 'loop'")),
+                };
+                let jump = self.inst(0x1000)?;
+                self.patch(jump, Fixup::Addr, start)?;
+                for pos in whiles {
+                    self.patch(pos, Fixup::Addr, self.here as u16)?;
+                }
+            },
+            _ if number(text).is_some() => {
+                let value = number(text).unwrap_or_default() as i64;
+                if !(-0x80..=0xff).contains(&value) {
+                    return Err(self.error(format!("value {} is out of range", value)));
+                }
+                self.emit(value as u8)?;
+            },
+            _ if self.macros.contains_key(text) => self.expand_macro(&token)?,
+            _ if text.starts_with(':') => return Err(self.error(format!("unknown directive '{}'", text))),
+            // Anything else names a subroutine to call
+            _ => {
+                let pos = self.inst(0x2000)?;
+                self.tokens.push(token);
+                self.addr(pos, Fixup::Addr)?;
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Replace a macro invocation with its body
+    fn expand_macro(&mut self, token: &Token) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error(format!("too many macro expansions, does '{}' invoke itself?", token.text)));
+        }
+
+        let arg_count = self.macros[&token.text].args.len();
+        let mut values = Vec::with_capacity(arg_count);
+        for _ in 0..arg_count {
+            values.push(self.expect_token()?.text);
+        }
+
+        let mac = &self.macros[&token.text];
+        let body: Vec<Token> = mac
+            .body
+            .iter()
+            .map(|t| match mac.args.iter().position(|a| *a == t.text) {
+                Some(i) => Token { text: values[i].clone(), line: token.line },
+                None => Token { text: t.text.clone(), line: token.line },
+            })
+            .collect();
+
+        self.tokens.extend(body.into_iter().rev());
+
+        Ok(())
+    }
+
+    /// Statements starting with a register, `vx := ...`, `vx += ...` and so on
+    fn register_statement(&mut self, x: u8) -> Result<(), AsmError> {
+        let op = self.expect_token()?.text;
+        let xr = (x as u16) << 8;
+
+        // Register to register forms share the 8xyn encoding
+        let n = match op.as_str() {
+            ":=" => 0x0,
+            "|=" => 0x1,
+            "&=" => 0x2,
+            "^=" => 0x3,
+            "+=" => 0x4,
+            "-=" => 0x5,
+            ">>=" => 0x6,
+            "=-" => 0x7,
+            "<<=" => 0xe,
+            _ => return Err(self.error(format!("unknown operator '{}'", op))),
+        };
+
+        if self.is_reg() {
+            let y = self.reg()?;
+            self.inst(0x8000 | xr | (y as u16) << 4 | n)?;
+            return Ok(());
+        }
+
+        match op.as_str() {
+            ":=" => match self.peek() {
+                Some("key") => {
+                    self.expect("key")?;
+                    self.inst(0xf00a | xr)?;
+                },
+                Some("delay") => {
+                    self.expect("delay")?;
+                    self.inst(0xf007 | xr)?;
+                },
+                Some("random") => {
+                    self.expect("random")?;
+                    let kk = self.byte()?;
+                    self.inst(0xc000 | xr | kk as u16)?;
+                },
+                _ => {
+                    let kk = self.byte()?;
+                    self.inst(0x6000 | xr | kk as u16)?;
+                },
+            },
+            "+=" => {
+                let kk = self.byte()?;
+                self.inst(0x7000 | xr | kk as u16)?;
+            },
+            "-=" => {
+                // Subtracting a constant is adding its two's complement
+                let kk = self.byte()?;
+                self.inst(0x7000 | xr | kk.wrapping_neg() as u16)?;
+            },
+            _ => return Err(self.error(format!("'{}' needs a register operand", op))),
+        }
+
+        Ok(())
+    }
+
+    /// `if` condition followed by `then` or `begin`, returns true for `then`
+    fn conditional_then(&mut self) -> Result<bool, AsmError> {
+        // The condition has to be compiled differently depending on the keyword after it,
+        // so look ahead for it first. A condition is at most four words, a `{ ... }` calc
+        // expression counting as one however long it is.
+        let mut depth = 0;
+        let mut words = 0;
+        let mut keyword = None;
+        for token in self.tokens.iter().rev() {
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth > 0 => depth -= 1,
+                "then" | "begin" if depth == 0 => {
+                    keyword = Some(token.text.clone());
+                    break;
+                },
+                _ => {},
+            }
+
+            if depth == 0 {
+                words += 1;
+                if words == 4 {
+                    break;
+                }
+            }
+        }
+        let keyword = keyword.ok_or_else(|| self.error("'if' without 'then' or 'begin'"))?;
+
+        let then = keyword == "then";
+        self.conditional(!then)?;
+        self.expect(&keyword)?;
+
+        Ok(then)
+    }
+
+    /// Compile a condition into instructions that skip the next one
+    ///
+    /// The next instruction is skipped when the condition is false, or when it is true if `negated`.
+    fn conditional(&mut self, negated: bool) -> Result<(), AsmError> {
+        let x = self.reg()? as u16;
+        let token = self.expect_token()?.text;
+
+        let op = match negate(&token) {
+            Some(inverse) if negated => inverse,
+            Some(inverse) => negate(inverse).unwrap_or_default(),
+            None => return Err(self.error(format!("unknown comparison '{}'", token))),
+        };
+
+        let t = self.aliases.get("compare-temp").copied().unwrap_or(0xf) as u16;
+
+        match op {
+            "==" | "!=" => {
+                let (reg_skip, byte_skip) = if op == "==" { (0x9000, 0x4000) } else { (0x5000, 0x3000) };
+                if self.is_reg() {
+                    let y = self.reg()? as u16;
+                    self.inst(reg_skip | x << 8 | y << 4)?;
+                } else {
+                    let kk = self.byte()? as u16;
+                    self.inst(byte_skip | x << 8 | kk)?;
+                }
+            },
+            "key" => { self.inst(0xe0a1 | x << 8)?; },
+            "-key" => { self.inst(0xe09e | x << 8)?; },
+            _ => {
+                // 8xy5 and 8xy7 leave VF = 1 when no borrow occurs, i.e. when the first operand
+                // is greater than or equal to the second
+                let operand_is_reg = self.is_reg();
+                let y = if operand_is_reg { self.reg()? as u16 } else { self.byte()? as u16 };
+
+                // Compute VF = (a >= b), then skip when the condition fails
+                let (a_is_x, skip) = match op {
+                    "<" => (true, 0x4f00),
+                    ">=" => (true, 0x3f00),
+                    ">" => (false, 0x4f00),
+                    _ => (false, 0x3f00),
+                };
+
+                if operand_is_reg {
+                    let (a, b) = if a_is_x { (x, y) } else { (y, x) };
+                    self.inst(0x8000 | t << 8 | a << 4)?;
+                    self.inst(0x8005 | t << 8 | b << 4)?;
+                } else {
+                    self.inst(0x6000 | t << 8 | y)?;
+                    // t = n, then t - x flags n >= x and x - t flags x >= n
+                    let n = if a_is_x { 0x7 } else { 0x5 };
+                    self.inst(0x8000 | t << 8 | x << 4 | n)?;
+                }
+
+                self.inst(skip)?;
+            },
+        }
+
+        Ok(())
+    }
+}
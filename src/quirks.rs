@@ -0,0 +1,216 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Interpreters for the various Chip-8 platforms disagree on a handful of instructions.
+// ROMs are usually written against one specific interpreter and only play correctly when
+// those differences are reproduced, see https://chip8.gulrak.net/ for a full comparison.
+
+/// How Fx55 and Fx65 leave the I register after copying V0 through Vx
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStore {
+    /// I is left untouched (SUPER-CHIP)
+    Unchanged,
+
+    /// I is incremented by x (CHIP-48)
+    IncrementX,
+
+    /// I is incremented by x + 1 (COSMAC VIP, XO-CHIP)
+    IncrementX1,
+}
+
+/// Names of the presets, without aliases
+const PRESETS: [&str; 5] = ["default", "vip", "chip48", "schip", "xochip"];
+
+/// Behavioral differences between Chip-8 interpreters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6/8xyE shift Vy and store the result in Vx, instead of shifting Vx in place
+    pub shift_vy: bool,
+
+    /// Fx55/Fx65 effect on the I register
+    pub load_store: LoadStore,
+
+    /// Bnnn jumps to xnn + Vx instead of nnn + V0
+    pub jump_vx: bool,
+
+    /// 8xy1/8xy2/8xy3 reset VF to 0
+    pub vf_reset: bool,
+
+    /// Dxyn wraps sprites around the screen edges instead of clipping them
+    pub wrap: bool,
+
+    /// Dxyn waits for the start of the next 60Hz frame before drawing
+    pub display_wait: bool,
+
+    /// Fx1E sets VF to 1 when I overflows past 0xFFF, 0 otherwise
+    pub add_i_overflow: bool,
+}
+
+impl Default for Quirks {
+    /// The behavior this emulator has always had
+    fn default() -> Self {
+        Quirks {
+            shift_vy: false,
+            load_store: LoadStore::Unchanged,
+            jump_vx: false,
+            vf_reset: false,
+            wrap: false,
+            display_wait: false,
+            add_i_overflow: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// Original COSMAC VIP interpreter
+    pub fn vip() -> Self {
+        Quirks {
+            shift_vy: true,
+            load_store: LoadStore::IncrementX1,
+            jump_vx: false,
+            vf_reset: true,
+            wrap: false,
+            display_wait: true,
+            add_i_overflow: false,
+        }
+    }
+
+    /// CHIP-48 for the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_vy: false,
+            load_store: LoadStore::IncrementX,
+            jump_vx: true,
+            vf_reset: false,
+            wrap: false,
+            display_wait: false,
+            add_i_overflow: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1
+    pub fn schip() -> Self {
+        Quirks {
+            shift_vy: false,
+            load_store: LoadStore::Unchanged,
+            jump_vx: true,
+            vf_reset: false,
+            wrap: false,
+            display_wait: false,
+            add_i_overflow: false,
+        }
+    }
+
+    /// XO-CHIP as implemented by Octo
+    pub fn xochip() -> Self {
+        Quirks {
+            shift_vy: true,
+            load_store: LoadStore::IncrementX1,
+            jump_vx: false,
+            vf_reset: false,
+            wrap: true,
+            display_wait: false,
+            add_i_overflow: false,
+        }
+    }
+
+    /// Look up a named preset
+    pub fn preset(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Quirks::default()),
+            "vip" | "cosmac" | "chip8" => Some(Quirks::vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" | "superchip" => Some(Quirks::schip()),
+            "xochip" => Some(Quirks::xochip()),
+            _ => None,
+        }
+    }
+
+    /// Enable or disable a single quirk by name
+    pub fn set(&mut self, name: &str, enabled: bool) -> Option<()> {
+        match name {
+            "shift-vy" => self.shift_vy = enabled,
+            "load-store-x" => self.set_load_store(LoadStore::IncrementX, enabled),
+            "load-store-x1" => self.set_load_store(LoadStore::IncrementX1, enabled),
+            "jump-vx" => self.jump_vx = enabled,
+            "vf-reset" => self.vf_reset = enabled,
+            "wrap" => self.wrap = enabled,
+            "display-wait" => self.display_wait = enabled,
+            "add-i-overflow" => self.add_i_overflow = enabled,
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    /// Switch to `variant`, or back to leaving I unchanged if `variant` is the one in use
+    fn set_load_store(&mut self, variant: LoadStore, enabled: bool) {
+        if enabled {
+            self.load_store = variant;
+        } else if self.load_store == variant {
+            self.load_store = LoadStore::Unchanged;
+        }
+    }
+}
+
+impl fmt::Display for Quirks {
+    /// Profile that parses back into the same quirks, the preset name when one matches
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = PRESETS.iter().find(|name| Quirks::preset(name) == Some(*self)) {
+            return f.write_str(name);
+        }
+
+        // Every quirk is off in the default profile
+        let quirks = [
+            ("shift-vy", self.shift_vy),
+            ("load-store-x", self.load_store == LoadStore::IncrementX),
+            ("load-store-x1", self.load_store == LoadStore::IncrementX1),
+            ("jump-vx", self.jump_vx),
+            ("vf-reset", self.vf_reset),
+            ("wrap", self.wrap),
+            ("display-wait", self.display_wait),
+            ("add-i-overflow", self.add_i_overflow),
+        ];
+
+        f.write_str("default")?;
+        for (name, _) in quirks.iter().filter(|(_, enabled)| *enabled) {
+            write!(f, ",+{}", name)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Error returned when parsing an unknown preset or quirk name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseQuirksError(String);
+
+impl fmt::Display for ParseQuirksError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown quirk profile or quirk '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseQuirksError {}
+
+impl FromStr for Quirks {
+    type Err = ParseQuirksError;
+
+    /// Parse a preset name optionally followed by individual overrides, e.g. `vip,-display-wait,+wrap`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let mut quirks = Quirks::preset(name).ok_or_else(|| ParseQuirksError(name.to_string()))?;
+
+        for part in parts {
+            let (name, enabled) = match part.strip_prefix('-') {
+                Some(name) => (name, false),
+                None => (part.strip_prefix('+').unwrap_or(part), true),
+            };
+
+            quirks.set(name, enabled).ok_or_else(|| ParseQuirksError(name.to_string()))?;
+        }
+
+        Ok(quirks)
+    }
+}
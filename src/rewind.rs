@@ -0,0 +1,236 @@
+use crate::emulator::Emulator;
+use crate::input::Input;
+
+use std::collections::VecDeque;
+
+// Rewinding keeps a ring buffer of save states taken every `interval` instructions, compressed
+// with PackBits since most of memory and the framebuffer is blank. Input changes between two
+// snapshots are logged, so any instruction in between can be reached exactly by restoring the
+// snapshot before it and re-running the instructions up to it.
+
+/// Default instructions between two snapshots
+pub const REWIND_INTERVAL: u64 = 60;
+
+/// Default memory budget for snapshots in bytes
+pub const REWIND_BUDGET: usize = 8 * 1024 * 1024;
+
+/// Compressed machine state and the input changes that followed it
+struct Snapshot {
+    /// Instructions executed before this snapshot was taken
+    position: u64,
+
+    /// PackBits compressed `Emulator::save_state`
+    state: Vec<u8>,
+
+    /// Input state in effect from the given position on
+    inputs: Vec<(u64, Input)>,
+}
+
+impl Snapshot {
+    fn size(&self) -> usize {
+        self.state.len() + self.inputs.len() * std::mem::size_of::<(u64, Input)>()
+    }
+}
+
+/// History of recent machine states
+pub struct Rewind {
+    snapshots: VecDeque<Snapshot>,
+
+    /// Instructions between two snapshots
+    pub interval: u64,
+
+    /// Maximum bytes used by snapshots, the oldest are dropped first
+    pub budget: usize,
+
+    /// Bytes currently used by snapshots
+    used: usize,
+
+    /// Instructions executed so far
+    position: u64,
+
+    /// Input state at the last recorded instruction
+    last_input: Option<Input>,
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(REWIND_INTERVAL, REWIND_BUDGET)
+    }
+}
+
+impl Rewind {
+    pub fn new(interval: u64, budget: usize) -> Self {
+        Rewind {
+            snapshots: VecDeque::new(),
+            interval: interval.max(1),
+            budget,
+            used: 0,
+            position: 0,
+            last_input: None,
+        }
+    }
+
+    /// Forget all recorded history
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.used = 0;
+        self.position = 0;
+        self.last_input = None;
+    }
+
+    /// Bytes currently used by snapshots
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Instructions recorded so far, less the ones rewound
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Instructions that can currently be rewound
+    pub fn available(&self) -> u64 {
+        self.snapshots.front().map_or(0, |s| self.position - s.position)
+    }
+
+    /// Record the machine state, call before executing each instruction
+    pub fn record(&mut self, emu: &Emulator) {
+        // Without a budget nothing is kept, but the position still follows the movie being recorded
+        if self.budget == 0 {
+            self.position += 1;
+            return;
+        }
+
+        let last = self.snapshots.back().map(|s| s.position);
+        if self.position.is_multiple_of(self.interval) && last != Some(self.position) {
+            let snapshot = Snapshot {
+                position: self.position,
+                state: compress(&emu.save_state()),
+                inputs: Vec::new(),
+            };
+
+            self.used += snapshot.size();
+            self.snapshots.push_back(snapshot);
+            self.last_input = Some(emu.input);
+        }
+
+        if self.last_input.as_ref().map(|i| i.input) != Some(emu.input.input) {
+            if let Some(snapshot) = self.snapshots.back_mut() {
+                snapshot.inputs.push((self.position, emu.input));
+                self.used += std::mem::size_of::<(u64, Input)>();
+            }
+            self.last_input = Some(emu.input);
+        }
+
+        // Always keep the newest snapshot, even if it alone is over budget
+        while self.used > self.budget && self.snapshots.len() > 1 {
+            if let Some(old) = self.snapshots.pop_front() {
+                self.used -= old.size();
+            }
+        }
+
+        self.position += 1;
+    }
+
+    /// Move `emu` back by `steps` instructions, returns false once the oldest snapshot is reached
+    pub fn rewind(&mut self, emu: &mut Emulator, steps: u64) -> bool {
+        let oldest = match self.snapshots.front() {
+            Some(snapshot) => snapshot.position,
+            None => return false,
+        };
+        let target = self.position.saturating_sub(steps).max(oldest);
+
+        // Drop the history after the target, it is about to be played differently
+        while self.snapshots.back().is_some_and(|s| s.position > target) {
+            if let Some(old) = self.snapshots.pop_back() {
+                self.used -= old.size();
+            }
+        }
+
+        let snapshot = match self.snapshots.back_mut() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+
+        let state = decompress(&snapshot.state);
+        if emu.load_state(&state).is_err() {
+            return false;
+        }
+
+        // Replay up to the target with the inputs seen the first time around, ending with the
+        // input the target instruction saw, as a snapshot taken there would have
+        for position in snapshot.position..=target {
+            for (_, input) in snapshot.inputs.iter().filter(|(p, _)| *p == position) {
+                emu.input = *input;
+            }
+
+            if position == target || emu.step().is_err() {
+                break;
+            }
+        }
+
+        let dropped = snapshot.inputs.iter().filter(|(p, _)| *p > target).count();
+        snapshot.inputs.retain(|(p, _)| *p <= target);
+        self.used -= dropped * std::mem::size_of::<(u64, Input)>();
+
+        self.position = target;
+        self.last_input = Some(emu.input);
+
+        target > oldest
+    }
+}
+
+/// PackBits compression
+///
+/// A control byte n below 128 is followed by n + 1 literal bytes, otherwise the next byte is repeated 257 - n times.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let run = data[i..].iter().take(128).take_while(|b| **b == data[i]).count();
+
+        if run >= 2 {
+            out.push((257 - run) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+
+        // Collect literals up to the start of the next run
+        let start = i;
+        while i < data.len() && i - start < 128 {
+            if i + 1 < data.len() && data[i] == data[i + 1] {
+                break;
+            }
+            i += 1;
+        }
+
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+
+    out
+}
+
+/// Reverse of `compress`
+pub fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let n = data[i] as usize;
+        i += 1;
+
+        if n < 128 {
+            let end = (i + n + 1).min(data.len());
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        } else if let Some(b) = data.get(i) {
+            out.extend(std::iter::repeat_n(*b, 257 - n));
+            i += 1;
+        }
+    }
+
+    out
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Cxkk draws from one of several generators. Every generator keeps its whole state in a single
+// integer, so it can be seeded, captured in a save state and restored exactly.
+//
+// The COSMAC VIP generator reproduces the interpreter's Cxkk routine at 0x01D9. Its seed is the
+// 1802 register R9: the routine steps it and adds the byte of interpreter code R9.0 points at in
+// page 1 to R9.1, folds the sum with itself shifted right through the carry and keeps the
+// result in R9.1. The VIP's 60Hz interrupt steps R9 as well, so the numbers also depend on how
+// many frames went by.
+
+/// Page 1 of the COSMAC VIP CHIP-8 interpreter, 0x0100 to 0x01FF, read by its Cxkk routine
+const VIP_INTERPRETER_PAGE: [u8; 256] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0xa3, 0x98, 0x56, 0xd4, 0xf8, 0x81, 0xbc, 0xf8, 0x95, 0xac,
+    0x22, 0xdc, 0x12, 0x56, 0xd4, 0x06, 0xb8, 0xd4, 0x06, 0xa8, 0xd4, 0x64, 0x0a, 0x01, 0xe6, 0x8a,
+    0xf4, 0xaa, 0x3b, 0x28, 0x9a, 0xfc, 0x01, 0xba, 0xd4, 0xf8, 0x81, 0xba, 0x06, 0xfa, 0x0f, 0xaa,
+    0x0a, 0xaa, 0xd4, 0xe6, 0x06, 0xbf, 0x93, 0xbe, 0xf8, 0x1b, 0xae, 0x2a, 0x1a, 0xf8, 0x00, 0x5a,
+    0x0e, 0xf5, 0x3b, 0x4b, 0x56, 0x0a, 0xfc, 0x01, 0x5a, 0x30, 0x40, 0x4e, 0xf6, 0x3b, 0x3c, 0x9f,
+    0x56, 0x2a, 0x2a, 0xd4, 0x00, 0x22, 0x86, 0x52, 0xf8, 0xf0, 0xa7, 0x07, 0x5a, 0x87, 0xf3, 0x17,
+    0x1a, 0x3a, 0x5b, 0x12, 0xd4, 0x22, 0x86, 0x52, 0xf8, 0xf0, 0xa7, 0x0a, 0x57, 0x87, 0xf3, 0x17,
+    0x1a, 0x3a, 0x6b, 0x12, 0xd4, 0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xa5, 0x86, 0xfa,
+    0x0f, 0xb5, 0xd4, 0x45, 0xe6, 0xf3, 0x3a, 0x82, 0x15, 0x15, 0xd4, 0x45, 0xe6, 0xf3, 0x3a, 0x88,
+    0xd4, 0x45, 0x07, 0x30, 0x8c, 0x45, 0x07, 0x30, 0x84, 0xe6, 0x62, 0x26, 0x45, 0xa3, 0x36, 0x88,
+    0xd4, 0x3e, 0x88, 0xd4, 0xf8, 0xf0, 0xa7, 0xe7, 0x45, 0xf4, 0xa5, 0x86, 0xfa, 0x0f, 0x3b, 0xb2,
+    0xfc, 0x01, 0xb5, 0xd4, 0x45, 0x56, 0xd4, 0x45, 0xe6, 0xf4, 0x56, 0xd4, 0x45, 0xfa, 0x0f, 0x3a,
+    0xc4, 0x07, 0x56, 0xd4, 0xaf, 0x22, 0xf8, 0xd3, 0x73, 0x8f, 0xf9, 0xf0, 0x52, 0xe6, 0x07, 0xd2,
+    0x56, 0xf8, 0xff, 0xa6, 0xf8, 0x00, 0x7e, 0x56, 0xd4, 0x19, 0x89, 0xae, 0x93, 0xbe, 0x99, 0xee,
+    0xf4, 0x56, 0x76, 0xe6, 0xf4, 0xb9, 0x56, 0x45, 0xf2, 0x56, 0xd4, 0x45, 0xaa, 0x86, 0xfa, 0x0f,
+    0xba, 0xd4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe0, 0x00, 0x4b, 0x00,
+];
+
+/// Method used to produce random bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// xorshift64*, evenly distributed
+    Xorshift,
+
+    /// The COSMAC VIP interpreter's routine, seeded by R9
+    Vip,
+}
+
+/// Error returned when parsing an unknown generator name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAlgorithmError(String);
+
+impl fmt::Display for ParseAlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown random number generator '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseAlgorithmError {}
+
+impl FromStr for Algorithm {
+    type Err = ParseAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "xorshift" => Ok(Algorithm::Xorshift),
+            "vip" | "cosmac" => Ok(Algorithm::Vip),
+            _ => Err(ParseAlgorithmError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::Xorshift => "xorshift",
+            Algorithm::Vip => "vip",
+        })
+    }
+}
+
+/// Random number generator used by Cxkk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    pub algorithm: Algorithm,
+
+    /// Generator state: never 0 for xorshift, R9 in the low 16 bits for the VIP
+    pub state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+impl Rng {
+    /// Create a xorshift generator that always produces the same sequence for `seed`
+    pub fn new(seed: u64) -> Self {
+        Rng::with_algorithm(Algorithm::Xorshift, seed)
+    }
+
+    /// Create a generator of `algorithm` that always produces the same sequence for `seed`
+    pub fn with_algorithm(algorithm: Algorithm, seed: u64) -> Self {
+        let state = match algorithm {
+            // xorshift gets stuck on an all zero state
+            Algorithm::Xorshift if seed == 0 => 0x9e37_79b9_7f4a_7c15,
+            Algorithm::Xorshift => seed,
+            Algorithm::Vip => seed & 0xffff,
+        };
+
+        Rng { algorithm, state }
+    }
+
+    /// Create a xorshift generator with a random seed
+    pub fn from_entropy() -> Self {
+        Rng::new(rand::random())
+    }
+
+    /// Next random byte
+    pub fn next_u8(&mut self) -> u8 {
+        match self.algorithm {
+            Algorithm::Xorshift => {
+                self.state ^= self.state >> 12;
+                self.state ^= self.state << 25;
+                self.state ^= self.state >> 27;
+
+                (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+            },
+            Algorithm::Vip => {
+                // INC R9, then D = M(0x0100 + R9.0) + R9.1 with the carry in DF
+                let r9 = (self.state as u16).wrapping_add(1);
+                let sum = VIP_INTERPRETER_PAGE[r9 as usize & 0xff] as u16 + (r9 >> 8);
+                let (vx, carry) = (sum as u8, sum > 0xff);
+
+                // SHRC shifts DF into bit 7, then the result is added to Vx and kept in R9.1
+                let shifted = (vx >> 1) | ((carry as u8) << 7);
+                let val = vx.wrapping_add(shifted);
+
+                self.state = ((val as u64) << 8) | (r9 as u64 & 0xff);
+
+                val
+            },
+        }
+    }
+
+    /// Advance the generator for the end of a 60Hz frame
+    pub fn tick_frame(&mut self) {
+        if self.algorithm == Algorithm::Vip {
+            // The VIP's display interrupt increments R9
+            self.state = (self.state as u16).wrapping_add(1) as u64;
+        }
+    }
+}
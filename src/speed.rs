@@ -0,0 +1,85 @@
+use std::fmt;
+
+// Speed controls of the window: fast forward while a key is held, slow motion, and pausing with
+// frame advance. Frames keep running in whole 60Hz frames, only how often they run changes.
+
+/// Speeds in percent that slow motion cycles through
+const SLOW_MOTION: [u32; 3] = [100, 50, 25];
+
+/// Pacing chosen with the speed hotkeys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Speed {
+    /// Pacing in percent of normal speed
+    pub percent: u32,
+
+    /// Frames run as fast as possible
+    pub fast_forward: bool,
+
+    /// Frames only run when advanced one at a time
+    pub paused: bool,
+
+    /// Frames requested with frame advance while paused
+    advance: u32,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Speed {
+    /// Normal speed, running
+    pub fn new() -> Self {
+        Speed {
+            percent: 100,
+            fast_forward: false,
+            paused: false,
+            advance: 0,
+        }
+    }
+
+    /// Switch to the next slow motion speed, back to normal after the slowest
+    pub fn cycle_slow_motion(&mut self) {
+        let next = SLOW_MOTION.iter().position(|s| *s == self.percent).map_or(0, |i| (i + 1) % SLOW_MOTION.len());
+        self.percent = SLOW_MOTION[next];
+    }
+
+    /// Pause or resume, dropping frame advances not run yet
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.advance = 0;
+    }
+
+    /// Pause, or run a single frame when already paused
+    pub fn frame_advance(&mut self) {
+        if self.paused {
+            self.advance += 1;
+        } else {
+            self.paused = true;
+        }
+    }
+
+    /// Use up one frame advance while paused, false if none is left
+    pub fn take_advance(&mut self) -> bool {
+        if self.advance == 0 {
+            return false;
+        }
+
+        self.advance -= 1;
+        true
+    }
+}
+
+impl fmt::Display for Speed {
+    /// Speed as shown in the window title
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.paused {
+            f.write_str("paused")
+        } else if self.fast_forward {
+            f.write_str("fast forward")
+        } else {
+            write!(f, "{}%", self.percent)
+        }
+    }
+}
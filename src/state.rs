@@ -0,0 +1,309 @@
+use crate::emulator::Emulator;
+use crate::error::Chip8Error;
+use crate::quirks::{LoadStore};
+use crate::rng::{Algorithm};
+
+use std::path::Path;
+
+// Save state file format, all values little endian:
+//
+// +--------+---------+----------+-------------+---------+----------+
+// | "C8ST" | version | reserved | payload len | payload | CRC-32   |
+// | 4      | u16     | u16      | u32         | ...     | u32      |
+// +--------+---------+----------+-------------+---------+----------+
+//
+// The CRC-32 covers the payload only. The payload is the machine state in the order written by
+// `Emulator::save_state`. Any change to that order or to the size of a field must bump VERSION.
+
+/// Identifies a save state file
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// Current version of the payload layout
+pub const VERSION: u16 = 4;
+
+/// Size of the header preceding the payload
+const HEADER_LEN: usize = 12;
+
+/// Stack pointer value stored for an empty stack
+const EMPTY_STACK: u8 = 0xff;
+
+/// Key value stored when Fx0A is not waiting for a release
+const NO_KEY: u8 = 0xff;
+
+/// CRC-32 (IEEE 802.3) of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+/// Little endian payload writer
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    fn bool(&mut self, val: bool) {
+        self.u8(val as u8);
+    }
+
+    fn u16(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn u64(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn bytes(&mut self, val: &[u8]) {
+        self.buf.extend_from_slice(val);
+    }
+}
+
+/// Little endian payload reader
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.buf.len() < len {
+            return Err(Chip8Error::InvalidState("truncated payload"));
+        }
+
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+
+        Ok(head)
+    }
+
+    fn fill(&mut self, dst: &mut [u8]) -> Result<(), Chip8Error> {
+        dst.copy_from_slice(self.bytes(dst.len())?);
+
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, Chip8Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let mut val = [0; 2];
+        val.copy_from_slice(self.bytes(2)?);
+
+        Ok(u16::from_le_bytes(val))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let mut val = [0; 4];
+        val.copy_from_slice(self.bytes(4)?);
+
+        Ok(u32::from_le_bytes(val))
+    }
+
+    fn u64(&mut self) -> Result<u64, Chip8Error> {
+        let mut val = [0; 8];
+        val.copy_from_slice(self.bytes(8)?);
+
+        Ok(u64::from_le_bytes(val))
+    }
+}
+
+impl Emulator {
+    /// Serialize the complete machine state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = Writer { buf: Vec::new() };
+
+        // Memory and stack
+        w.bytes(&self.memory.memory);
+        for val in self.memory.stack.iter() {
+            w.u16(*val);
+        }
+        w.u8(if self.memory.sp == usize::MAX { EMPTY_STACK } else { self.memory.sp as u8 });
+
+        // Registers
+        for reg in 0..16 {
+            w.u8(self.registers.reg_read(reg).unwrap_or_default());
+        }
+        w.u8(self.registers.dt);
+        w.u8(self.registers.st);
+        w.u16(self.registers.i);
+        w.u16(self.registers.pc);
+        w.u16(self.registers.sp);
+        w.bytes(&self.registers.flags);
+
+        // Display
+        w.bool(self.display.hires);
+        w.u8(self.display.planes);
+        w.bytes(&self.display.memory);
+
+        // Input and audio
+        w.bytes(&self.input.input);
+        w.bool(self.audio.pattern.is_some());
+        w.bytes(&self.audio.pattern.unwrap_or_default());
+        w.u8(self.audio.pitch);
+
+        // Timing and random number generator
+        w.u32(self.tick_cnt);
+        w.bool(self.frame_drawn);
+        w.bool(self.exited);
+        w.u8(self.key_wait.unwrap_or(NO_KEY));
+        w.u8(match self.rng.algorithm {
+            Algorithm::Xorshift => 0,
+            Algorithm::Vip => 1,
+        });
+        w.u64(self.rng.state);
+
+        // Quirks
+        w.bool(self.quirks.shift_vy);
+        w.u8(match self.quirks.load_store {
+            LoadStore::Unchanged => 0,
+            LoadStore::IncrementX => 1,
+            LoadStore::IncrementX1 => 2,
+        });
+        w.bool(self.quirks.jump_vx);
+        w.bool(self.quirks.vf_reset);
+        w.bool(self.quirks.wrap);
+        w.bool(self.quirks.display_wait);
+        w.bool(self.quirks.add_i_overflow);
+
+        let payload = w.buf;
+        let mut data = Vec::with_capacity(HEADER_LEN + payload.len() + 4);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend_from_slice(&payload);
+        data.extend_from_slice(&crc32(&payload).to_le_bytes());
+
+        data
+    }
+
+    /// Restore a machine state produced by `save_state`
+    ///
+    /// The state is validated completely before anything is changed, a bad state leaves the emulator untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut header = Reader { buf: data };
+
+        if header.bytes(4).map_err(|_| Chip8Error::InvalidState("truncated header"))? != MAGIC {
+            return Err(Chip8Error::InvalidState("not a save state"));
+        }
+
+        if header.u16()? != VERSION {
+            return Err(Chip8Error::InvalidState("unsupported version"));
+        }
+
+        let _reserved = header.u16()?;
+        let len = header.u32()? as usize;
+        let payload = header.bytes(len)?;
+
+        if header.u32()? != crc32(payload) {
+            return Err(Chip8Error::InvalidState("checksum mismatch"));
+        }
+
+        // Decode into a copy so a failure half way through does not leave a mixed state behind
+        let mut emu = self.clone();
+        let mut r = Reader { buf: payload };
+
+        r.fill(&mut emu.memory.memory)?;
+        for val in emu.memory.stack.iter_mut() {
+            *val = r.u16()?;
+        }
+        emu.memory.sp = match r.u8()? {
+            EMPTY_STACK => usize::MAX,
+            sp if (sp as usize) < emu.memory.stack.len() => sp as usize,
+            _ => return Err(Chip8Error::InvalidState("stack pointer out of range")),
+        };
+
+        for reg in 0..16 {
+            let val = r.u8()?;
+            emu.registers.reg_write(reg, val)?;
+        }
+        emu.registers.dt = r.u8()?;
+        emu.registers.st = r.u8()?;
+        emu.registers.i = r.u16()?;
+        emu.registers.pc = r.u16()?;
+        emu.registers.sp = r.u16()?;
+        r.fill(&mut emu.registers.flags)?;
+
+        emu.display.hires = r.bool()?;
+        emu.display.planes = r.u8()?;
+        r.fill(&mut emu.display.memory)?;
+
+        r.fill(&mut emu.input.input)?;
+        let has_pattern = r.bool()?;
+        let mut pattern = [0; 16];
+        r.fill(&mut pattern)?;
+        emu.audio.pattern = if has_pattern { Some(pattern) } else { None };
+        emu.audio.pitch = r.u8()?;
+
+        emu.tick_cnt = r.u32()?;
+        emu.frame_drawn = r.bool()?;
+        emu.exited = r.bool()?;
+        emu.key_wait = match r.u8()? {
+            NO_KEY => None,
+            key if key < 16 => Some(key),
+            _ => return Err(Chip8Error::InvalidState("key out of range")),
+        };
+        emu.rng.algorithm = match r.u8()? {
+            0 => Algorithm::Xorshift,
+            1 => Algorithm::Vip,
+            _ => return Err(Chip8Error::InvalidState("unknown random number generator")),
+        };
+        emu.rng.state = r.u64()?;
+
+        emu.quirks.shift_vy = r.bool()?;
+        emu.quirks.load_store = match r.u8()? {
+            0 => LoadStore::Unchanged,
+            1 => LoadStore::IncrementX,
+            2 => LoadStore::IncrementX1,
+            _ => return Err(Chip8Error::InvalidState("unknown load/store quirk")),
+        };
+        emu.quirks.jump_vx = r.bool()?;
+        emu.quirks.vf_reset = r.bool()?;
+        emu.quirks.wrap = r.bool()?;
+        emu.quirks.display_wait = r.bool()?;
+        emu.quirks.add_i_overflow = r.bool()?;
+
+        if !r.buf.is_empty() {
+            return Err(Chip8Error::InvalidState("trailing payload data"));
+        }
+
+        *self = emu;
+
+        Ok(())
+    }
+
+    /// Write the machine state to `path`
+    pub fn save_state_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Chip8Error> {
+        std::fs::write(path, self.save_state())?;
+
+        Ok(())
+    }
+
+    /// Restore the machine state from `path`
+    pub fn load_state_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Chip8Error> {
+        let data = std::fs::read(path)?;
+
+        self.load_state(&data)
+    }
+}
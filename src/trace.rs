@@ -0,0 +1,162 @@
+use crate::emulator::Emulator;
+use crate::disasm::disassemble_at;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+// Execution trace, one line per instruction with the machine state before it runs. The line
+// formats are stable so traces of two runs can be compared with diff.
+//
+// In ring mode lines are kept in memory instead of being written, and only the last ones are
+// written out when the program crashes, which keeps long runs cheap.
+
+/// Layout of a trace line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// `0200 00e0 cls                  v0=00 ... vf=00 i=0000 sp=0 dt=00 st=00`
+    Default,
+
+    /// `PC:0200 OP:00E0 V:00 .. 00 I:0000 SP:0 DT:00 ST:00`, the registers without the disassembly
+    Registers,
+}
+
+impl TraceFormat {
+    /// Trace line for the instruction at PC of `emu`, before it is executed
+    pub fn line(&self, emu: &Emulator) -> String {
+        let regs = &emu.registers;
+        let pc = regs.pc as usize;
+        let opcode = emu.memory.read_inst(pc).unwrap_or_default();
+        let v: Vec<u8> = (0..16).map(|x| regs.reg_read(x).unwrap_or_default()).collect();
+
+        // Number of return addresses on the stack
+        let sp = emu.memory.sp.wrapping_add(1);
+
+        match self {
+            TraceFormat::Default => {
+                let (text, _) = disassemble_at(&emu.memory, &emu.quirks, pc);
+                let v: Vec<String> = v.iter().enumerate().map(|(x, val)| format!("v{:x}={:02x}", x, val)).collect();
+
+                format!("{:04x} {:04x} {:<20} {} i={:04x} sp={} dt={:02x} st={:02x}",
+                        pc, opcode, text, v.join(" "), regs.i, sp, regs.dt, regs.st)
+            },
+            TraceFormat::Registers => {
+                let v: Vec<String> = v.iter().map(|val| format!("{:02X}", val)).collect();
+
+                format!("PC:{:04X} OP:{:04X} V:{} I:{:04X} SP:{} DT:{:02X} ST:{:02X}",
+                        pc, opcode, v.join(" "), regs.i, sp, regs.dt, regs.st)
+            },
+        }
+    }
+}
+
+/// Error returned when parsing an unknown trace format name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTraceFormatError(String);
+
+impl fmt::Display for ParseTraceFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown trace format '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseTraceFormatError {}
+
+impl FromStr for TraceFormat {
+    type Err = ParseTraceFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(TraceFormat::Default),
+            "registers" => Ok(TraceFormat::Registers),
+            _ => Err(ParseTraceFormatError(s.to_string())),
+        }
+    }
+}
+
+/// Parse an address range written as `start-end` in hexadecimal, e.g. `200-2ff`
+pub fn parse_range(s: &str) -> Option<RangeInclusive<u16>> {
+    let hex = |s: &str| {
+        let s = s.trim();
+        u16::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16).ok()
+    };
+
+    let (start, end) = s.split_once('-')?;
+    let (start, end) = (hex(start)?, hex(end)?);
+
+    if start > end {
+        return None;
+    }
+
+    Some(start..=end)
+}
+
+/// Writes trace lines for executed instructions
+pub struct Tracer {
+    out: Box<dyn Write>,
+
+    /// Line layout
+    pub format: TraceFormat,
+
+    /// Only trace instructions at these addresses
+    pub range: Option<RangeInclusive<u16>>,
+
+    /// Number of lines kept in ring mode, 0 writes every line as it happens
+    last: usize,
+    ring: VecDeque<String>,
+}
+
+impl Tracer {
+    /// Trace to `out`, e.g. a file or stdout
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Tracer {
+            out,
+            format: TraceFormat::Default,
+            range: None,
+            last: 0,
+            ring: VecDeque::new(),
+        }
+    }
+
+    /// Keep only the last `n` lines and write them on `crash`
+    pub fn keep_last(&mut self, n: usize) {
+        self.last = n;
+        self.ring = VecDeque::with_capacity(n);
+    }
+
+    /// Trace the instruction `emu` is about to execute
+    pub fn record(&mut self, emu: &Emulator) -> io::Result<()> {
+        if self.range.as_ref().is_some_and(|range| !range.contains(&emu.registers.pc)) {
+            return Ok(());
+        }
+
+        let line = self.format.line(emu);
+
+        if self.last == 0 {
+            return writeln!(self.out, "{}", line);
+        }
+
+        if self.ring.len() == self.last {
+            self.ring.pop_front();
+        }
+        self.ring.push_back(line);
+
+        Ok(())
+    }
+
+    /// Write out the lines kept in ring mode, called when execution fails
+    pub fn crash(&mut self) -> io::Result<()> {
+        for line in self.ring.drain(..) {
+            writeln!(self.out, "{}", line)?;
+        }
+
+        self.out.flush()
+    }
+
+    /// Write out buffered output
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,300 @@
+use crate::emulator::Emulator;
+use crate::trace::TraceFormat;
+use crate::error::Chip8Error;
+
+use std::collections::VecDeque;
+use std::fmt;
+
+// Finds the first instruction where two runs stop agreeing, either from two trace logs written
+// with --trace or by running two emulators side by side. Trace lines hold the state *before* an
+// instruction, so the culprit is the instruction on the line preceding the first differing one.
+
+/// Memory addresses listed individually before the rest is summarized
+const MAX_MEMORY_DIFFS: usize = 8;
+
+/// Machine state on one trace line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceState {
+    pub pc: u16,
+    pub opcode: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+
+    /// Number of return addresses on the stack
+    pub sp: u16,
+
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl TraceState {
+    /// State of `emu` as it would be traced
+    pub fn capture(emu: &Emulator) -> Self {
+        let regs = &emu.registers;
+        let mut v = [0; 16];
+        for (x, val) in v.iter_mut().enumerate() {
+            *val = regs.reg_read(x as u8).unwrap_or_default();
+        }
+
+        TraceState {
+            pc: regs.pc,
+            opcode: emu.memory.read_inst(regs.pc as usize).unwrap_or_default(),
+            v,
+            i: regs.i,
+            sp: emu.memory.sp.wrapping_add(1) as u16,
+            dt: regs.dt,
+            st: regs.st,
+        }
+    }
+
+    /// Parse a line in any of the `TraceFormat` layouts
+    pub fn parse(line: &str) -> Option<Self> {
+        let hex16 = |s: &str| u16::from_str_radix(s, 16).ok();
+        let hex8 = |s: &str| u8::from_str_radix(s, 16).ok();
+        let mut tokens = line.split_whitespace();
+
+        let mut state = TraceState { pc: 0, opcode: 0, v: [0; 16], i: 0, sp: 0, dt: 0, st: 0 };
+
+        if line.starts_with("PC:") {
+            // PC:0200 OP:6E05 V:00 .. 00 I:0000 SP:0 DT:00 ST:00
+            state.pc = hex16(tokens.next()?.strip_prefix("PC:")?)?;
+            state.opcode = hex16(tokens.next()?.strip_prefix("OP:")?)?;
+            state.v[0] = hex8(tokens.next()?.strip_prefix("V:")?)?;
+            for x in 1..16 {
+                state.v[x] = hex8(tokens.next()?)?;
+            }
+            state.i = hex16(tokens.next()?.strip_prefix("I:")?)?;
+            state.sp = tokens.next()?.strip_prefix("SP:")?.parse().ok()?;
+            state.dt = hex8(tokens.next()?.strip_prefix("DT:")?)?;
+            state.st = hex8(tokens.next()?.strip_prefix("ST:")?)?;
+
+            return Some(state);
+        }
+
+        // 0200 6e05 ld ve, 0x5   v0=00 ... vf=00 i=0000 sp=0 dt=00 st=00
+        state.pc = hex16(tokens.next()?)?;
+        state.opcode = hex16(tokens.next()?)?;
+
+        // The mnemonic has a varying number of words, everything after it is name=value
+        let mut seen = 0;
+        for (name, value) in tokens.filter_map(|t| t.split_once('=')) {
+            match name {
+                "i" => state.i = hex16(value)?,
+                "sp" => state.sp = value.parse().ok()?,
+                "dt" => state.dt = hex8(value)?,
+                "st" => state.st = hex8(value)?,
+                _ => {
+                    let x = usize::from_str_radix(name.strip_prefix('v')?, 16).ok()?;
+                    *state.v.get_mut(x)? = hex8(value)?;
+                },
+            }
+            seen += 1;
+        }
+
+        if seen != 20 {
+            return None;
+        }
+
+        Some(state)
+    }
+
+    /// Description of every field that differs from `other`
+    pub fn differences(&self, other: &TraceState) -> Vec<String> {
+        let mut diffs = Vec::new();
+
+        if self.pc != other.pc {
+            diffs.push(format!("pc: {:04x} != {:04x}", self.pc, other.pc));
+        }
+
+        if self.opcode != other.opcode {
+            diffs.push(format!("opcode: {:04x} != {:04x}", self.opcode, other.opcode));
+        }
+
+        for x in 0..16 {
+            if self.v[x] != other.v[x] {
+                diffs.push(format!("v{:x}: {:02x} != {:02x}", x, self.v[x], other.v[x]));
+            }
+        }
+
+        if self.i != other.i {
+            diffs.push(format!("i: {:04x} != {:04x}", self.i, other.i));
+        }
+
+        if self.sp != other.sp {
+            diffs.push(format!("sp: {} != {}", self.sp, other.sp));
+        }
+
+        if self.dt != other.dt {
+            diffs.push(format!("dt: {:02x} != {:02x}", self.dt, other.dt));
+        }
+
+        if self.st != other.st {
+            diffs.push(format!("st: {:02x} != {:02x}", self.st, other.st));
+        }
+
+        diffs
+    }
+}
+
+/// First point where two runs disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Number of instructions both runs executed identically
+    pub index: usize,
+
+    /// Lines leading up to the divergence in the first run, the last one is where it shows
+    pub context_a: Vec<String>,
+
+    /// Same for the second run
+    pub context_b: Vec<String>,
+
+    /// What differs
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "runs diverge after {} instructions", self.index)?;
+
+        let shared = self.context_a.len().saturating_sub(1);
+        for line in &self.context_a[..shared] {
+            writeln!(f, "  {}", line)?;
+        }
+
+        if let Some(line) = self.context_a.last() {
+            writeln!(f, "a {}", line)?;
+        }
+        if let Some(line) = self.context_b.last() {
+            writeln!(f, "b {}", line)?;
+        }
+
+        for diff in &self.differences {
+            writeln!(f, "  {}", diff)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare two trace logs line by line, keeping `context` lines before the divergence
+///
+/// Lines are compared by the state they describe, so logs in different `TraceFormat`s can be
+/// compared. Lines that are not traces, e.g. other program output, are compared as text.
+pub fn compare_traces(a: &str, b: &str, context: usize) -> Option<Divergence> {
+    let mut lines_a = a.lines().filter(|l| !l.trim().is_empty());
+    let mut lines_b = b.lines().filter(|l| !l.trim().is_empty());
+    let mut recent = VecDeque::with_capacity(context + 1);
+    let mut index = 0;
+
+    loop {
+        let (line_a, line_b) = match (lines_a.next(), lines_b.next()) {
+            (None, None) => return None,
+            (line_a, line_b) => (line_a, line_b),
+        };
+
+        let differences = match (line_a, line_b) {
+            (Some(line_a), Some(line_b)) => match (TraceState::parse(line_a), TraceState::parse(line_b)) {
+                (Some(state_a), Some(state_b)) => state_a.differences(&state_b),
+                _ if line_a.trim() == line_b.trim() => Vec::new(),
+                _ => vec!["lines differ".to_string()],
+            },
+            (None, _) => vec![format!("trace a ends after {} lines", index)],
+            _ => vec![format!("trace b ends after {} lines", index)],
+        };
+
+        if !differences.is_empty() {
+            let mut context_a: Vec<String> = recent.iter().cloned().collect();
+            let mut context_b = context_a.clone();
+            context_a.push(line_a.unwrap_or("<end of trace>").to_string());
+            context_b.push(line_b.unwrap_or("<end of trace>").to_string());
+
+            return Some(Divergence { index, context_a, context_b, differences });
+        }
+
+        if recent.len() == context {
+            recent.pop_front();
+        }
+        if context > 0 {
+            recent.push_back(line_a.unwrap_or_default().to_string());
+        }
+        index += 1;
+    }
+}
+
+/// Differences in the complete machine state of two emulators
+pub fn state_differences(a: &Emulator, b: &Emulator) -> Vec<String> {
+    let mut diffs = TraceState::capture(a).differences(&TraceState::capture(b));
+
+    if a.registers.flags != b.registers.flags {
+        diffs.push(format!("flags: {:02x?} != {:02x?}", a.registers.flags, b.registers.flags));
+    }
+
+    // Only the used part of the stack, popped entries are left behind in the array
+    let stack = |emu: &Emulator| emu.memory.stack[..emu.memory.sp.wrapping_add(1).min(emu.memory.stack.len())].to_vec();
+    if stack(a) != stack(b) {
+        diffs.push(format!("stack: {:03x?} != {:03x?}", stack(a), stack(b)));
+    }
+
+    let memory: Vec<usize> = (0..a.memory.memory.len()).filter(|addr| a.memory.memory[*addr] != b.memory.memory[*addr]).collect();
+    for addr in memory.iter().take(MAX_MEMORY_DIFFS) {
+        diffs.push(format!("memory {:#06x}: {:02x} != {:02x}", addr, a.memory.memory[*addr], b.memory.memory[*addr]));
+    }
+    if memory.len() > MAX_MEMORY_DIFFS {
+        diffs.push(format!("and {} more memory bytes", memory.len() - MAX_MEMORY_DIFFS));
+    }
+
+    if a.display.hires != b.display.hires || a.display.pixels() != b.display.pixels() {
+        diffs.push("display differs".to_string());
+    }
+
+    if a.exited != b.exited {
+        diffs.push(format!("exited: {} != {}", a.exited, b.exited));
+    }
+
+    diffs
+}
+
+/// Step `a` and `b` together for up to `cycles` instructions, stopping at the first state mismatch
+///
+/// Both emulators should start from the same state, including the random number generator seed.
+/// Errors are part of the state: one emulator failing where the other does not is a divergence,
+/// both failing the same way ends the run.
+pub fn lockstep(a: &mut Emulator, b: &mut Emulator, cycles: u64, context: usize) -> Option<Divergence> {
+    let mut recent_a = VecDeque::with_capacity(context + 1);
+    let mut recent_b = VecDeque::with_capacity(context + 1);
+
+    for index in 0..cycles as usize {
+        let mut differences = state_differences(a, b);
+
+        recent_a.push_back(TraceFormat::Default.line(a));
+        recent_b.push_back(TraceFormat::Default.line(b));
+        if recent_a.len() > context + 1 {
+            recent_a.pop_front();
+            recent_b.pop_front();
+        }
+
+        if differences.is_empty() && a.exited {
+            return None;
+        }
+
+        if differences.is_empty() {
+            match (a.step(), b.step()) {
+                (Ok(()), Ok(())) => continue,
+                (Err(e_a), Err(e_b)) if e_a.to_string() == e_b.to_string() => return None,
+                (result_a, result_b) => {
+                    let describe = |r: Result<(), Chip8Error>| r.map_or_else(|e| e.to_string(), |_| "ok".to_string());
+                    differences.push(format!("step: {} != {}", describe(result_a), describe(result_b)));
+                },
+            }
+        }
+
+        return Some(Divergence {
+            index,
+            context_a: recent_a.into_iter().collect(),
+            context_b: recent_b.into_iter().collect(),
+            differences,
+        });
+    }
+
+    None
+}
@@ -0,0 +1,181 @@
+use chip8_emu::asm::{assemble, assemble_file};
+use chip8_emu::instruction::Instruction;
+use chip8_emu::quirks::Quirks;
+
+use std::path::PathBuf;
+
+// The assembler reads back what the trace and the disassembler print, so every opcode form is
+// disassembled, reassembled and compared byte for byte. The rest covers the directives.
+
+/// Directory for source files that include each other, removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        TempDir(dir)
+    }
+
+    fn write(&self, name: &str, source: &str) -> PathBuf {
+        let path = self.0.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, source).unwrap();
+
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn every_opcode_round_trips() {
+    for opcode in 0..=0xffff {
+        let instruction = match Instruction::decode(opcode, 0xbeef) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+
+        let text = instruction.to_string();
+        let bytes = assemble(&text).unwrap_or_else(|e| panic!("{:04x} '{}': {}", opcode, text, e));
+
+        assert_eq!(bytes, instruction.encode(), "{:04x} '{}'", opcode, text);
+        assert_eq!(bytes[..2], opcode.to_be_bytes(), "{:04x} '{}'", opcode, text);
+    }
+}
+
+#[test]
+fn jump_offset_round_trips_with_jump_vx() {
+    let quirks = Quirks { jump_vx: true, ..Quirks::default() };
+
+    for opcode in 0xb000..=0xbfff {
+        let instruction = Instruction::decode(opcode, 0).unwrap();
+        let text = instruction.display_with(&quirks);
+
+        assert_eq!(assemble(&text), Ok(opcode.to_be_bytes().to_vec()), "{}", text);
+    }
+
+    assert_eq!(Instruction::decode(0xb345, 0).unwrap().display_with(&quirks), "jp v3, 0x345");
+    assert_eq!(Instruction::decode(0xb345, 0).unwrap().display_with(&Quirks::default()), "jp v0, 0x345");
+    assert!(assemble("jp v3, 0x445").unwrap_err().msg.contains("starting with 3"));
+}
+
+#[test]
+fn labels_resolve_forwards_and_backwards() {
+    let source = "
+        start:
+            jp end        ; 0x200
+            call start    ; 0x202
+        end: ld i, start  ; 0x204
+    ";
+
+    assert_eq!(assemble(source), Ok(vec![0x12, 0x04, 0x22, 0x00, 0xa2, 0x00]));
+}
+
+#[test]
+fn constants_and_expressions() {
+    let source = "
+        speed = 3
+        top = speed + 0x10 - 0b10
+        ld v1, speed
+        ld v2, top
+        add v3, -1
+        ld i, sprite + 1
+        sprite: db 0
+    ";
+
+    assert_eq!(assemble(source), Ok(vec![0x61, 0x03, 0x62, 0x11, 0x73, 0xff, 0xa2, 0x09, 0x00]));
+}
+
+#[test]
+fn data_directives() {
+    let source = "
+        db 0x3c, 0b01000010, 66, -1
+        dw 0x1234, words
+        words:
+    ";
+
+    assert_eq!(assemble(source), Ok(vec![0x3c, 0x42, 0x42, 0xff, 0x12, 0x34, 0x02, 0x08]));
+}
+
+#[test]
+fn long_load_takes_four_bytes() {
+    let source = "
+        ld i, long far
+        far: cls
+    ";
+
+    assert_eq!(assemble(source), Ok(vec![0xf0, 0x00, 0x02, 0x04, 0x00, 0xe0]));
+}
+
+#[test]
+fn includes_are_relative_to_the_including_file() {
+    let dir = TempDir::new("include");
+    dir.write("lib/sprites.asm", "include \"digits.asm\"\nsmile: db 0x66\n");
+    dir.write("lib/digits.asm", "one: db 0x20, 0x60\n");
+    let main = dir.write("main.asm", "ld i, smile\ninclude \"lib/sprites.asm\"\njp one\n");
+
+    assert_eq!(assemble_file(main), Ok(vec![0xa2, 0x04, 0x20, 0x60, 0x66, 0x12, 0x02]));
+}
+
+#[test]
+fn include_loops_are_caught() {
+    let dir = TempDir::new("loop");
+    let main = dir.write("main.asm", "cls\ninclude \"main.asm\"\n");
+
+    let err = assemble_file(&main).unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(err.msg.contains("nested"), "{}", err);
+}
+
+#[test]
+fn missing_include_is_reported() {
+    let err = assemble("cls\ninclude \"does/not/exist.asm\"").unwrap_err();
+
+    assert_eq!((err.file.as_str(), err.line), ("<input>", 2));
+}
+
+#[test]
+fn errors_point_at_their_line() {
+    let cases = [
+        ("cls\nfoo v1", 2, "unknown instruction 'foo'"),
+        ("cls\n\nld v1", 3, "invalid operands for 'ld'"),
+        ("jp nowhere", 1, "unknown symbol 'nowhere'"),
+        ("ld v1, 0x100", 1, "out of range"),
+        ("db 0xzz", 1, "invalid number '0xzz'"),
+        ("a:\na:", 2, "'a' is already defined"),
+        ("x = 1\nx = 2", 2, "'x' is already defined"),
+        ("add v1, vq", 1, "unknown symbol 'vq'"),
+        ("ld v1, 1 +", 1, "missing value"),
+    ];
+
+    for (source, line, msg) in cases {
+        let err = assemble(source).unwrap_err();
+
+        assert_eq!(err.line, line, "{}", source);
+        assert!(err.msg.contains(msg), "{}: {}", source, err.msg);
+    }
+}
+
+#[test]
+fn overflowing_expressions_are_errors() {
+    let big = "0x7fffffffffffffff";
+
+    for expr in [format!("{} + 1", big), format!("0 - {} - 2", big), format!("-{} - 2", big)] {
+        let err = assemble(&format!("db {}", expr)).unwrap_err();
+
+        assert!(err.msg.contains("too large"), "{}: {}", expr, err.msg);
+    }
+}
+
+#[test]
+fn program_too_large_is_rejected() {
+    let source = "cls\n".repeat(0x8000);
+
+    assert!(assemble(&source).unwrap_err().msg.contains("larger than"));
+}
@@ -0,0 +1,96 @@
+use chip8_emu::audio::{Audio, DEFAULT_PITCH};
+
+// XO-CHIP playback rates, and the samples the SDL callback generates for the buzzer and for
+// pattern buffers. With a device running at 4000 samples per second the default pitch plays
+// exactly one pattern bit per output sample.
+
+/// `audio` with `pattern` loaded and played at `pitch`
+fn loaded(pattern: [u8; 16], pitch: u8) -> Audio {
+    Audio { pattern: Some(pattern), pitch }
+}
+
+#[test]
+fn pitch_sets_the_playback_rate() {
+    assert_eq!(Audio::new().pitch, DEFAULT_PITCH);
+    assert_eq!(Audio::new().playback_rate(), 4000.0);
+
+    // 48 steps per octave
+    assert_eq!(loaded([0; 16], 112).playback_rate(), 8000.0);
+    assert_eq!(loaded([0; 16], 16).playback_rate(), 2000.0);
+    assert!((loaded([0; 16], 0).playback_rate() - 1587.4).abs() < 0.1);
+    assert!((loaded([0; 16], 255).playback_rate() - 63082.4).abs() < 0.1);
+}
+
+#[cfg(feature = "sdl")]
+mod playback {
+    use super::loaded;
+    use chip8_emu::audio::{Audio, DEFAULT_PITCH};
+    use chip8_emu::frontend::SquareWave;
+    use sdl2::audio::AudioCallback;
+
+    /// Whether each of the next `n` samples of `wave` is high
+    fn play(wave: &mut SquareWave, n: usize) -> Vec<bool> {
+        let mut out = vec![0.0; n];
+        wave.callback(&mut out);
+
+        out.iter().map(|sample| *sample > 0.0).collect()
+    }
+
+    /// Bits of `pattern`, most significant bit of the first byte first
+    fn bits(pattern: &[u8; 16]) -> Vec<bool> {
+        (0..128).map(|bit| pattern[bit / 8] & (0x80 >> (bit % 8)) != 0).collect()
+    }
+
+    fn pattern() -> [u8; 16] {
+        [0x00, 0xff, 0x0f, 0xf0, 0xaa, 0x55, 0x81, 0x7e, 0x01, 0x80, 0x33, 0xcc, 0x12, 0x34, 0x56, 0x78]
+    }
+
+    #[test]
+    fn buzzer_is_a_440_hz_square_wave() {
+        // 128 samples per period, high up to and including the middle one
+        let mut wave = SquareWave::new(440.0 * 128.0);
+        let period: Vec<bool> = (0..128).map(|sample| sample <= 64).collect();
+
+        for samples in play(&mut wave, 10 * 128).chunks(128) {
+            assert_eq!(samples, period);
+        }
+    }
+
+    #[test]
+    fn pattern_is_played_in_a_loop() {
+        let mut wave = SquareWave::new(4000.0);
+        wave.set_audio(loaded(pattern(), DEFAULT_PITCH));
+
+        let samples = play(&mut wave, 3 * 128);
+        for repeat in samples.chunks(128) {
+            assert_eq!(repeat, bits(&pattern()));
+        }
+    }
+
+    #[test]
+    fn pitch_changes_the_pattern_speed() {
+        // One octave up skips every other bit
+        let mut wave = SquareWave::new(4000.0);
+        wave.set_audio(loaded(pattern(), DEFAULT_PITCH + 48));
+        let expected: Vec<bool> = bits(&pattern()).into_iter().step_by(2).collect();
+        assert_eq!(play(&mut wave, 64), expected);
+
+        // One octave down plays every bit twice
+        let mut wave = SquareWave::new(4000.0);
+        wave.set_audio(loaded(pattern(), DEFAULT_PITCH - 48));
+        let expected: Vec<bool> = bits(&pattern()).into_iter().flat_map(|bit| [bit, bit]).collect();
+        assert_eq!(play(&mut wave, 256), expected);
+    }
+
+    #[test]
+    fn silent_pattern_stays_low() {
+        let mut wave = SquareWave::new(44_100.0);
+        wave.set_audio(loaded([0; 16], 200));
+
+        assert!(play(&mut wave, 5000).iter().all(|high| !high));
+
+        // Going back to the buzzer
+        wave.set_audio(Audio::new());
+        assert!(play(&mut wave, 100).iter().any(|high| *high));
+    }
+}
@@ -0,0 +1,194 @@
+use chip8_emu::debugger::{Action, Debugger};
+use chip8_emu::emulator::Emulator;
+
+// Debugger commands run against a small program, the way the emulator loop drives them: run
+// instructions until `should_break` stops execution, then enter the next command.
+//
+//     0x200  ld v0, 1
+//     0x202  call 0x20a
+//     0x204  add v0, 1
+//     0x206  add v0, 1
+//     0x208  jp 0x208
+//     0x20a  add v1, 1
+//     0x20c  add v1, 1
+//     0x20e  ret
+
+const PROGRAM: [u8; 16] = [
+    0x60, 0x01, 0x22, 0x0a, 0x70, 0x01, 0x70, 0x01, 0x12, 0x08, 0x71, 0x01, 0x71, 0x01, 0x00, 0xee,
+];
+
+/// Machine and debugger stopped before the first instruction
+fn stopped() -> (Emulator, Debugger) {
+    let mut emu = Emulator::new();
+    emu.memory.load_bytes(&PROGRAM).unwrap();
+
+    let mut debugger = Debugger::new();
+    debugger.break_now();
+    assert!(debugger.should_break(&emu));
+
+    (emu, debugger)
+}
+
+/// Run `line`, which must resume execution, then execute instructions until the debugger stops
+/// again or `limit` is reached. Returns the number of instructions executed.
+fn resume(emu: &mut Emulator, debugger: &mut Debugger, line: &str, limit: usize) -> usize {
+    assert_eq!(debugger.command(emu, line), Ok(Some(Action::Resume)), "{}", line);
+
+    // The instruction execution stopped at runs right away
+    for executed in 1..=limit {
+        emu.step().unwrap();
+        if debugger.should_break(emu) {
+            return executed;
+        }
+    }
+
+    limit
+}
+
+#[test]
+fn step_runs_a_number_of_instructions() {
+    let (mut emu, mut debugger) = stopped();
+
+    assert_eq!(resume(&mut emu, &mut debugger, "s", 100), 1);
+    assert_eq!(emu.registers.pc, 0x202);
+
+    // Into the call
+    assert_eq!(resume(&mut emu, &mut debugger, "step 3", 100), 3);
+    assert_eq!(emu.registers.pc, 0x20e);
+}
+
+#[test]
+fn next_steps_over_calls() {
+    let (mut emu, mut debugger) = stopped();
+
+    assert_eq!(resume(&mut emu, &mut debugger, "n", 100), 1);
+    assert_eq!(emu.registers.pc, 0x202);
+
+    // The whole subroutine runs
+    assert_eq!(resume(&mut emu, &mut debugger, "next", 100), 4);
+    assert_eq!(emu.registers.pc, 0x204);
+    assert_eq!(emu.registers.reg_read(1).unwrap(), 2);
+}
+
+#[test]
+fn breakpoints_stop_continue() {
+    let (mut emu, mut debugger) = stopped();
+
+    assert_eq!(debugger.command(&mut emu, "b 20c"), Ok(None));
+    assert_eq!(debugger.command(&mut emu, "break 0x206"), Ok(None));
+    assert_eq!(debugger.breakpoints.iter().copied().collect::<Vec<_>>(), [0x206, 0x20c]);
+
+    assert_eq!(resume(&mut emu, &mut debugger, "c", 100), 3);
+    assert_eq!(emu.registers.pc, 0x20c);
+    assert_eq!(resume(&mut emu, &mut debugger, "continue", 100), 3);
+    assert_eq!(emu.registers.pc, 0x206);
+
+    assert_eq!(debugger.command(&mut emu, "d 206"), Ok(None));
+    assert_eq!(debugger.command(&mut emu, "delete 206"), Err("no breakpoint at 0206".to_string()));
+
+    // Nothing stops the loop anymore
+    assert_eq!(resume(&mut emu, &mut debugger, "c", 100), 100);
+}
+
+#[test]
+fn set_changes_registers() {
+    let (mut emu, mut debugger) = stopped();
+
+    for line in ["set v3 2a", "set vf 0xff", "set i 123", "set pc 20a", "set dt 10", "set st ff"] {
+        assert_eq!(debugger.command(&mut emu, line), Ok(None), "{}", line);
+    }
+
+    assert_eq!(emu.registers.reg_read(3).unwrap(), 0x2a);
+    assert_eq!(emu.registers.reg_read(0xf).unwrap(), 0xff);
+    assert_eq!(emu.registers.i, 0x123);
+    assert_eq!(emu.registers.pc, 0x20a);
+    assert_eq!(emu.registers.dt, 0x10);
+    assert_eq!(emu.registers.st, 0xff);
+}
+
+#[test]
+fn values_that_do_not_fit_are_rejected() {
+    let (mut emu, mut debugger) = stopped();
+    emu.registers.reg_write(3, 0x2a).unwrap();
+
+    let cases = [
+        ("set v3 100", "value '100' doesn't fit in a byte"),
+        ("set vf 0x1ff", "value '0x1ff' doesn't fit in a byte"),
+        ("set dt 100", "value '100' doesn't fit in a byte"),
+        ("set st 1ff", "value '1ff' doesn't fit in a byte"),
+        ("set i 10000", "invalid number '10000'"),
+        ("set pc 10000", "invalid number '10000'"),
+    ];
+
+    for (line, msg) in cases {
+        assert_eq!(debugger.command(&mut emu, line), Err(msg.to_string()), "{}", line);
+    }
+    assert_eq!(emu.registers.reg_read(3).unwrap(), 0x2a);
+    assert_eq!(emu.registers.reg_read(0xf).unwrap(), 0);
+    assert_eq!((emu.registers.i, emu.registers.pc, emu.registers.dt, emu.registers.st), (0, 0x200, 0, 0));
+
+    // Bytes before the bad one are written, nothing after it
+    assert_eq!(debugger.command(&mut emu, "w 300 12 345 67"), Err("value '345' doesn't fit in a byte".to_string()));
+    assert_eq!(&emu.memory.memory[0x300..0x303], [0x12, 0x00, 0x00]);
+}
+
+#[test]
+fn write_changes_memory() {
+    let (mut emu, mut debugger) = stopped();
+
+    assert_eq!(debugger.command(&mut emu, "w 300 de ad 0xbe"), Ok(None));
+    assert_eq!(&emu.memory.memory[0x300..0x304], [0xde, 0xad, 0xbe, 0x00]);
+
+    // Turn the first instruction into ld v0, 7
+    assert_eq!(debugger.command(&mut emu, "write 201 7"), Ok(None));
+    resume(&mut emu, &mut debugger, "s", 100);
+    assert_eq!(emu.registers.reg_read(0).unwrap(), 7);
+}
+
+#[test]
+fn listings_and_dumps_stay_within_memory() {
+    let (mut emu, mut debugger) = stopped();
+
+    for line in ["r", "regs", "m 200", "mem fff0 100", "m ffff 18446744073709551615", "l", "list fffc 10", "b", "h"] {
+        assert_eq!(debugger.command(&mut emu, line), Ok(None), "{}", line);
+    }
+}
+
+#[test]
+fn empty_line_and_quit() {
+    let (mut emu, mut debugger) = stopped();
+
+    assert_eq!(debugger.command(&mut emu, "   "), Ok(None));
+    assert_eq!(debugger.command(&mut emu, "q"), Ok(Some(Action::Quit)));
+    assert_eq!(debugger.command(&mut emu, "quit"), Ok(Some(Action::Quit)));
+}
+
+#[test]
+fn invalid_commands_are_errors() {
+    let (mut emu, mut debugger) = stopped();
+
+    let cases = [
+        ("bogus", "unknown command 'bogus', try 'help'"),
+        ("s x", "invalid count 'x'"),
+        ("s 0", "count must be greater than 0"),
+        ("b 12345", "invalid number '12345'"),
+        ("b zz", "invalid number 'zz'"),
+        ("d", "missing address"),
+        ("m", "missing address"),
+        ("m 200 -1", "invalid length '-1'"),
+        ("l 200 x", "invalid count 'x'"),
+        ("set v1", "usage: set <reg> <val>"),
+        ("set vg 1", "unknown register 'vg'"),
+        ("set x 1", "unknown register 'x'"),
+        ("w 300", "missing bytes"),
+        ("w 300 1g", "invalid number '1g'"),
+    ];
+
+    for (line, msg) in cases {
+        assert_eq!(debugger.command(&mut emu, line), Err(msg.to_string()), "{}", line);
+    }
+
+    // None of them changed anything
+    assert_eq!(emu.registers.pc, 0x200);
+    assert!(debugger.breakpoints.is_empty());
+}
@@ -0,0 +1,137 @@
+use chip8_emu::asm::assemble;
+use chip8_emu::disasm::{disassemble_at, Disassembly, ENTRY_POINT};
+use chip8_emu::memory::Memory;
+use chip8_emu::quirks::Quirks;
+
+use std::path::Path;
+
+// Code found by following the paths from the entry point, labels and data in the listing, and
+// listings of every ROM in games/ assembling back into the same bytes.
+
+fn listing(rom: &[u8]) -> String {
+    Disassembly::new(rom, ENTRY_POINT).listing()
+}
+
+#[test]
+fn code_and_data_are_told_apart() {
+    let rom = [
+        0xa2, 0x0c, 0x22, 0x08, 0xd0, 0x15, 0x12, 0x06, 0x00, 0xee, 0xff, 0xff, 0x3c, 0x42, 0x42, 0x3c, //
+        0x00, 0x30, 0x00, 0xf0, 0x00, 0x02, 0x00, 0x00, 0x00, 0xfd,
+    ];
+
+    // The sprite holds valid opcodes, but nothing reaches them
+    let expected = "    ld i, data_20c                  ; 0200: a2 0c
+    call label_208                  ; 0202: 22 08
+    drw v0, v1, 5                   ; 0204: d0 15
+
+label_206:
+    jp label_206                    ; 0206: 12 06
+
+label_208:
+    ret                             ; 0208: 00 ee
+    db 0xff, 0xff                   ; 020a: ff ff
+
+data_20c:
+    db 0x3c, 0x42, 0x42, 0x3c, 0x00, 0x30, 0x00, 0xf0; 020c: 3c 42 42 3c 00 30 00 f0
+    db 0x00, 0x02, 0x00, 0x00, 0x00, 0xfd; 0214: 00 02 00 00 00 fd
+";
+    assert_eq!(listing(&rom), expected);
+}
+
+#[test]
+fn skips_follow_both_paths() {
+    // se v0, 1   ld i, long 0x0000   exit   db 0xff
+    let rom = [0x30, 0x01, 0xf0, 0x00, 0x00, 0x00, 0x00, 0xfd, 0xff];
+    let dis = Disassembly::new(&rom, ENTRY_POINT);
+
+    assert!(dis.is_code(0x200) && dis.is_code(0x202) && dis.is_code(0x206));
+    assert!(!dis.is_code(0x204) && !dis.is_code(0x208));
+}
+
+#[test]
+fn calls_continue_after_returning_and_computed_jumps_end_paths() {
+    // call 0x208   exit   jp v0, 0x20a   ret   cls
+    let rom = [0x22, 0x08, 0x00, 0xfd, 0xb2, 0x0a, 0x00, 0xe0, 0x00, 0xee, 0x00, 0xe0];
+    let dis = Disassembly::new(&rom, ENTRY_POINT);
+
+    assert!(dis.is_code(0x200) && dis.is_code(0x202) && dis.is_code(0x208));
+    assert!(!dis.is_code(0x204) && !dis.is_code(0x206) && !dis.is_code(0x20a));
+    assert_eq!(dis.label_at(0x208), Some("label_208"));
+    assert_eq!(dis.label_at(0x20a), None);
+}
+
+#[test]
+fn code_labels_win_over_data_labels() {
+    // ld i, 0x204   jp 0x204   jp 0x204
+    let dis = Disassembly::new(&[0xa2, 0x04, 0x12, 0x04, 0x12, 0x04], ENTRY_POINT);
+
+    assert_eq!(dis.label_at(0x204), Some("label_204"));
+}
+
+#[test]
+fn targets_the_listing_can_not_place_are_defined_up_front() {
+    // ld i, 0x050 (the font, not labeled)   jp 0x300 (past the end)
+    let rom = [0xa0, 0x50, 0x13, 0x00];
+    let expected = "label_300 = 0x300
+    ld i, 0x50                      ; 0200: a0 50
+    jp label_300                    ; 0202: 13 00
+";
+    assert_eq!(listing(&rom), expected);
+
+    // ld i, 0x203 (the second byte of the loop)   jp 0x202
+    let rom = [0xa2, 0x03, 0x12, 0x02];
+    assert!(listing(&rom).starts_with("data_203 = 0x203\n"));
+}
+
+#[test]
+fn listings_of_every_game_assemble_back() {
+    let games = Path::new(env!("CARGO_MANIFEST_DIR")).join("games");
+    let mut checked = 0;
+
+    for entry in std::fs::read_dir(games).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "txt") {
+            continue;
+        }
+
+        let rom = std::fs::read(&path).unwrap();
+        let source = listing(&rom);
+        let bytes = assemble(&source).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+        assert!(bytes == rom, "{} assembles differently", path.display());
+        checked += 1;
+    }
+
+    assert!(checked >= 30, "only {} ROMs", checked);
+}
+
+#[test]
+fn opcodes_with_a_bad_fixed_nibble_are_data() {
+    // 9ab0 is sne va, vb but 9ab1 has no instruction, so the path ends there
+    let rom = [0x9a, 0xb0, 0x9a, 0xb1, 0x5a, 0xb1, 0x00, 0xfd];
+    let expected = "    sne va, vb                      ; 0200: 9a b0
+    db 0x9a, 0xb1, 0x5a, 0xb1, 0x00, 0xfd; 0202: 9a b1 5a b1 00 fd
+";
+    assert_eq!(listing(&rom), expected);
+    assert_eq!(assemble(expected).unwrap(), rom);
+
+    let mut memory = Memory::new();
+    memory.load_bytes(&rom).unwrap();
+    assert_eq!(disassemble_at(&memory, &Quirks::default(), 0x202), ("dw 0x9ab1".to_string(), 2));
+}
+
+#[test]
+fn single_instructions() {
+    let mut memory = Memory::new();
+    memory.load_bytes(&[0x00, 0xe0, 0xf0, 0x00, 0x12, 0x34, 0x5a, 0xb1, 0xb3, 0x45]).unwrap();
+    let quirks = Quirks::default();
+
+    assert_eq!(disassemble_at(&memory, &quirks, 0x200), ("cls".to_string(), 2));
+    assert_eq!(disassemble_at(&memory, &quirks, 0x202), ("ld i, long 0x1234".to_string(), 4));
+    assert_eq!(disassemble_at(&memory, &quirks, 0x206), ("dw 0x5ab1".to_string(), 2));
+    assert_eq!(disassemble_at(&memory, &quirks, 0x208), ("jp v0, 0x345".to_string(), 2));
+
+    // With the jump_vx quirk Bxnn adds Vx
+    let jump_vx = Quirks { jump_vx: true, ..Quirks::default() };
+    assert_eq!(disassemble_at(&memory, &jump_vx, 0x208), ("jp v3, 0x345".to_string(), 2));
+}
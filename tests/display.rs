@@ -0,0 +1,97 @@
+use chip8_emu::display::{Display, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::process::Command;
+
+// The 64x32 and SUPER-CHIP 128x64 modes of the framebuffer, scrolling at the edges of either, and
+// the window scale that high resolution pixels need.
+
+/// Display in the given mode with the pixels at `points` set in plane 1
+fn with_pixels(hires: bool, points: &[(usize, usize)]) -> Display {
+    let mut display = Display::new();
+    display.set_hires(hires);
+
+    let width = display.width();
+    for (x, y) in points {
+        display.memory[y * width + x] = 1;
+    }
+
+    display
+}
+
+/// Coordinates of every set pixel
+fn set_pixels(display: &Display) -> Vec<(usize, usize)> {
+    let width = display.width();
+
+    display.pixels().iter().enumerate().filter(|(_, px)| **px != 0).map(|(idx, _)| (idx % width, idx / width)).collect()
+}
+
+#[test]
+fn modes_change_the_size_and_clear_the_screen() {
+    let mut display = with_pixels(false, &[(63, 31)]);
+    assert_eq!((display.width(), display.height()), (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize));
+    assert_eq!(display.pixels().len(), 64 * 32);
+
+    display.set_hires(true);
+    assert_eq!((display.width(), display.height()), (HIRES_SCREEN_WIDTH as usize, HIRES_SCREEN_HEIGHT as usize));
+    assert_eq!(display.pixels().len(), 128 * 64);
+    assert!(set_pixels(&display).is_empty());
+}
+
+#[test]
+fn scrolling_drops_pixels_at_the_edges() {
+    for hires in [false, true] {
+        let mut display = with_pixels(hires, &[(0, 0), (5, 5)]);
+        let (right, bottom) = (display.width() - 1, display.height() - 1);
+
+        display.scroll_down(3);
+        assert_eq!(set_pixels(&display), [(0, 3), (5, 8)]);
+        display.scroll_right(4);
+        assert_eq!(set_pixels(&display), [(4, 3), (9, 8)]);
+        display.scroll_left(5);
+        assert_eq!(set_pixels(&display), [(4, 8)]);
+        display.scroll_up(8);
+        assert_eq!(set_pixels(&display), [(4, 0)]);
+
+        // Pixels on the far edges go too
+        let mut display = with_pixels(hires, &[(right, bottom)]);
+        display.scroll_right(1);
+        assert!(set_pixels(&display).is_empty());
+    }
+}
+
+#[test]
+fn scrolling_only_moves_the_selected_planes() {
+    let mut display = Display::new();
+    display.memory[0] = 3;
+    display.planes = 2;
+
+    display.scroll_right(1);
+    assert_eq!(&display.memory[..2], [1, 2]);
+
+    display.clear_planes();
+    assert_eq!(&display.memory[..2], [1, 0]);
+}
+
+#[test]
+fn window_scale_leaves_room_for_hires_pixels() {
+    let rom = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("games").join("test.ch8");
+    let run = |scale: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_chip8-emu"))
+            .args(["--headless", "-n", "1", "--scale", scale])
+            .arg(&rom)
+            .output()
+            .unwrap();
+
+        (output.status.code().unwrap(), String::from_utf8_lossy(&output.stderr).into_owned())
+    };
+
+    for scale in ["0", "1", "3", "15"] {
+        let (code, stderr) = run(scale);
+        assert_eq!(code, 2);
+        assert!(stderr.contains("window scale must be a positive even number"), "{}", stderr);
+    }
+
+    for scale in ["2", "6"] {
+        assert_eq!(run(scale).0, 0);
+    }
+}
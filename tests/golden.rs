@@ -0,0 +1,192 @@
+use chip8_emu::display::Display;
+use chip8_emu::golden::{fnv1a, framebuffer_hash, parse_manifest, run, InputEvent, SEED};
+use chip8_emu::quirks::Quirks;
+use chip8_emu::rng::{Algorithm, Rng};
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Manifest parsing, and chip8-test run on manifests written to a temporary directory. The ROM
+// paths point back into games/, so only the manifest is modified by --update.
+
+const HASH: &str = "88abc4b4a6b0bb59";
+
+fn rom(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("games").join(name).display().to_string()
+}
+
+/// Manifest file holding `lines`, removed when dropped
+struct Manifest(PathBuf);
+
+impl Manifest {
+    fn new(name: &str, lines: &[String]) -> Self {
+        let path = std::env::temp_dir().join(format!("chip8-golden-{}-{}.txt", name, std::process::id()));
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        Manifest(path)
+    }
+
+    fn text(&self) -> String {
+        std::fs::read_to_string(&self.0).unwrap()
+    }
+
+    /// Run chip8-test on the manifest, returns its exit code and output
+    fn check(&self, args: &[&str]) -> (i32, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_chip8-test")).args(args).arg(&self.0).output().unwrap();
+
+        (output.status.code().unwrap(), String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl Drop for Manifest {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn matching_manifest_passes() {
+    let manifest = Manifest::new("pass", &[format!("{}  300  default  {}  text=BON", rom("BC_test.ch8"), HASH)]);
+
+    let (code, out) = manifest.check(&[]);
+    assert_eq!(code, 0, "{}", out);
+    assert!(out.contains("1 passed, 0 failed"), "{}", out);
+}
+
+#[test]
+fn hash_mismatch_fails() {
+    let manifest = Manifest::new("mismatch", &[format!("{}  300  default  0000000000000000", rom("BC_test.ch8"))]);
+
+    let (code, out) = manifest.check(&[]);
+    assert_eq!(code, 1, "{}", out);
+    assert!(out.contains(&format!("hash {}, expected 0000000000000000", HASH)), "{}", out);
+}
+
+#[test]
+fn update_rewrites_hashes_and_passes() {
+    let lines = [
+        "# rom  frames  quirks  hash".to_string(),
+        format!("{}  300     default  0000000000000000  text=BON   # comment", rom("BC_test.ch8")),
+    ];
+    let manifest = Manifest::new("update", &lines);
+
+    let (code, out) = manifest.check(&["--update"]);
+    assert_eq!(code, 0, "{}", out);
+
+    // Only the hash column changes, spacing and comments stay
+    let expected = format!("{}\n{}  300     default  {}  text=BON   # comment\n", lines[0], rom("BC_test.ch8"), HASH);
+    assert_eq!(manifest.text(), expected);
+    assert_eq!(manifest.check(&[]).0, 0);
+}
+
+#[test]
+fn update_still_fails_on_text() {
+    let lines = [
+        format!("{}  300  default  0000000000000000  text=BON", rom("BC_test.ch8")),
+        format!("{}  300  default  0000000000000000  text=NOPE", rom("BC_test.ch8")),
+    ];
+    let manifest = Manifest::new("text", &lines);
+
+    let (code, out) = manifest.check(&["--update"]);
+    assert_eq!(code, 1, "{}", out);
+    assert!(out.contains("text 'NOPE' missing"), "{}", out);
+    assert!(out.contains("1 passed, 1 failed"), "{}", out);
+
+    // The hashes are written all the same
+    assert_eq!(manifest.text().matches(HASH).count(), 2);
+}
+
+#[test]
+fn invalid_manifest_is_an_error() {
+    let manifest = Manifest::new("invalid", &[format!("{}  lots  default  0", rom("BC_test.ch8"))]);
+
+    let (code, _) = manifest.check(&[]);
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn manifest_columns() {
+    let text = "
+        # rom  frames  quirks  hash  input
+        a.ch8  200  default  1a2b3c4d5e6f7a8b
+        sub/b.ch8  120  vip,+wrap  ABC  30:+5 35:-f   # keypad
+        c.ch8  1  schip  0  text=BON !text=ERR seed=42 rng=vip
+    ";
+    let cases = parse_manifest(text, Path::new("games")).unwrap();
+
+    assert_eq!(cases.len(), 3);
+
+    assert_eq!(cases[0].rom, Path::new("games/a.ch8"));
+    assert_eq!((cases[0].frames, cases[0].hash, cases[0].line), (200, 0x1a2b_3c4d_5e6f_7a8b, 3));
+    assert_eq!(cases[0].quirks, Quirks::default());
+    assert_eq!(cases[0].rng, Rng::new(SEED));
+    assert!(cases[0].input.is_empty() && cases[0].text.is_empty());
+
+    assert_eq!(cases[1].rom, Path::new("games/sub/b.ch8"));
+    assert_eq!(cases[1].quirks, Quirks { wrap: true, ..Quirks::vip() });
+    assert_eq!(cases[1].hash, 0xabc);
+    assert_eq!(
+        cases[1].input,
+        [InputEvent { frame: 30, key: 5, pressed: true }, InputEvent { frame: 35, key: 0xf, pressed: false }]
+    );
+
+    assert_eq!(cases[2].rng, Rng::with_algorithm(Algorithm::Vip, 42));
+    assert_eq!(cases[2].text, [("BON".to_string(), true), ("ERR".to_string(), false)]);
+    assert_eq!(cases[2].line, 5);
+}
+
+#[test]
+fn invalid_manifest_lines() {
+    let cases = [
+        ("a.ch8 200 default", "line 1: expected rom, frames, quirks and hash"),
+        ("\na.ch8 -1 default 0", "line 2: invalid frame count '-1'"),
+        ("a.ch8 200 nope 0", "line 1: unknown quirk profile or quirk 'nope'"),
+        ("a.ch8 200 default xyz", "line 1: invalid hash 'xyz'"),
+        ("a.ch8 200 default 0 seed=x", "line 1: invalid seed 'x'"),
+        ("a.ch8 200 default 0 30+5", "line 1: invalid input event '30+5', expected frame:+key or frame:-key"),
+        ("a.ch8 200 default 0 30:5", "line 1: invalid input event '30:5', expected frame:+key or frame:-key"),
+        ("a.ch8 200 default 0 30:+10", "line 1: invalid input event '30:+10', expected frame:+key or frame:-key"),
+        ("a.ch8 200 default 0 x:+1", "line 1: invalid input event 'x:+1', expected frame:+key or frame:-key"),
+    ];
+
+    for (text, msg) in cases {
+        assert_eq!(parse_manifest(text, Path::new(".")).unwrap_err(), msg, "{}", text);
+    }
+
+    assert!(parse_manifest("a.ch8 200 default 0 rng=nope", Path::new(".")).unwrap_err().starts_with("line 1: "));
+}
+
+#[test]
+fn scripted_input_is_applied_before_its_frame() {
+    // ld v0, key   exit
+    let program = [0xf0, 0x0a, 0x00, 0xfd];
+    let press = |frame| [InputEvent { frame, key: 7, pressed: true }, InputEvent { frame: frame + 1, key: 7, pressed: false }];
+
+    let emu = run(&program, 10, Quirks::default(), Rng::new(SEED), &[]).unwrap();
+    assert!(!emu.exited);
+
+    let emu = run(&program, 10, Quirks::default(), Rng::new(SEED), &press(5)).unwrap();
+    assert!(emu.exited);
+    assert_eq!(emu.registers.reg_read(0).unwrap(), 7);
+
+    // Events past the last frame never happen
+    let emu = run(&program, 10, Quirks::default(), Rng::new(SEED), &press(10)).unwrap();
+    assert!(!emu.exited);
+}
+
+#[test]
+fn framebuffer_hashes() {
+    // Standard FNV-1a test vectors
+    assert_eq!(fnv1a(*b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fnv1a(*b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(fnv1a(*b"foobar"), 0x8594_4171_f739_67e8);
+
+    // The resolution is part of the hash, even for blank screens
+    let mut display = Display::new();
+    let lores = framebuffer_hash(&display);
+    display.set_hires(true);
+    assert_ne!(framebuffer_hash(&display), lores);
+
+    display.memory[0] = 1;
+    assert_ne!(framebuffer_hash(&display), lores);
+}
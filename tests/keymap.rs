@@ -0,0 +1,208 @@
+use chip8_emu::keymap::{KeyConfig, KeyMap, Rebinding, KEYPAD_LAYOUT};
+
+use std::path::PathBuf;
+
+// Key bindings files with global and per-ROM sections, the keymaps they give each ROM, files
+// written back by `save`, and rebinding the keypad one key at a time as the window does.
+
+fn config(text: &str) -> KeyConfig {
+    text.parse().unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn hosts(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+/// Temporary directory for key bindings files, removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        TempDir(std::env::temp_dir().join(format!("chip8-keymap-{}-{}", name, std::process::id())))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn default_keys_follow_the_keypad() {
+    let keymap = KeyMap::new();
+
+    assert_eq!(keymap.key("1"), Some(0x1));
+    assert_eq!(keymap.key("4"), Some(0xc));
+    assert_eq!(keymap.key("x"), Some(0x0));
+    assert_eq!(keymap.key("V"), Some(0xf));
+    assert_eq!(keymap.key("Up"), None);
+}
+
+#[test]
+fn binding_takes_host_keys_from_other_keys() {
+    let mut keymap = KeyMap::new();
+
+    keymap.bind(5, &hosts(&["Up", "q"]));
+    assert_eq!(keymap.keys[5], hosts(&["Up", "q"]));
+    assert_eq!(keymap.key("W"), None);
+    assert_eq!(keymap.key("Q"), Some(5));
+    assert!(keymap.keys[4].is_empty());
+}
+
+#[test]
+fn files_with_sections() {
+    let text = "
+        # chip-8 key = host keys
+        5 = W, Up
+        8 = S ,Down   # comment
+
+        [PONG]
+        1 = Left Shift
+        c =
+
+        [ TANK ]
+    ";
+    let config = config(text);
+
+    assert_eq!(config.global, [(5, hosts(&["W", "Up"])), (8, hosts(&["S", "Down"]))]);
+    assert_eq!(config.roms.len(), 2);
+    assert_eq!(config.roms["PONG"], [(1, hosts(&["Left Shift"])), (0xc, vec![])]);
+    assert!(config.roms["TANK"].is_empty());
+}
+
+#[test]
+fn rom_sections_change_the_global_bindings() {
+    let config = config("5 = Up\n8 = Down\n[PONG]\n1 = Up\nc =\n");
+
+    let global = config.keymap(None);
+    assert_eq!((global.key("Up"), global.key("Down"), global.key("W")), (Some(5), Some(8), None));
+
+    // Up moves to key 1, the rest of the global bindings stay
+    let pong = config.keymap(Some("PONG"));
+    assert_eq!((pong.key("Up"), pong.key("Down"), pong.key("1")), (Some(1), Some(8), None));
+    assert!(pong.keys[5].is_empty() && pong.keys[0xc].is_empty());
+
+    assert_eq!(config.keymap(Some("BRIX")), global);
+}
+
+#[test]
+fn invalid_lines() {
+    let cases = [
+        ("5 W", 1, "expected 'key = host keys', found '5 W'"),
+        ("\n10 = W", 2, "invalid Chip-8 key '10'"),
+        ("g = W", 1, "invalid Chip-8 key 'g'"),
+        ("1 = W\n[PONG\n2 = E", 2, "missing ']' after '[PONG'"),
+    ];
+
+    for (text, line, msg) in cases {
+        let err = text.parse::<KeyConfig>().unwrap_err();
+        assert_eq!((err.line, err.msg.as_str()), (line, msg), "{}", text);
+    }
+
+    let err = "= W".parse::<KeyConfig>().unwrap_err();
+    assert_eq!(err.to_string(), "line 1: invalid Chip-8 key ''");
+}
+
+#[test]
+fn display_parses_back() {
+    let original = config("5 = W, Up\n[PONG]\n1 = Left Shift\nc =\n[BRIX]\n4 = Left\n");
+    let text = original.to_string();
+
+    assert!(text.starts_with("# "), "{}", text);
+    assert!(text.contains("\n[PONG]\n1 = Left Shift\nc = \n"), "{}", text);
+    assert_eq!(config(&text), original);
+}
+
+#[test]
+fn save_and_load() {
+    let dir = TempDir::new("save");
+    let path = dir.0.join("nested").join("keymap");
+    let original = config("5 = W, Up\n[PONG]\n1 = Left Shift\n");
+
+    original.save(&path).unwrap();
+    assert_eq!(KeyConfig::load(&path).unwrap(), original);
+
+    std::fs::write(&path, "5 = W\n7\n").unwrap();
+    let err = KeyConfig::load(&path).unwrap_err();
+    assert_eq!(err, format!("{}: line 2: expected 'key = host keys', found '7'", path.display()));
+
+    assert!(KeyConfig::load(dir.0.join("missing")).unwrap_err().starts_with("cannot read key bindings"));
+}
+
+/// Walk through the whole keypad, binding host keys named after the position on the keypad
+fn rebind_all(rebinding: &mut Rebinding) {
+    for position in 0..16 {
+        rebinding.bind(&format!("Key{}", position));
+    }
+}
+
+#[test]
+fn rebinding_walks_the_keypad_in_order() {
+    let mut rebinding = Rebinding::new(KeyMap::new(), None);
+
+    assert_eq!(rebinding.current(), Some(0x1));
+    rebinding.bind("Up");
+    assert_eq!(rebinding.current(), Some(0x2));
+    assert!(rebinding.is_done(0x1) && !rebinding.is_done(0x2));
+
+    // Keys not reached yet keep their host keys
+    assert_eq!(rebinding.keymap.key("Up"), Some(0x1));
+    assert_eq!(rebinding.keymap.key("1"), None);
+    assert_eq!(rebinding.keymap.key("2"), Some(0x2));
+
+    rebind_all(&mut rebinding);
+    assert_eq!(rebinding.current(), None);
+    assert!(KEYPAD_LAYOUT.iter().all(|key| rebinding.is_done(*key)));
+
+    // Further keys are ignored
+    let keymap = rebinding.keymap.clone();
+    rebinding.bind("Down");
+    assert_eq!(rebinding.keymap, keymap);
+}
+
+#[test]
+fn rebinding_the_same_host_key_moves_it() {
+    let mut rebinding = Rebinding::new(KeyMap::new(), None);
+
+    rebinding.bind("Space");
+    rebinding.bind("Space");
+
+    assert_eq!(rebinding.keymap.key("Space"), Some(0x2));
+    assert!(rebinding.keymap.keys[0x1].is_empty());
+}
+
+#[test]
+fn rebinds_are_saved_for_all_roms_or_one() {
+    let dir = TempDir::new("rebind");
+    let path = dir.0.join("keymap");
+    let mut config = config("5 = Up\n[BRIX]\n4 = Left\n");
+
+    // For all ROMs, replacing the global bindings
+    let mut rebinding = Rebinding::new(config.keymap(None), None);
+    rebind_all(&mut rebinding);
+    config.set_keymap(rebinding.rom.as_deref(), &rebinding.keymap);
+
+    // Then for PONG only
+    let mut rebinding = Rebinding::new(config.keymap(Some("PONG")), Some("PONG".to_string()));
+    rebinding.bind("Left Shift");
+    config.set_keymap(rebinding.rom.as_deref(), &rebinding.keymap);
+    config.save(&path).unwrap();
+
+    let saved = KeyConfig::load(&path).unwrap();
+    assert_eq!(saved, config);
+
+    for (position, key) in KEYPAD_LAYOUT.iter().enumerate() {
+        assert_eq!(saved.keymap(None).key(&format!("Key{}", position)), Some(*key));
+    }
+    assert_eq!(saved.keymap(None).key("Up"), None);
+
+    let pong = saved.keymap(Some("PONG"));
+    assert_eq!(pong.key("Left Shift"), Some(0x1));
+    assert_eq!(pong.key("Key0"), None);
+    assert_eq!(pong.key("Key1"), Some(0x2));
+
+    // Other ROM sections stay as they were
+    assert_eq!(saved.roms["BRIX"], [(4, hosts(&["Left"]))]);
+    assert_eq!(saved.keymap(Some("BRIX")).key("Left"), Some(4));
+}
@@ -0,0 +1,180 @@
+#![cfg(feature = "sdl")]
+
+use chip8_emu::input::Input;
+use chip8_emu::keypad::VirtualKeypad;
+
+use sdl2::event::Event;
+use sdl2::mouse::{MouseButton, MouseState};
+use sdl2::rect::Rect;
+
+// Virtual keypad driven by synthetic SDL events. The keypad fills the right of a 960x320 window,
+// in 80 pixel cells:
+//
+//     1 2 3 C
+//     4 5 6 D
+//     7 8 9 E
+//     A 0 B F
+
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
+fn keypad() -> VirtualKeypad {
+    VirtualKeypad::new(Rect::new(640, 0, 320, 320), (960, 320))
+}
+
+/// Center of the cell in `col`, `row`
+fn cell(col: i32, row: i32) -> (i32, i32) {
+    (640 + col * 80 + 40, row * 80 + 40)
+}
+
+fn held(input: &Input) -> Vec<usize> {
+    (0..16).filter(|key| input.poll(*key) != 0).collect()
+}
+
+fn mouse_down(which: u32, (x, y): (i32, i32)) -> Event {
+    Event::MouseButtonDown { timestamp: 0, window_id: 1, which, mouse_btn: MouseButton::Left, clicks: 1, x, y }
+}
+
+fn mouse_up(which: u32, (x, y): (i32, i32)) -> Event {
+    Event::MouseButtonUp { timestamp: 0, window_id: 1, which, mouse_btn: MouseButton::Left, clicks: 1, x, y }
+}
+
+/// Mouse moved to `x`, `y`, with the left button down if `left`
+fn mouse_motion(left: bool, (x, y): (i32, i32)) -> Event {
+    let mousestate = MouseState::from_sdl_state(if left { 1 } else { 0 });
+    Event::MouseMotion { timestamp: 0, window_id: 1, which: 0, mousestate, x, y, xrel: 0, yrel: 0 }
+}
+
+/// Window position as the fraction of the window size touch events use
+fn touch((x, y): (i32, i32)) -> (f32, f32) {
+    (x as f32 / 960.0, y as f32 / 320.0)
+}
+
+fn finger_down(finger_id: i64, pos: (i32, i32)) -> Event {
+    let (x, y) = touch(pos);
+    Event::FingerDown { timestamp: 0, touch_id: 1, finger_id, x, y, dx: 0.0, dy: 0.0, pressure: 1.0 }
+}
+
+fn finger_motion(finger_id: i64, pos: (i32, i32)) -> Event {
+    let (x, y) = touch(pos);
+    Event::FingerMotion { timestamp: 0, touch_id: 1, finger_id, x, y, dx: 0.0, dy: 0.0, pressure: 1.0 }
+}
+
+fn finger_up(finger_id: i64, pos: (i32, i32)) -> Event {
+    let (x, y) = touch(pos);
+    Event::FingerUp { timestamp: 0, touch_id: 1, finger_id, x, y, dx: 0.0, dy: 0.0, pressure: 0.0 }
+}
+
+#[test]
+fn key_at_follows_the_vip_layout() {
+    let keypad = keypad();
+
+    assert_eq!(keypad.key_at(cell(0, 0).0, cell(0, 0).1), Some(0x1));
+    assert_eq!(keypad.key_at(cell(3, 0).0, cell(3, 0).1), Some(0xc));
+    assert_eq!(keypad.key_at(cell(1, 3).0, cell(1, 3).1), Some(0x0));
+    assert_eq!(keypad.key_at(959, 319), Some(0xf));
+    assert_eq!(keypad.key_at(639, 40), None);
+    assert_eq!(keypad.key_at(960, 40), None);
+}
+
+#[test]
+fn click_holds_key_until_release() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    assert!(keypad.handle(&mouse_down(0, cell(1, 1)), &mut input));
+    assert_eq!(held(&input), vec![0x5]);
+
+    assert!(keypad.handle(&mouse_up(0, cell(1, 1)), &mut input));
+    assert_eq!(held(&input), Vec::<usize>::new());
+}
+
+#[test]
+fn release_outside_keypad_still_releases() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    keypad.handle(&mouse_down(0, cell(2, 2)), &mut input);
+    keypad.handle(&mouse_up(0, (10, 10)), &mut input);
+
+    assert_eq!(held(&input), Vec::<usize>::new());
+}
+
+#[test]
+fn click_outside_keypad_presses_nothing() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    keypad.handle(&mouse_down(0, (100, 100)), &mut input);
+
+    assert_eq!(held(&input), Vec::<usize>::new());
+}
+
+#[test]
+fn drag_moves_press_to_other_key() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    keypad.handle(&mouse_down(0, cell(0, 0)), &mut input);
+    keypad.handle(&mouse_motion(true, cell(1, 0)), &mut input);
+    assert_eq!(held(&input), vec![0x2]);
+
+    // Off the keypad nothing is held
+    keypad.handle(&mouse_motion(true, (100, 40)), &mut input);
+    assert_eq!(held(&input), Vec::<usize>::new());
+}
+
+#[test]
+fn hover_without_button_presses_nothing() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    assert!(!keypad.handle(&mouse_motion(false, cell(1, 1)), &mut input));
+    assert_eq!(held(&input), Vec::<usize>::new());
+}
+
+#[test]
+fn fingers_hold_keys_independently() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    keypad.handle(&finger_down(1, cell(0, 1)), &mut input);
+    keypad.handle(&finger_down(2, cell(2, 1)), &mut input);
+    assert_eq!(held(&input), vec![0x4, 0x6]);
+
+    keypad.handle(&finger_up(1, cell(0, 1)), &mut input);
+    assert_eq!(held(&input), vec![0x6]);
+
+    keypad.handle(&finger_motion(2, cell(3, 1)), &mut input);
+    assert_eq!(held(&input), vec![0xd]);
+}
+
+#[test]
+fn key_held_by_two_pointers_stays_held_until_both_leave() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    keypad.handle(&finger_down(1, cell(1, 1)), &mut input);
+    keypad.handle(&mouse_down(0, cell(1, 1)), &mut input);
+
+    keypad.handle(&finger_up(1, cell(1, 1)), &mut input);
+    assert_eq!(held(&input), vec![0x5]);
+
+    keypad.handle(&mouse_up(0, cell(1, 1)), &mut input);
+    assert_eq!(held(&input), Vec::<usize>::new());
+}
+
+#[test]
+fn mouse_events_made_from_touches_are_ignored() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    keypad.handle(&finger_down(1, cell(1, 1)), &mut input);
+
+    // SDL follows the touch with a click, whose release must not drop the finger's key
+    assert!(!keypad.handle(&mouse_down(TOUCH_MOUSE_ID, cell(1, 1)), &mut input));
+    assert!(!keypad.handle(&mouse_up(TOUCH_MOUSE_ID, cell(1, 1)), &mut input));
+    assert_eq!(held(&input), vec![0x5]);
+}
+
+#[test]
+fn keyboard_keys_are_left_alone() {
+    let (mut keypad, mut input) = (keypad(), Input::new());
+
+    input.set(0x5, true);
+    keypad.handle(&mouse_down(0, cell(0, 0)), &mut input);
+    keypad.handle(&mouse_up(0, cell(0, 0)), &mut input);
+
+    assert_eq!(held(&input), vec![0x5]);
+}
@@ -0,0 +1,204 @@
+use chip8_emu::emulator::Emulator;
+use chip8_emu::movie::{state_hash, Movie, Player, Recorder};
+use chip8_emu::quirks::Quirks;
+use chip8_emu::rng::{Algorithm, Rng};
+
+// Input movies recorded from a scripted session and played back on a fresh machine. BRIX moves
+// its paddle with 4 and 6 and picks the ball direction with Cxkk, so both the input and the
+// random number generator have to be reproduced for the end state to match.
+
+const BRIX: &[u8] = include_bytes!("../games/BRIX");
+
+/// Instructions in the recorded session
+const LENGTH: u64 = 6000;
+
+/// Key changes made during the session, as instruction and key state
+const SESSION: [(u64, usize, bool); 6] = [
+    (600, 0x4, true),
+    (900, 0x4, false),
+    (1500, 0x6, true),
+    (1500, 0x5, true),
+    (2700, 0x6, false),
+    (2710, 0x5, false),
+];
+
+/// Machine with BRIX loaded, at power on
+fn machine(seed: u64, quirks: Quirks) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::new(seed);
+    emu.quirks = quirks;
+    emu.memory.load_bytes(BRIX).unwrap();
+
+    emu
+}
+
+/// Record the session on a fresh machine, returns the movie and the machine at its end
+fn record(seed: u64) -> (Movie, Emulator) {
+    let mut emu = machine(seed, Quirks::vip());
+    let mut recorder = Recorder::new(BRIX, &emu);
+
+    for position in 0..LENGTH {
+        for (_, key, pressed) in SESSION.iter().filter(|(p, _, _)| *p == position) {
+            emu.input.set(*key, *pressed);
+        }
+
+        recorder.record(&emu);
+        emu.step().unwrap();
+    }
+
+    (recorder.finish(&emu), emu)
+}
+
+/// Play `movie` on a fresh machine until it ends
+fn play(movie: &Movie) -> (Player, Emulator) {
+    let mut emu = machine(rand::random(), Quirks::default());
+    movie.apply(&mut emu);
+
+    let mut player = Player::new(movie.clone());
+    while player.play(&mut emu) {
+        emu.step().unwrap();
+    }
+
+    (player, emu)
+}
+
+#[test]
+fn recording_holds_setup_and_key_changes() {
+    let (movie, emu) = record(42);
+
+    assert_eq!(movie.seed, 42);
+    assert_eq!(movie.quirks, Quirks::vip());
+    assert_eq!(movie.cycles_per_frame, emu.cycles_per_frame);
+    assert_eq!(movie.length, LENGTH);
+    assert_eq!(movie.hash, state_hash(&emu));
+
+    let events: Vec<String> = movie.events.iter().map(|e| e.to_string()).collect();
+    assert_eq!(events, ["600:+4", "900:-4", "1500:+5", "1500:+6", "2700:-6", "2710:-5"]);
+}
+
+#[test]
+fn playback_reproduces_the_run() {
+    let (movie, recorded) = record(42);
+    let (player, emu) = play(&movie);
+
+    assert_eq!(player.verify(&emu), Ok(()));
+    assert_eq!(emu.save_state(), recorded.save_state());
+}
+
+#[test]
+fn movie_file_round_trips() {
+    let (movie, _) = record(42);
+
+    let text = movie.to_string();
+    assert_eq!(text.parse::<Movie>(), Ok(movie));
+}
+
+#[test]
+fn changed_input_fails_verification() {
+    let (mut movie, _) = record(42);
+
+    // Release 6 half way through holding it
+    movie.events[4].cycle -= 600;
+
+    let (player, emu) = play(&movie);
+
+    assert!(player.verify(&emu).is_err());
+}
+
+#[test]
+fn changed_seed_fails_verification() {
+    let (mut movie, _) = record(42);
+    movie.seed = 43;
+
+    let (player, emu) = play(&movie);
+
+    assert!(player.verify(&emu).is_err());
+}
+
+#[test]
+fn vip_generator_is_replayed() {
+    let mut emu = machine(0, Quirks::vip());
+    emu.rng = Rng::with_algorithm(Algorithm::Vip, 0x1234);
+    let mut recorder = Recorder::new(BRIX, &emu);
+
+    for _ in 0..LENGTH {
+        recorder.record(&emu);
+        emu.step().unwrap();
+    }
+
+    let movie: Movie = recorder.finish(&emu).to_string().parse().unwrap();
+    assert_eq!(movie.rng, Algorithm::Vip);
+
+    let (player, played) = play(&movie);
+    assert_eq!(player.verify(&played), Ok(()));
+}
+
+#[test]
+fn unfinished_playback_fails_verification() {
+    let (movie, _) = record(42);
+    let mut emu = machine(42, Quirks::vip());
+    let mut player = Player::new(movie);
+
+    for _ in 0..100 {
+        player.play(&mut emu);
+        emu.step().unwrap();
+    }
+
+    assert!(player.verify(&emu).unwrap_err().contains("100 of 6000"));
+}
+
+#[test]
+fn rewound_recording_drops_later_events() {
+    let mut emu = machine(1, Quirks::default());
+    let mut recorder = Recorder::new(BRIX, &emu);
+
+    for position in 0..20 {
+        emu.input.set(0x4, position >= 10);
+        recorder.record(&emu);
+        emu.step().unwrap();
+    }
+
+    // Rewinding restores the input along with the rest of the machine
+    emu.input.set(0x4, false);
+    recorder.truncate(5, &emu);
+    recorder.record(&emu);
+
+    let movie = recorder.finish(&emu);
+    assert!(movie.events.is_empty());
+    assert_eq!(movie.length, 6);
+}
+
+#[test]
+fn other_rom_is_rejected() {
+    let (movie, _) = record(42);
+
+    assert!(movie.check_rom(BRIX).is_ok());
+    assert!(movie.check_rom(&BRIX[1..]).is_err());
+}
+
+#[test]
+fn invalid_movie_files_are_rejected() {
+    let header = "rom 0\nrng xorshift\nseed 1\nquirks vip\nipf 10\nlength 100\nhash 0\n";
+
+    let err = format!("{}oops\n", header).parse::<Movie>().unwrap_err();
+    assert_eq!(err.line, 8);
+
+    assert!(format!("{}50:+5\n10:-5\n", header).parse::<Movie>().is_err());
+    assert!(format!("{}100:+5\n", header).parse::<Movie>().is_err());
+    assert!(format!("{}10:+g\n", header).parse::<Movie>().is_err());
+    assert!("rom 0\nrng xorshift\nseed 1\nquirks vip\nipf 10\nlength 100\n".parse::<Movie>().is_err());
+    assert!(header.replace("rng xorshift", "rng lcg").parse::<Movie>().is_err());
+    assert!(header.replace("ipf 10", "ipf 0").parse::<Movie>().is_err());
+}
+
+#[test]
+fn quirk_profiles_round_trip() {
+    for profile in ["default", "vip", "chip48", "schip", "xochip", "vip,-display-wait", "default,+load-store-x,+wrap"] {
+        let quirks: Quirks = profile.parse().unwrap();
+
+        assert_eq!(quirks.to_string().parse::<Quirks>(), Ok(quirks), "{}", profile);
+    }
+
+    assert_eq!(Quirks::vip().to_string(), "vip");
+    assert_eq!("chip48,-jump-vx".parse::<Quirks>().unwrap().to_string(), "default,+load-store-x");
+}
@@ -0,0 +1,130 @@
+use chip8_emu::display::Display;
+use chip8_emu::golden;
+use chip8_emu::memory::{BIG_FONTS, FONTS};
+use chip8_emu::ocr::{bc_test_error, glyph_lines, read_text};
+use chip8_emu::quirks::Quirks;
+use chip8_emu::rng::Rng;
+
+// Text read off framebuffers drawn pixel by pixel, and off the screens of the test ROMs.
+
+const BC_TEST: &[u8] = include_bytes!("../games/BC_test.ch8");
+const TEST_OPCODE: &[u8] = include_bytes!("../games/test_opcode.ch8");
+
+/// Draw `rows` of an 8 pixel wide sprite with its top left corner at `x`, `y`
+fn draw(display: &mut Display, rows: &[u8], x: usize, y: usize) {
+    let width = display.width();
+
+    for (dy, row) in rows.iter().enumerate() {
+        for dx in 0..8 {
+            if row & (0x80 >> dx) != 0 {
+                display.memory[(y + dy) * width + x + dx] = 1;
+            }
+        }
+    }
+}
+
+/// Small font sprite of hexadecimal digit `d`
+fn font(d: usize) -> &'static [u8] {
+    &FONTS[d * 5..d * 5 + 5]
+}
+
+fn big_font(d: usize) -> &'static [u8] {
+    &BIG_FONTS[d * 10..d * 10 + 10]
+}
+
+#[test]
+fn blank_screen_has_no_text() {
+    assert!(read_text(&Display::new()).is_empty());
+}
+
+#[test]
+fn font_digits_form_words() {
+    let mut display = Display::new();
+
+    // Digits 5 pixels apart, as Fx29 text is usually drawn, then a wider gap before the next word
+    draw(&mut display, font(0xe), 2, 2);
+    draw(&mut display, font(1), 12, 2);
+    draw(&mut display, font(2), 17, 2);
+    draw(&mut display, font(0xa), 30, 2);
+
+    assert_eq!(read_text(&display), ["E 12 A"]);
+
+    let lines = glyph_lines(&display);
+    let positions: Vec<(&str, isize, isize)> = lines[0].iter().map(|g| (g.text, g.x, g.y)).collect();
+    assert_eq!(positions, [("E", 2, 2), ("1", 12, 2), ("2", 17, 2), ("A", 30, 2)]);
+}
+
+#[test]
+fn lines_are_read_top_to_bottom() {
+    let mut display = Display::new();
+
+    draw(&mut display, font(7), 40, 20);
+    draw(&mut display, font(3), 10, 1);
+    draw(&mut display, font(4), 15, 2);
+
+    assert_eq!(read_text(&display), ["34", "7"]);
+}
+
+#[test]
+fn big_font_in_hires() {
+    let mut display = Display::new();
+    display.set_hires(true);
+
+    draw(&mut display, big_font(9), 100, 40);
+    draw(&mut display, big_font(0), 110, 40);
+
+    assert_eq!(read_text(&display), ["90"]);
+}
+
+#[test]
+fn glyph_touching_a_drawing_is_not_read() {
+    let mut display = Display::new();
+
+    draw(&mut display, font(8), 10, 10);
+    draw(&mut display, &[0x80], 14, 12);
+
+    assert!(read_text(&display).is_empty());
+}
+
+#[test]
+fn test_rom_verdicts_are_read() {
+    let emu = golden::run(BC_TEST, 300, Quirks::default(), Rng::new(golden::SEED), &[]).unwrap();
+    assert!(read_text(&emu.display).iter().any(|line| line.split(' ').any(|w| w == "BON")));
+
+    let emu = golden::run(TEST_OPCODE, 300, Quirks::default(), Rng::new(golden::SEED), &[]).unwrap();
+    let text = read_text(&emu.display).join(" ");
+    assert!(text.contains("OK"), "{}", text);
+    assert!(!text.contains("NO"), "{}", text);
+}
+
+#[test]
+fn bc_test_error_codes() {
+    let lines = |text: &[&str]| text.iter().map(|l| l.to_string()).collect::<Vec<String>>();
+
+    assert_eq!(bc_test_error(&lines(&["E 01"])), Some((1, "3XNN verify that the jump condition is fair")));
+    assert_eq!(
+        bc_test_error(&lines(&["BC", "E 17"])),
+        Some((17, "FX33 calculating the binary representation is mistaken or the result is poorly stored"))
+    );
+    assert_eq!(bc_test_error(&lines(&["E 12"])).map(|(_, d)| d.starts_with("8XYE")), Some(true));
+
+    assert_eq!(bc_test_error(&lines(&["BON"])), None);
+    assert_eq!(bc_test_error(&lines(&["E 00"])), None);
+    assert_eq!(bc_test_error(&lines(&["E 18"])), None);
+    assert_eq!(bc_test_error(&lines(&["E"])), None);
+    assert_eq!(bc_test_error(&[]), None);
+}
+
+#[test]
+fn bc_test_error_from_the_screen() {
+    let mut display = Display::new();
+
+    // E and a two digit code in the font, as a failed BC_test.ch8 shows error 5
+    draw(&mut display, font(0xe), 10, 10);
+    draw(&mut display, font(0), 20, 10);
+    draw(&mut display, font(5), 25, 10);
+
+    let text = read_text(&display);
+    assert_eq!(text, ["E 05"]);
+    assert_eq!(bc_test_error(&text), Some((5, "8XY5 verify that VF is set to 0 when there is a borrow")));
+}
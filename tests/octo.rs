@@ -0,0 +1,265 @@
+use chip8_emu::debugger::Monitor;
+use chip8_emu::octo::compile;
+
+// Octo programs compiled against bytes worked out from the Octo manual. Programs that start with
+// `: main` need no jump to it, so their output starts at 0x200 with the first statement.
+
+fn rom(source: &str) -> Vec<u8> {
+    compile(source).unwrap_or_else(|e| panic!("{}", e)).rom
+}
+
+/// Line and message of the error `source` fails with
+fn error(source: &str) -> (usize, String) {
+    let err = compile(source).unwrap_err();
+
+    (err.line, err.msg)
+}
+
+#[test]
+fn main_after_subroutines_is_jumped_to() {
+    let source = "
+        : sub
+            return
+        : main
+            sub
+    ";
+
+    assert_eq!(rom(source), [0x12, 0x04, 0x00, 0xee, 0x22, 0x02]);
+}
+
+#[test]
+fn statements() {
+    let source = "
+        : main
+        clear return exit lores hires scroll-left scroll-right scroll-down 3 scroll-up 2 audio plane 3
+        bcd v1 save v2 load v3 save v1 - v4 load v1 - v4 saveflags v5 loadflags v6 sprite v1 v2 7
+        i := 0x123 i += v4 i := hex v5 i := bighex v6 delay := v1 buzzer := v2 pitch := v3
+        v1 := key v2 := delay v3 := random 0x0f
+        v4 |= v5 v4 &= v5 v4 ^= v5 v4 += v5 v4 -= v5 v4 >>= v5 v4 =- v5 v4 <<= v5 v4 -= 1
+        native 0x123
+    ";
+
+    let words: Vec<u16> = rom(source).chunks(2).map(|w| u16::from_be_bytes([w[0], w[1]])).collect();
+    assert_eq!(
+        words,
+        [
+            0x00e0, 0x00ee, 0x00fd, 0x00fe, 0x00ff, 0x00fc, 0x00fb, 0x00c3, 0x00d2, 0xf002, 0xf301, //
+            0xf133, 0xf255, 0xf365, 0x5142, 0x5143, 0xf575, 0xf685, 0xd127, //
+            0xa123, 0xf41e, 0xf529, 0xf630, 0xf115, 0xf218, 0xf33a, //
+            0xf10a, 0xf207, 0xc30f, //
+            0x8451, 0x8452, 0x8453, 0x8454, 0x8455, 0x8456, 0x8457, 0x845e, 0x74ff, //
+            0x0123,
+        ]
+    );
+}
+
+#[test]
+fn alias_and_const() {
+    let source = "
+        :alias x v3
+        :const speed 7
+        : main
+            x := speed
+            x += 1
+            v2 := x
+    ";
+
+    assert_eq!(rom(source), [0x63, 0x07, 0x73, 0x01, 0x82, 0x30]);
+}
+
+#[test]
+fn calc_evaluates_right_to_left() {
+    let source = "
+        : main
+        :calc width { 8 * 2 + 1 }
+        :calc half { width / 2 }
+            v0 := width
+            v1 := half
+            :byte { half - 1 }
+        :calc here { HERE }
+            i := here
+    ";
+
+    assert_eq!(rom(source), [0x60, 0x18, 0x61, 0x0c, 0x0b, 0xa2, 0x05]);
+}
+
+#[test]
+fn macros_substitute_arguments() {
+    let source = "
+        :macro twice reg { reg += 1 reg += 1 }
+        : main
+            twice v4
+            twice v5
+    ";
+
+    assert_eq!(rom(source), [0x74, 0x01, 0x74, 0x01, 0x75, 0x01, 0x75, 0x01]);
+}
+
+#[test]
+fn if_then_skips_one_statement() {
+    let source = "
+        : main
+            if v0 == 5 then v1 := 2
+            if v0 != v2 then v1 := 3
+            if v0 key then clear
+    ";
+
+    assert_eq!(rom(source), [0x40, 0x05, 0x61, 0x02, 0x50, 0x20, 0x61, 0x03, 0xe0, 0xa1, 0x00, 0xe0]);
+}
+
+#[test]
+fn conditions_can_compare_against_calc_expressions() {
+    assert_eq!(rom(": main if v0 == { 2 + 3 } then v1 := 2"), [0x40, 0x05, 0x61, 0x02]);
+    assert_eq!(rom(": main if v0 != { 1 + ( 2 * 3 ) } begin v1 := 2 end"), [0x40, 0x07, 0x12, 0x06, 0x61, 0x02]);
+
+    // A later `then` doesn't close a condition that is missing one
+    assert_eq!(
+        error(": main\n  if v0 == { 2 + 3 } v1 := 2\n  if v0 == 1 then v1 := 3"),
+        (2, "'if' without 'then' or 'begin'".to_string())
+    );
+}
+
+#[test]
+fn ordered_comparisons_subtract_into_compare_temp() {
+    assert_eq!(rom(": main if v1 < v2 then v3 := 1"), [0x8f, 0x10, 0x8f, 0x25, 0x4f, 0x00, 0x63, 0x01]);
+    assert_eq!(rom(": main if v1 > 3 then v3 := 1"), [0x6f, 0x03, 0x8f, 0x15, 0x4f, 0x00, 0x63, 0x01]);
+    assert_eq!(
+        rom(":alias compare-temp ve : main if v1 >= v2 then v3 := 1"),
+        [0x8e, 0x10, 0x8e, 0x25, 0x3f, 0x00, 0x63, 0x01]
+    );
+}
+
+#[test]
+fn if_begin_else_end() {
+    let source = "
+        : main
+            if v0 == 1 begin
+                v1 := 1
+            else
+                v1 := 2
+            end
+            exit
+    ";
+
+    // 0x200 se v0, 1   0x202 jp 0x208   0x204 ld v1, 1   0x206 jp 0x20a   0x208 ld v1, 2   0x20a exit
+    assert_eq!(rom(source), [0x30, 0x01, 0x12, 0x08, 0x61, 0x01, 0x12, 0x0a, 0x61, 0x02, 0x00, 0xfd]);
+}
+
+#[test]
+fn loop_while_again() {
+    let source = "
+        : main
+            v0 := 0
+            loop
+                v0 += 1
+                while v0 != 10
+            again
+            exit
+    ";
+
+    // 0x200 ld v0, 0   0x202 add v0, 1   0x204 sne v0, 10   0x206 jp 0x20a   0x208 jp 0x202   0x20a exit
+    assert_eq!(rom(source), [0x60, 0x00, 0x70, 0x01, 0x40, 0x0a, 0x12, 0x0a, 0x12, 0x02, 0x00, 0xfd]);
+}
+
+#[test]
+fn forward_references_are_patched() {
+    let source = "
+        : main
+            i := long sprite
+            :unpack 0xa sprite
+            jump0 sprite
+        : sprite
+            0x3c
+    ";
+
+    assert_eq!(rom(source), [0xf0, 0x00, 0x02, 0x0a, 0x60, 0xa2, 0x61, 0x0a, 0xb2, 0x0a, 0x3c]);
+}
+
+#[test]
+fn next_names_the_operand_byte() {
+    assert_eq!(rom(": main :next speed v0 := 5 i := speed"), [0x60, 0x05, 0xa2, 0x01]);
+}
+
+#[test]
+fn breakpoints_and_monitors() {
+    let source = "
+        : main
+            v0 := 1
+        :breakpoint here
+            v1 := 2
+        :monitor data 4
+        :monitor main \"%d %x\"
+        : data
+            1 2 3 4
+    ";
+
+    let program = compile(source).unwrap();
+
+    assert_eq!(program.rom, [0x60, 0x01, 0x61, 0x02, 0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(program.breakpoints.into_iter().collect::<Vec<_>>(), [(0x202, "here".to_string())]);
+    assert_eq!(
+        program.monitors,
+        [
+            Monitor { name: "data".to_string(), addr: 0x204, len: 4 },
+            Monitor { name: "main".to_string(), addr: 0x200, len: 2 },
+        ]
+    );
+}
+
+#[test]
+fn addresses_past_0xfff_need_a_long_load() {
+    let far = |statement: &str| format!(": main\n  {}\n:org 0x1002\n: far\n  exit", statement);
+
+    for statement in ["jump far", "far", ":call far", "i := far", "jump0 far", ":unpack 0 far"] {
+        assert_eq!(error(&far(statement)), (2, "address 0x1002 is out of range".to_string()), "{}", statement);
+    }
+
+    // Long loads reach it, labels known before the jump are checked as values
+    assert_eq!(&rom(&far("i := long far"))[..4], [0xf0, 0x00, 0x10, 0x02]);
+    assert_eq!(error(":org 0x1002\n: main\n  jump main"), (3, "value 4098 is out of range".to_string()));
+}
+
+#[test]
+fn control_flow_past_0xfff_is_an_error() {
+    let cases = [
+        (": main\n:org 0xffc\n  if v0 == 1 begin\n  v1 := 1\n  end", 5),
+        (": main\n:org 0xffc\n  if v0 == 1 begin\n  else\n  v1 := 1\n  end", 4),
+        (": main\n:org 0xffa\n  loop\n  while v0 != 1\n  v1 := 1\n  again", 6),
+        (": main\n:org 0x1000\n  loop\n  again", 4),
+    ];
+
+    for (source, line) in cases {
+        let (err_line, msg) = error(source);
+        assert_eq!(err_line, line, "{}: {}", source, msg);
+        assert!(msg.starts_with("address 0x10"), "{}: {}", source, msg);
+    }
+}
+
+#[test]
+fn errors_point_at_their_line() {
+    let cases = [
+        ("v0 := 1", 1, "no 'main' label"),
+        (": main\n  jump nowhere", 2, "undefined name 'nowhere'"),
+        (": main\n\n  else", 3, "'else' without 'if ... begin'"),
+        (": main\n  end", 2, "'end' without 'if ... begin'"),
+        (": main\n  again", 2, "'again' without 'loop'"),
+        (": main\n  while v0 == 1", 2, "'while' outside of a loop"),
+        (": main\n  if v0 == 1 begin\n  v1 := 2", 3, "missing 'end'"),
+        (": main\n  if v0 == 1 v1 := 2", 2, "without 'then' or 'begin'"),
+        (": main\n  if v0 ~ 1 then v1 := 2", 2, "unknown comparison '~'"),
+        (": main\n  v0 := 256", 2, "out of range"),
+        (": main\n  v0 ** v1", 2, "unknown operator '**'"),
+        (": main\n  :foo", 2, "unknown directive ':foo'"),
+        (": main\n: main", 2, "'main' is already defined"),
+        (": main\n: v3", 2, "'v3' can not be used as a name"),
+        (": main\n:calc x { 1 + ( 2 }", 2, "missing ')'"),
+        (": main\n:macro m { m }\nm", 3, "too many macro expansions"),
+    ];
+
+    for (source, line, msg) in cases {
+        let (err_line, err_msg) = error(source);
+
+        assert_eq!(err_line, line, "{}: {}", source, err_msg);
+        assert!(err_msg.contains(msg), "{}: {}", source, err_msg);
+    }
+}
@@ -0,0 +1,785 @@
+use chip8_emu::emulator::Emulator;
+use chip8_emu::memory::{FONT_OFFSET, BIG_FONT_OFFSET};
+use chip8_emu::quirks::{Quirks, LoadStore};
+use chip8_emu::rng::Rng;
+
+// Conformance tests for every opcode. Each test sets up a machine, runs a single instruction
+// with `check` and compares the complete machine state afterwards with the state before it plus
+// the changes the instruction is expected to make. Anything else changing fails the test.
+
+/// Complete machine state, in a form that can be compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: u16,
+    dt: u8,
+    st: u8,
+    flags: [u8; 8],
+    stack: [u16; 16],
+    stack_top: usize,
+    memory: Vec<u8>,
+    display: Vec<u8>,
+    hires: bool,
+    planes: u8,
+    input: [u8; 16],
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    rng: u64,
+    tick_cnt: u32,
+    frame_drawn: bool,
+    exited: bool,
+    key_wait: Option<u8>,
+}
+
+impl State {
+    fn capture(emu: &Emulator) -> Self {
+        let mut v = [0; 16];
+        for (x, val) in v.iter_mut().enumerate() {
+            *val = emu.registers.reg_read(x as u8).unwrap();
+        }
+
+        State {
+            v,
+            i: emu.registers.i,
+            pc: emu.registers.pc,
+            sp: emu.registers.sp,
+            dt: emu.registers.dt,
+            st: emu.registers.st,
+            flags: emu.registers.flags,
+            stack: emu.memory.stack,
+            stack_top: emu.memory.sp,
+            memory: emu.memory.memory.to_vec(),
+            display: emu.display.memory.to_vec(),
+            hires: emu.display.hires,
+            planes: emu.display.planes,
+            input: emu.input.input,
+            audio_pattern: emu.audio.pattern,
+            pitch: emu.audio.pitch,
+            rng: emu.rng.state,
+            tick_cnt: emu.tick_cnt,
+            frame_drawn: emu.frame_drawn,
+            exited: emu.exited,
+            key_wait: emu.key_wait,
+        }
+    }
+}
+
+/// Compare two states, naming the first differing byte of memory or display
+fn assert_state(actual: &State, expected: &State) {
+    if let Some(addr) = (0..actual.memory.len()).find(|a| actual.memory[*a] != expected.memory[*a]) {
+        panic!("memory {:#05x} is {:02x}, expected {:02x}", addr, actual.memory[addr], expected.memory[addr]);
+    }
+
+    if let Some(px) = (0..actual.display.len()).find(|p| actual.display[*p] != expected.display[*p]) {
+        panic!("display pixel {} is {}, expected {}", px, actual.display[px], expected.display[px]);
+    }
+
+    let rest = |s: &State| State { memory: Vec::new(), display: Vec::new(), ..s.clone() };
+    assert_eq!(rest(actual), rest(expected));
+}
+
+/// Machine with `program` at 0x200, Vx = x * 0x11 and I = 0x300
+fn machine(program: &[u16]) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::new(1);
+
+    let bytes: Vec<u8> = program.iter().flat_map(|op| op.to_be_bytes().to_vec()).collect();
+    emu.memory.load_bytes(&bytes).unwrap();
+
+    for x in 0..16 {
+        emu.registers.reg_write(x, x * 0x11).unwrap();
+    }
+    emu.registers.i = 0x300;
+
+    emu
+}
+
+/// Set Vx
+fn set(emu: &mut Emulator, x: u8, val: u8) {
+    emu.registers.reg_write(x, val).unwrap();
+}
+
+/// Run the instruction at PC, the machine must end up as before it with `changes` applied
+///
+/// The expected state already has PC advanced past a 2 byte instruction and the timer tick counted.
+fn check<F: FnOnce(&mut State)>(emu: &mut Emulator, changes: F) {
+    let mut expected = State::capture(emu);
+    expected.pc += 2;
+    expected.tick_cnt += 1;
+    changes(&mut expected);
+
+    emu.step().unwrap();
+
+    assert_state(&State::capture(emu), &expected);
+}
+
+#[test]
+fn sys_is_ignored() {
+    let mut emu = machine(&[0x0123, 0x0000]);
+    check(&mut emu, |_| ());
+    check(&mut emu, |_| ());
+}
+
+#[test]
+fn cls_clears_display() {
+    let mut emu = machine(&[0x00e0]);
+    emu.display.memory[0] = 1;
+    emu.display.memory[2047] = 1;
+
+    check(&mut emu, |s| {
+        s.display[0] = 0;
+        s.display[2047] = 0;
+    });
+}
+
+#[test]
+fn ret_pops_return_address() {
+    let mut emu = machine(&[0x00ee]);
+    emu.memory.push(0x0456).unwrap();
+    emu.registers.sp = 0;
+
+    check(&mut emu, |s| {
+        s.pc = 0x458;
+        s.stack_top = usize::MAX;
+        s.sp = usize::MAX as u16;
+    });
+}
+
+#[test]
+fn scroll_down() {
+    let mut emu = machine(&[0x00c2]);
+    emu.display.memory[3] = 1;
+
+    check(&mut emu, |s| {
+        s.display[3] = 0;
+        s.display[3 + 2 * 64] = 1;
+    });
+}
+
+#[test]
+fn scroll_up() {
+    let mut emu = machine(&[0x00d1]);
+    emu.display.memory[3 + 64] = 1;
+
+    check(&mut emu, |s| {
+        s.display[3 + 64] = 0;
+        s.display[3] = 1;
+    });
+}
+
+#[test]
+fn scroll_right_and_left() {
+    let mut emu = machine(&[0x00fb, 0x00fc]);
+    emu.display.memory[1] = 1;
+
+    check(&mut emu, |s| {
+        s.display[1] = 0;
+        s.display[5] = 1;
+    });
+    check(&mut emu, |s| {
+        s.display[5] = 0;
+        s.display[1] = 1;
+    });
+}
+
+#[test]
+fn exit_stops_without_advancing() {
+    let mut emu = machine(&[0x00fd]);
+
+    check(&mut emu, |s| {
+        s.pc -= 2;
+        s.exited = true;
+    });
+}
+
+#[test]
+fn high_and_low_switch_resolution() {
+    let mut emu = machine(&[0x00ff, 0x00fe]);
+    emu.display.memory[0] = 1;
+
+    check(&mut emu, |s| {
+        s.hires = true;
+        s.display[0] = 0;
+    });
+    check(&mut emu, |s| s.hires = false);
+}
+
+#[test]
+fn jump() {
+    let mut emu = machine(&[0x1234]);
+
+    check(&mut emu, |s| s.pc = 0x234);
+}
+
+#[test]
+fn call_pushes_return_address() {
+    let mut emu = machine(&[0x2345]);
+
+    check(&mut emu, |s| {
+        s.pc = 0x345;
+        s.stack[0] = 0x200;
+        s.stack_top = 0;
+        s.sp = 0;
+    });
+}
+
+#[test]
+fn skip_eq_byte() {
+    let mut emu = machine(&[0x3122, 0x3111]);
+
+    check(&mut emu, |_| ());
+    check(&mut emu, |s| s.pc += 2);
+}
+
+#[test]
+fn skip_ne_byte() {
+    let mut emu = machine(&[0x4111, 0x4122]);
+
+    check(&mut emu, |_| ());
+    check(&mut emu, |s| s.pc += 2);
+}
+
+#[test]
+fn skip_eq_reg() {
+    let mut emu = machine(&[0x5120, 0x5120]);
+
+    check(&mut emu, |_| ());
+    set(&mut emu, 2, 0x11);
+    check(&mut emu, |s| s.pc += 2);
+}
+
+#[test]
+fn skip_over_long_load() {
+    let mut emu = machine(&[0x3111, 0xf000, 0x1234]);
+
+    check(&mut emu, |s| s.pc += 4);
+}
+
+#[test]
+fn every_skip_steps_over_long_load() {
+    // V1 = 0x11, V2 = 0x22 and key 1 held, so each of these skips
+    for skip in [0x3111, 0x4122, 0x5110, 0x9120, 0xe19e, 0xe2a1] {
+        let mut emu = machine(&[skip, 0xf000, 0x1234, 0x00e0]);
+        emu.input.set(0x1, true);
+
+        // Lands on the CLS, not on the second half of the long load
+        check(&mut emu, |s| s.pc += 4);
+        check(&mut emu, |_| ());
+    }
+}
+
+#[test]
+fn skip_over_long_load_wraps_at_end_of_memory() {
+    // The skip is at 0xfffc and the long load wraps around to 0x0000
+    let mut emu = machine(&[]);
+    for (addr, byte) in [(0xfffc, 0x31), (0xfffd, 0x11), (0xfffe, 0xf0), (0xffff, 0x00), (0x0000, 0x12), (0x0001, 0x34)] {
+        emu.memory.write(addr, byte).unwrap();
+    }
+    emu.registers.pc = 0xfffc;
+
+    check(&mut emu, |s| s.pc = 0x0002);
+}
+
+#[test]
+fn store_range() {
+    let mut emu = machine(&[0x5232, 0x5322]);
+
+    check(&mut emu, |s| {
+        s.memory[0x300] = 0x22;
+        s.memory[0x301] = 0x33;
+    });
+    check(&mut emu, |s| {
+        s.memory[0x300] = 0x33;
+        s.memory[0x301] = 0x22;
+    });
+}
+
+#[test]
+fn load_range() {
+    let mut emu = machine(&[0x5233]);
+    emu.memory.memory[0x300] = 0xab;
+    emu.memory.memory[0x301] = 0xcd;
+
+    check(&mut emu, |s| {
+        s.v[2] = 0xab;
+        s.v[3] = 0xcd;
+    });
+}
+
+#[test]
+fn load_byte() {
+    let mut emu = machine(&[0x6a42]);
+
+    check(&mut emu, |s| s.v[0xa] = 0x42);
+}
+
+#[test]
+fn add_byte_wraps_without_flag() {
+    let mut emu = machine(&[0x7e05]);
+
+    check(&mut emu, |s| s.v[0xe] = 0xf3);
+
+    let mut emu = machine(&[0x7e20]);
+    check(&mut emu, |s| s.v[0xe] = 0x0e);
+}
+
+#[test]
+fn load_reg() {
+    let mut emu = machine(&[0x8120]);
+
+    check(&mut emu, |s| s.v[1] = 0x22);
+}
+
+#[test]
+fn logic_ops() {
+    let mut emu = machine(&[0x8341, 0x8562, 0x8783]);
+
+    check(&mut emu, |s| s.v[3] = 0x33 | 0x44);
+    check(&mut emu, |s| s.v[5] = 0x55 & 0x66);
+    check(&mut emu, |s| s.v[7] = 0x77 ^ 0x88);
+}
+
+#[test]
+fn logic_ops_reset_vf_with_quirk() {
+    let mut emu = machine(&[0x8121]);
+    emu.quirks.vf_reset = true;
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x33;
+        s.v[0xf] = 0;
+    });
+}
+
+#[test]
+fn add_reg_sets_carry() {
+    let mut emu = machine(&[0x8124, 0x8124]);
+    set(&mut emu, 1, 0xf0);
+    set(&mut emu, 2, 0x20);
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x10;
+        s.v[0xf] = 1;
+    });
+    check(&mut emu, |s| {
+        s.v[1] = 0x30;
+        s.v[0xf] = 0;
+    });
+}
+
+#[test]
+fn sub_sets_not_borrow() {
+    let mut emu = machine(&[0x8125, 0x8125, 0x8125]);
+    set(&mut emu, 1, 0x30);
+    set(&mut emu, 2, 0x10);
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x20;
+        s.v[0xf] = 1;
+    });
+
+    // Equal values do not borrow
+    set(&mut emu, 2, 0x20);
+    check(&mut emu, |s| {
+        s.v[1] = 0x00;
+        s.v[0xf] = 1;
+    });
+    check(&mut emu, |s| {
+        s.v[1] = 0xe0;
+        s.v[0xf] = 0;
+    });
+}
+
+#[test]
+fn subn_sets_not_borrow() {
+    let mut emu = machine(&[0x8127, 0x8127, 0x8127]);
+    set(&mut emu, 1, 0x10);
+    set(&mut emu, 2, 0x30);
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x20;
+        s.v[0xf] = 1;
+    });
+
+    set(&mut emu, 1, 0x30);
+    check(&mut emu, |s| {
+        s.v[1] = 0x00;
+        s.v[0xf] = 1;
+    });
+
+    set(&mut emu, 1, 0x40);
+    check(&mut emu, |s| {
+        s.v[1] = 0xf0;
+        s.v[0xf] = 0;
+    });
+}
+
+#[test]
+fn shift_right_sets_lsb() {
+    let mut emu = machine(&[0x8106, 0x8106]);
+    set(&mut emu, 1, 0x05);
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x02;
+        s.v[0xf] = 1;
+    });
+    check(&mut emu, |s| {
+        s.v[1] = 0x01;
+        s.v[0xf] = 0;
+    });
+}
+
+#[test]
+fn shift_left_sets_msb() {
+    let mut emu = machine(&[0x810e, 0x810e, 0x810e]);
+    set(&mut emu, 1, 0x81);
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x02;
+        s.v[0xf] = 1;
+    });
+    check(&mut emu, |s| {
+        s.v[1] = 0x04;
+        s.v[0xf] = 0;
+    });
+
+    // Only the top bit counts, not the low bits
+    set(&mut emu, 1, 0x41);
+    check(&mut emu, |s| {
+        s.v[1] = 0x82;
+        s.v[0xf] = 0;
+    });
+}
+
+#[test]
+fn shifts_read_vy_with_quirk() {
+    let mut emu = machine(&[0x8126, 0x812e]);
+    emu.quirks.shift_vy = true;
+    set(&mut emu, 2, 0x83);
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x41;
+        s.v[0xf] = 1;
+    });
+    check(&mut emu, |s| {
+        s.v[1] = 0x06;
+        s.v[0xf] = 1;
+    });
+}
+
+#[test]
+fn flag_wins_over_result_in_vf() {
+    // 8Fy4: 0xff + 0x01 carries, the result 0x00 is overwritten by the flag
+    let mut emu = machine(&[0x8f14]);
+    set(&mut emu, 1, 0x01);
+    check(&mut emu, |s| s.v[0xf] = 1);
+
+    // 8Fy5: 0x10 - 0x20 borrows
+    let mut emu = machine(&[0x8f15]);
+    set(&mut emu, 0xf, 0x10);
+    set(&mut emu, 1, 0x20);
+    check(&mut emu, |s| s.v[0xf] = 0);
+
+    // 8Fy6: 0x02 shifts out a 0
+    let mut emu = machine(&[0x8f06]);
+    set(&mut emu, 0xf, 0x02);
+    check(&mut emu, |s| s.v[0xf] = 0);
+
+    // 8Fy7: 0x20 - 0x10 does not borrow
+    let mut emu = machine(&[0x8f17]);
+    set(&mut emu, 0xf, 0x10);
+    set(&mut emu, 1, 0x20);
+    check(&mut emu, |s| s.v[0xf] = 1);
+
+    // 8FyE: 0x80 shifts out a 1
+    let mut emu = machine(&[0x8f0e]);
+    set(&mut emu, 0xf, 0x80);
+    check(&mut emu, |s| s.v[0xf] = 1);
+}
+
+#[test]
+fn flag_from_vf_as_operand() {
+    // 8xF4 reads VF before writing the carry
+    let mut emu = machine(&[0x81f4]);
+    set(&mut emu, 1, 0x01);
+
+    check(&mut emu, |s| {
+        s.v[1] = 0x00;
+        s.v[0xf] = 1;
+    });
+}
+
+#[test]
+fn skip_ne_reg() {
+    let mut emu = machine(&[0x9110, 0x9120]);
+
+    check(&mut emu, |_| ());
+    check(&mut emu, |s| s.pc += 2);
+}
+
+#[test]
+fn load_i() {
+    let mut emu = machine(&[0xa123]);
+
+    check(&mut emu, |s| s.i = 0x123);
+}
+
+#[test]
+fn jump_offset() {
+    let mut emu = machine(&[0xb300]);
+    set(&mut emu, 0, 0x10);
+
+    check(&mut emu, |s| s.pc = 0x310);
+}
+
+#[test]
+fn jump_offset_vx_with_quirk() {
+    let mut emu = machine(&[0xb310]);
+    emu.quirks.jump_vx = true;
+
+    check(&mut emu, |s| s.pc = 0x310 + 0x33);
+}
+
+#[test]
+fn random_masks_generator_output() {
+    let mut emu = machine(&[0xc10f]);
+    let mut rng = emu.rng;
+    let val = rng.next_u8() & 0x0f;
+
+    check(&mut emu, |s| {
+        s.v[1] = val;
+        s.rng = rng.state;
+    });
+}
+
+#[test]
+fn draw_sets_collision() {
+    let mut emu = machine(&[0xd012, 0xd012]);
+    set(&mut emu, 0, 2);
+    set(&mut emu, 1, 1);
+    emu.memory.memory[0x300] = 0xc0;
+    emu.memory.memory[0x301] = 0x01;
+
+    let pixels = [2 + 64, 3 + 64, 9 + 128];
+    check(&mut emu, |s| {
+        for px in pixels.iter() {
+            s.display[*px] = 1;
+        }
+        s.v[0xf] = 0;
+        s.frame_drawn = true;
+    });
+    check(&mut emu, |s| {
+        for px in pixels.iter() {
+            s.display[*px] = 0;
+        }
+        s.v[0xf] = 1;
+    });
+}
+
+#[test]
+fn draw_clips_at_edge() {
+    let mut emu = machine(&[0xd011]);
+    set(&mut emu, 0, 62);
+    set(&mut emu, 1, 0);
+    emu.memory.memory[0x300] = 0xf0;
+
+    check(&mut emu, |s| {
+        s.display[62] = 1;
+        s.display[63] = 1;
+        s.v[0xf] = 0;
+        s.frame_drawn = true;
+    });
+}
+
+#[test]
+fn skip_key() {
+    let mut emu = machine(&[0xe59e, 0xe59e]);
+
+    check(&mut emu, |_| ());
+    emu.input.set(5, true);
+    check(&mut emu, |s| s.pc += 2);
+}
+
+#[test]
+fn skip_not_key_leaves_registers_alone() {
+    let mut emu = machine(&[0xe5a1, 0x0000, 0xe5a1]);
+    set(&mut emu, 5, 0x05);
+    emu.registers.dt = 0x42;
+
+    check(&mut emu, |s| s.pc += 2);
+    emu.input.set(5, true);
+    check(&mut emu, |_| ());
+}
+
+#[test]
+fn skip_key_uses_low_nibble() {
+    let mut emu = machine(&[0xe19e]);
+    set(&mut emu, 1, 0x13);
+    emu.input.set(3, true);
+
+    check(&mut emu, |s| s.pc += 2);
+}
+
+#[test]
+fn load_long_i() {
+    let mut emu = machine(&[0xf000, 0xbeef]);
+
+    check(&mut emu, |s| {
+        s.i = 0xbeef;
+        s.pc += 2;
+    });
+}
+
+#[test]
+fn plane() {
+    let mut emu = machine(&[0xf201]);
+
+    check(&mut emu, |s| s.planes = 2);
+}
+
+#[test]
+fn audio_and_pitch() {
+    let mut emu = machine(&[0xf002, 0xf13a]);
+    for offset in 0..16 {
+        emu.memory.memory[0x300 + offset] = offset as u8;
+    }
+
+    let mut pattern = [0; 16];
+    for (offset, val) in pattern.iter_mut().enumerate() {
+        *val = offset as u8;
+    }
+    check(&mut emu, |s| s.audio_pattern = Some(pattern));
+    check(&mut emu, |s| s.pitch = 0x11);
+}
+
+#[test]
+fn delay_timer() {
+    let mut emu = machine(&[0xf315, 0xf407]);
+
+    check(&mut emu, |s| s.dt = 0x33);
+    check(&mut emu, |s| s.v[4] = 0x33);
+}
+
+#[test]
+fn sound_timer() {
+    let mut emu = machine(&[0xf218]);
+
+    check(&mut emu, |s| s.st = 0x22);
+}
+
+#[test]
+fn wait_key_waits_for_release() {
+    let mut emu = machine(&[0xf30a]);
+
+    // Nothing pressed, the instruction repeats
+    check(&mut emu, |s| s.pc -= 2);
+
+    // Pressing keys picks the lowest one but keeps waiting
+    emu.input.set(7, true);
+    emu.input.set(2, true);
+    check(&mut emu, |s| {
+        s.pc -= 2;
+        s.key_wait = Some(2);
+    });
+
+    // Releasing another key changes nothing
+    emu.input.set(7, false);
+    check(&mut emu, |s| s.pc -= 2);
+
+    // Releasing the pressed key completes the instruction
+    emu.input.set(2, false);
+    check(&mut emu, |s| {
+        s.v[3] = 2;
+        s.key_wait = None;
+    });
+}
+
+#[test]
+fn add_i() {
+    let mut emu = machine(&[0xf21e]);
+
+    check(&mut emu, |s| s.i = 0x322);
+}
+
+#[test]
+fn add_i_flags_overflow_with_quirk() {
+    let mut emu = machine(&[0xf21e]);
+    emu.quirks.add_i_overflow = true;
+    emu.registers.i = 0xff0;
+
+    check(&mut emu, |s| {
+        s.i = 0x1012;
+        s.v[0xf] = 1;
+    });
+}
+
+#[test]
+fn fonts() {
+    let mut emu = machine(&[0xf229, 0xf230]);
+
+    check(&mut emu, |s| s.i = (FONT_OFFSET + 0x22 * 5) as u16);
+    check(&mut emu, |s| s.i = (BIG_FONT_OFFSET + 0x2 * 10) as u16);
+}
+
+#[test]
+fn bcd() {
+    let mut emu = machine(&[0xf133]);
+    set(&mut emu, 1, 254);
+
+    check(&mut emu, |s| {
+        s.memory[0x300] = 2;
+        s.memory[0x301] = 5;
+        s.memory[0x302] = 4;
+    });
+}
+
+#[test]
+fn store_and_load_registers() {
+    let mut emu = machine(&[0xf255, 0xf165]);
+
+    check(&mut emu, |s| {
+        s.memory[0x300] = 0x00;
+        s.memory[0x301] = 0x11;
+        s.memory[0x302] = 0x22;
+    });
+
+    emu.memory.memory[0x300] = 0xaa;
+    emu.memory.memory[0x301] = 0xbb;
+    check(&mut emu, |s| {
+        s.v[0] = 0xaa;
+        s.v[1] = 0xbb;
+    });
+}
+
+#[test]
+fn store_increments_i_with_quirk() {
+    let mut emu = machine(&[0xf155]);
+    emu.quirks = Quirks { load_store: LoadStore::IncrementX1, ..Quirks::default() };
+
+    check(&mut emu, |s| {
+        s.memory[0x301] = 0x11;
+        s.i = 0x302;
+    });
+}
+
+#[test]
+fn store_and_load_flags() {
+    let mut emu = machine(&[0xf975, 0xf185]);
+
+    check(&mut emu, |s| {
+        for x in 0..8 {
+            s.flags[x] = x as u8 * 0x11;
+        }
+    });
+
+    emu.registers.flags[0] = 0xaa;
+    check(&mut emu, |s| s.v[0] = 0xaa);
+}
+
+#[test]
+fn unknown_opcode_is_an_error() {
+    let mut emu = machine(&[0x5121]);
+
+    assert!(emu.step().is_err());
+}
@@ -0,0 +1,60 @@
+use chip8_emu::quirks::{LoadStore, Quirks};
+
+// Quirk profiles as given to --quirks: presets, their aliases and overrides, and the profile
+// strings `Display` writes for them.
+
+fn parse(profile: &str) -> Quirks {
+    profile.parse().unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[test]
+fn presets_and_aliases() {
+    assert_eq!(parse("default"), Quirks::default());
+    assert_eq!(parse("vip"), Quirks::vip());
+    assert_eq!(parse("cosmac"), Quirks::vip());
+    assert_eq!(parse("chip8"), Quirks::vip());
+    assert_eq!(parse("CHIP48"), Quirks::chip48());
+    assert_eq!(parse("schip"), Quirks::schip());
+    assert_eq!(parse("superchip"), Quirks::schip());
+    assert_eq!(parse("xochip"), Quirks::xochip());
+}
+
+#[test]
+fn overrides_apply_in_order() {
+    let quirks = parse("vip, -display-wait, +wrap, jump-vx");
+    assert_eq!(quirks, Quirks { display_wait: false, wrap: true, jump_vx: true, ..Quirks::vip() });
+
+    assert_eq!(parse("default,+wrap,-wrap"), Quirks::default());
+    assert_eq!(parse("default,+load-store-x,+load-store-x1").load_store, LoadStore::IncrementX1);
+}
+
+#[test]
+fn clearing_a_load_store_variant_keeps_the_other() {
+    // vip increments I by x + 1
+    assert_eq!(parse("vip,-load-store-x").load_store, LoadStore::IncrementX1);
+    assert_eq!(parse("vip,-load-store-x1").load_store, LoadStore::Unchanged);
+
+    // chip48 increments I by x
+    assert_eq!(parse("chip48,-load-store-x1").load_store, LoadStore::IncrementX);
+    assert_eq!(parse("chip48,-load-store-x").load_store, LoadStore::Unchanged);
+}
+
+#[test]
+fn unknown_names_are_rejected() {
+    for (profile, name) in [("nope", "nope"), ("", ""), ("vip,+nope", "nope"), ("vip,-", ""), ("+wrap", "+wrap")] {
+        let err = profile.parse::<Quirks>().unwrap_err();
+        assert_eq!(err.to_string(), format!("unknown quirk profile or quirk '{}'", name), "{}", profile);
+    }
+}
+
+#[test]
+fn display_round_trips() {
+    assert_eq!(Quirks::vip().to_string(), "vip");
+    assert_eq!(parse("chip48,-jump-vx").to_string(), "default,+load-store-x");
+    assert_eq!(parse("xochip,+add-i-overflow").to_string(), "default,+shift-vy,+load-store-x1,+wrap,+add-i-overflow");
+
+    for profile in ["default", "vip,-vf-reset", "schip,+wrap,+display-wait", "chip48,+shift-vy", "xochip,-wrap"] {
+        let quirks = parse(profile);
+        assert_eq!(parse(&quirks.to_string()), quirks, "{}", profile);
+    }
+}
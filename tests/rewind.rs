@@ -0,0 +1,224 @@
+use chip8_emu::emulator::Emulator;
+use chip8_emu::movie::{Player, Recorder};
+use chip8_emu::quirks::Quirks;
+use chip8_emu::rewind::{compress, decompress, Rewind};
+use chip8_emu::rng::Rng;
+
+// PackBits at the edges of its run lengths, and rewinding BRIX while its paddle is steered with
+// 4 and 6, so snapshots are restored and replayed with input changing in between.
+
+const BRIX: &[u8] = include_bytes!("../games/BRIX");
+
+/// Keys held before instruction `position`, changing in the middle of snapshot intervals
+fn script(position: u64) -> [bool; 2] {
+    [(position / 37).is_multiple_of(3), (position / 53) % 4 == 1]
+}
+
+fn machine() -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::new(11);
+    emu.quirks = Quirks::vip();
+    emu.memory.load_bytes(BRIX).unwrap();
+
+    emu
+}
+
+fn press(emu: &mut Emulator, position: u64) {
+    let [left, right] = script(position);
+    emu.input.set(0x4, left);
+    emu.input.set(0x6, right);
+}
+
+/// Apply the scripted input for `position`, record it and execute one instruction
+fn step(emu: &mut Emulator, rewind: &mut Rewind, position: u64) {
+    press(emu, position);
+    rewind.record(emu);
+    emu.step().unwrap();
+}
+
+/// Save state of a straight run about to execute instruction `position`, with its input applied
+fn straight_run(position: u64) -> Vec<u8> {
+    let mut emu = machine();
+
+    for p in 0..position {
+        press(&mut emu, p);
+        emu.step().unwrap();
+    }
+    press(&mut emu, position);
+
+    emu.save_state()
+}
+
+#[test]
+fn packbits_round_trips() {
+    let mut noise = Vec::new();
+    let mut x = 1u32;
+    for _ in 0..5000 {
+        x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        // Mostly zeros with short runs and stray bytes, like memory
+        noise.push(if x >> 30 == 0 { (x >> 16) as u8 } else { 0 });
+    }
+
+    let cases: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![7],
+        vec![1, 2],
+        vec![3, 3],
+        vec![0; 128],
+        vec![0; 129],
+        vec![0xaa; 1000],
+        (0..128).map(|b| b as u8).collect(),
+        (0..129).map(|b| b as u8).collect(),
+        (0..1000).map(|b| (b * 7) as u8).collect(),
+        noise,
+    ];
+
+    for data in cases {
+        assert_eq!(decompress(&compress(&data)), data, "{} bytes", data.len());
+    }
+}
+
+#[test]
+fn packbits_run_lengths() {
+    assert_eq!(compress(&[]), Vec::<u8>::new());
+    assert_eq!(decompress(&[]), Vec::<u8>::new());
+
+    // A run is at most 128 bytes, the rest starts a new one
+    assert_eq!(compress(&[5; 128]), [0x81, 5]);
+    assert_eq!(compress(&[5; 129]), [0x81, 5, 0x00, 5]);
+    assert_eq!(compress(&[5; 130]), [0x81, 5, 0xff, 5]);
+
+    // So is a literal
+    let literal: Vec<u8> = (0..129).map(|b| b as u8).collect();
+    let packed = compress(&literal);
+    assert_eq!(packed[0], 0x7f);
+    assert_eq!(packed[1..129], literal[..128]);
+    assert_eq!(packed[129..], [0x00, 128]);
+
+    // Literals stop where a run starts
+    assert_eq!(compress(&[1, 2, 3, 3, 3]), [0x01, 1, 2, 0xfe, 3]);
+}
+
+#[test]
+fn rewind_reaches_the_state_of_a_straight_run() {
+    let (mut emu, mut rewind) = (machine(), Rewind::new(10, usize::MAX));
+
+    for position in 0..1000 {
+        step(&mut emu, &mut rewind, position);
+    }
+
+    // Land between snapshots, on instructions 901 and 888 where keys change, and on snapshots
+    for (steps, target) in [(99, 901), (1, 900), (1, 899), (11, 888), (322, 566), (566, 0)] {
+        assert_eq!(rewind.rewind(&mut emu, steps), target > 0);
+        assert_eq!(rewind.position(), target);
+        assert_eq!(emu.save_state(), straight_run(target), "rewound to {}", target);
+    }
+}
+
+#[test]
+fn replay_after_rewind_matches_a_straight_run() {
+    let (mut emu, mut rewind) = (machine(), Rewind::new(10, usize::MAX));
+
+    for position in 0..1000 {
+        step(&mut emu, &mut rewind, position);
+    }
+
+    assert!(rewind.rewind(&mut emu, 457));
+    for position in rewind.position()..1500 {
+        step(&mut emu, &mut rewind, position);
+    }
+    press(&mut emu, 1500);
+    assert_eq!(emu.save_state(), straight_run(1500));
+
+    // The replayed part can be rewound again
+    assert!(rewind.rewind(&mut emu, 205));
+    assert_eq!(emu.save_state(), straight_run(1295));
+}
+
+#[test]
+fn different_input_after_rewind_replaces_the_history() {
+    let (mut emu, mut rewind) = (machine(), Rewind::new(10, usize::MAX));
+
+    for position in 0..200 {
+        step(&mut emu, &mut rewind, position);
+    }
+    assert!(rewind.rewind(&mut emu, 95));
+    let rewound = emu.save_state();
+
+    // Play on differently, then come back to the same point
+    for _ in 0..50 {
+        emu.input.set(0x6, true);
+        rewind.record(&emu);
+        emu.step().unwrap();
+    }
+    assert!(rewind.rewind(&mut emu, 50));
+
+    assert_eq!(emu.save_state(), rewound);
+}
+
+#[test]
+fn budget_evicts_the_oldest_snapshots() {
+    let (mut emu, mut rewind) = (machine(), Rewind::new(10, usize::MAX));
+    for position in 0..10 {
+        step(&mut emu, &mut rewind, position);
+    }
+    let snapshot_size = rewind.used();
+
+    // Room for about 5 snapshots
+    let (mut emu, mut rewind) = (machine(), Rewind::new(10, snapshot_size * 5 + snapshot_size / 2));
+    for position in 0..1000 {
+        step(&mut emu, &mut rewind, position);
+        assert!(rewind.used() <= rewind.budget);
+    }
+
+    let available = rewind.available();
+    assert!((30..=60).contains(&available), "{} instructions available", available);
+
+    // Rewinding further stops at the oldest snapshot left
+    assert!(!rewind.rewind(&mut emu, 1000));
+    assert_eq!(rewind.position(), 1000 - available);
+    assert_eq!(rewind.available(), 0);
+    assert_eq!(emu.save_state(), straight_run(1000 - available));
+}
+
+#[test]
+fn zero_budget_disables_rewinding() {
+    let (mut emu, mut rewind) = (machine(), Rewind::new(10, 0));
+    for position in 0..100 {
+        step(&mut emu, &mut rewind, position);
+    }
+
+    assert_eq!(rewind.used(), 0);
+    assert!(!rewind.rewind(&mut emu, 10));
+    assert_eq!(rewind.position(), 100);
+    assert_eq!(emu.save_state(), straight_run(100));
+}
+
+#[test]
+fn zero_budget_rewind_keeps_the_recording() {
+    let (mut emu, mut rewind) = (machine(), Rewind::new(10, 0));
+    let mut recorder = Recorder::new(BRIX, &emu);
+
+    for position in 0..1000 {
+        press(&mut emu, position);
+        rewind.record(&emu);
+        recorder.record(&emu);
+        emu.step().unwrap();
+    }
+
+    // Holding Backspace goes nowhere, the movie is cut where the machine still is
+    assert!(!rewind.rewind(&mut emu, 120));
+    assert_eq!(rewind.position(), 1000);
+    recorder.truncate(rewind.position(), &emu);
+
+    let movie = recorder.finish(&emu);
+    assert!(!movie.events.is_empty());
+
+    let mut replay = machine();
+    movie.apply(&mut replay);
+    let mut player = Player::new(movie);
+    while player.play(&mut replay) {
+        replay.step().unwrap();
+    }
+    player.verify(&replay).unwrap();
+}
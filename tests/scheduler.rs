@@ -0,0 +1,165 @@
+use chip8_emu::emulator::Emulator;
+
+use std::process::Command;
+
+// Frames of a fixed number of instructions with the timers ticking once at the end of each, as
+// set with --ipf or --clock, and the pacing that sleeps until the next frame is due.
+
+/// ld v0, 0xff   ld dt, v0   ld st, v0   add v1, 1   jp 0x206
+const PROGRAM: [u8; 10] = [0x60, 0xff, 0xf0, 0x15, 0xf0, 0x18, 0x71, 0x01, 0x12, 0x06];
+
+/// ROM file holding `PROGRAM`, removed when dropped
+struct Rom(std::path::PathBuf);
+
+impl Rom {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("chip8-scheduler-{}-{}.ch8", name, std::process::id()));
+        std::fs::write(&path, PROGRAM).unwrap();
+
+        Rom(path)
+    }
+
+    /// Run chip8-emu headless on the ROM, returns its exit code, stdout and stderr
+    fn run(&self, args: &[&str]) -> (i32, String, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_chip8-emu")).arg("--headless").args(args).arg(&self.0).output().unwrap();
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+        (output.status.code().unwrap(), text(&output.stdout), text(&output.stderr))
+    }
+}
+
+impl Drop for Rom {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Machine about to enter the loop of `PROGRAM` with both timers at 200
+fn machine(cycles_per_frame: u32) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.cycles_per_frame = cycles_per_frame;
+    emu.memory.load_bytes(&PROGRAM).unwrap();
+    emu.registers.pc = 0x206;
+    emu.registers.dt = 200;
+    emu.registers.st = 200;
+
+    emu
+}
+
+#[test]
+fn frames_run_a_fixed_number_of_instructions() {
+    for cycles_per_frame in [1, 7, 10, 100] {
+        let mut emu = machine(cycles_per_frame);
+
+        for frame in 1..=20 {
+            emu.run_frame().unwrap();
+            assert_eq!((emu.registers.dt, emu.registers.st), (200 - frame, 200 - frame), "{} per frame", cycles_per_frame);
+        }
+
+        // Every other instruction of the loop counts in v1
+        let counted = emu.registers.reg_read(1).unwrap() as u32;
+        assert_eq!(counted, (20 * cycles_per_frame).div_ceil(2) % 256, "{} per frame", cycles_per_frame);
+    }
+}
+
+#[test]
+fn timers_tick_after_the_last_instruction_of_a_frame() {
+    let mut emu = machine(7);
+
+    for step in 0..7 {
+        assert_eq!(emu.registers.dt, 200, "before step {}", step);
+        emu.step().unwrap();
+    }
+    assert_eq!(emu.registers.dt, 199);
+    assert_eq!(emu.tick_cnt, 0);
+}
+
+#[test]
+fn exit_ends_the_frame_early() {
+    // exit
+    let mut emu = Emulator::new();
+    emu.memory.load_bytes(&[0x00, 0xfd]).unwrap();
+
+    emu.run_frame().unwrap();
+    assert!(emu.exited);
+    assert_eq!(emu.tick_cnt, 1);
+}
+
+#[test]
+fn ipf_and_clock_set_the_frame_length() {
+    let rom = Rom::new("ipf");
+
+    let (code, ipf, stderr) = rom.run(&["--ipf", "7", "-n", "40", "--trace"]);
+    assert_eq!(code, 0, "{}", stderr);
+
+    // The delay timer is set by the second instruction and ticks after every 7th, trace lines
+    // show it before each instruction
+    let dt: Vec<&str> = ipf.lines().map(|line| &line[line.find("dt=").unwrap() + 3..][..2]).collect();
+    assert_eq!(dt[..2], ["00", "00"]);
+    assert!(dt[2..7].iter().all(|dt| *dt == "ff"));
+    assert!(dt[7..14].iter().all(|dt| *dt == "fe"));
+    assert!(dt[14..21].iter().all(|dt| *dt == "fd"));
+    assert_eq!(dt.len(), 40);
+
+    let (code, clock, _) = rom.run(&["--clock", "420", "-n", "40", "--trace"]);
+    assert_eq!(code, 0);
+    assert_eq!(clock, ipf);
+}
+
+#[test]
+fn clock_must_be_a_multiple_of_the_frame_rate() {
+    let rom = Rom::new("clock");
+
+    let (code, _, stderr) = rom.run(&["--clock", "100", "-n", "1"]);
+    assert_eq!(code, 2);
+    assert!(stderr.contains("clock speed of 100 is not a multiple of 60 instructions per second"), "{}", stderr);
+
+    for args in [["--clock", "0"], ["--ipf", "0"], ["--clock", "59"]] {
+        let (code, _, stderr) = rom.run(&args);
+        assert_eq!(code, 2, "{:?}", args);
+        assert!(stderr.contains("clock speed"), "{:?}: {}", args, stderr);
+    }
+
+    assert_eq!(rom.run(&["--clock", "60", "-n", "1"]).0, 0);
+}
+
+#[cfg(feature = "sdl")]
+mod pacing {
+    use chip8_emu::frontend::{wait_for_frame, MAX_LAG};
+
+    use std::time::{Duration, Instant};
+
+    const FRAME: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn sleeps_until_the_next_frame_is_due() {
+        let start = Instant::now();
+        let mut deadline = start;
+
+        for frame in 1..=3 {
+            wait_for_frame(&mut deadline, FRAME);
+            assert_eq!(deadline, start + FRAME * frame);
+            assert!(Instant::now() >= deadline);
+        }
+    }
+
+    #[test]
+    fn late_frames_are_made_up() {
+        // Two frames behind, the deadlines stay on schedule and nothing sleeps until caught up
+        let start = Instant::now() - FRAME * 2;
+        let mut deadline = start;
+
+        wait_for_frame(&mut deadline, FRAME);
+        assert_eq!(deadline, start + FRAME);
+        assert!(Instant::now() - start < FRAME * 3, "slept while behind");
+    }
+
+    #[test]
+    fn schedule_restarts_when_far_behind() {
+        let start = Instant::now() - FRAME * (MAX_LAG + 2);
+        let mut deadline = start;
+
+        wait_for_frame(&mut deadline, FRAME);
+        assert!(deadline > start + FRAME * MAX_LAG, "schedule kept");
+    }
+}
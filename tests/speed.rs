@@ -0,0 +1,67 @@
+use chip8_emu::speed::Speed;
+
+// The speed hotkeys of the window: slow motion, fast forward, pause and frame advance, and the
+// speed shown in the title.
+
+#[test]
+fn slow_motion_cycles_back_to_normal() {
+    let mut speed = Speed::new();
+    let mut percents = Vec::new();
+
+    for _ in 0..4 {
+        speed.cycle_slow_motion();
+        percents.push(speed.percent);
+    }
+
+    assert_eq!(percents, [50, 25, 100, 50]);
+}
+
+#[test]
+fn frames_only_run_when_advanced_while_paused() {
+    let mut speed = Speed::new();
+    assert!(!speed.take_advance());
+
+    // The first press pauses, each following one runs a single frame
+    speed.frame_advance();
+    assert!(speed.paused);
+    assert!(!speed.take_advance());
+
+    speed.frame_advance();
+    speed.frame_advance();
+    assert!(speed.take_advance());
+    assert!(speed.take_advance());
+    assert!(!speed.take_advance());
+}
+
+#[test]
+fn resuming_drops_pending_frame_advances() {
+    let mut speed = Speed::new();
+
+    speed.toggle_pause();
+    speed.frame_advance();
+    speed.toggle_pause();
+    assert!(!speed.paused);
+
+    speed.toggle_pause();
+    assert!(!speed.take_advance());
+}
+
+#[test]
+fn title_shows_the_speed() {
+    let mut speed = Speed::new();
+    assert_eq!(speed.to_string(), "100%");
+
+    speed.cycle_slow_motion();
+    assert_eq!(speed.to_string(), "50%");
+
+    speed.fast_forward = true;
+    assert_eq!(speed.to_string(), "fast forward");
+
+    // Pausing wins over both
+    speed.frame_advance();
+    assert_eq!(speed.to_string(), "paused");
+
+    speed.toggle_pause();
+    speed.fast_forward = false;
+    assert_eq!(speed.to_string(), "50%");
+}
@@ -0,0 +1,166 @@
+use chip8_emu::emulator::Emulator;
+use chip8_emu::error::Chip8Error;
+use chip8_emu::quirks::Quirks;
+use chip8_emu::rng::Rng;
+use chip8_emu::state::{crc32, VERSION};
+
+// Save states of BRIX part way through a game, and files damaged in each part of the format:
+// magic, version, checksum and payload. A rejected state must leave the emulator untouched.
+
+const BRIX: &[u8] = include_bytes!("../games/BRIX");
+
+/// BRIX after `steps` instructions with key 4 held for the second half
+fn running(steps: usize) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::new(7);
+    emu.quirks = Quirks::vip();
+    emu.memory.load_bytes(BRIX).unwrap();
+
+    for step in 0..steps {
+        emu.input.set(0x4, step >= steps / 2);
+        emu.step().unwrap();
+    }
+
+    emu
+}
+
+/// State file around `payload`, with a correct length and checksum
+fn state_with_payload(payload: &[u8]) -> Vec<u8> {
+    let mut data = b"C8ST".to_vec();
+    data.extend_from_slice(&VERSION.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    data.extend_from_slice(payload);
+    data.extend_from_slice(&crc32(payload).to_le_bytes());
+
+    data
+}
+
+/// Payload of a state file made by `save_state`
+fn payload(data: &[u8]) -> &[u8] {
+    &data[12..data.len() - 4]
+}
+
+/// Load `data` into a running machine and check it fails with `reason` and changes nothing
+fn assert_rejected(data: &[u8], reason: &str) {
+    let mut emu = running(500);
+    let before = emu.save_state();
+
+    match emu.load_state(data) {
+        Err(Chip8Error::InvalidState(msg)) => assert_eq!(msg, reason),
+        other => panic!("expected '{}', got {:?}", reason, other),
+    }
+
+    assert_eq!(emu.save_state(), before);
+}
+
+#[test]
+fn crc32_matches_the_standard_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn save_and_load_round_trip() {
+    let mut original = running(3000);
+    let data = original.save_state();
+
+    let mut restored = Emulator::new();
+    restored.load_state(&data).unwrap();
+    assert_eq!(restored.save_state(), data);
+
+    // Both continue the same way, random numbers included
+    for _ in 0..3000 {
+        original.step().unwrap();
+        restored.step().unwrap();
+    }
+    assert_eq!(restored.save_state(), original.save_state());
+}
+
+#[test]
+fn state_files_round_trip() {
+    let path = std::env::temp_dir().join(format!("chip8-state-{}.c8s", std::process::id()));
+    let emu = running(1000);
+
+    emu.save_state_file(&path).unwrap();
+    let mut restored = Emulator::new();
+    let result = restored.load_state_file(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    result.unwrap();
+    assert_eq!(restored.save_state(), emu.save_state());
+}
+
+#[test]
+fn bad_magic_is_rejected() {
+    let mut data = running(100).save_state();
+    data[0..4].copy_from_slice(b"C8SX");
+
+    assert_rejected(&data, "not a save state");
+    assert_rejected(b"C8", "truncated header");
+}
+
+#[test]
+fn other_version_is_rejected() {
+    let mut data = running(100).save_state();
+    data[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+    assert_rejected(&data, "unsupported version");
+}
+
+#[test]
+fn corrupt_payload_fails_the_checksum() {
+    let mut data = running(100).save_state();
+    data[12 + 0x300] ^= 0x01;
+
+    assert_rejected(&data, "checksum mismatch");
+
+    let last = data.len() - 1;
+    let mut data = running(100).save_state();
+    data[last] ^= 0x80;
+
+    assert_rejected(&data, "checksum mismatch");
+}
+
+#[test]
+fn truncated_file_is_rejected() {
+    let data = running(100).save_state();
+
+    assert_rejected(&data[..data.len() - 10], "truncated payload");
+    assert_rejected(&data[..20], "truncated payload");
+}
+
+#[test]
+fn short_payload_is_rejected() {
+    let data = running(100).save_state();
+    let payload = payload(&data);
+
+    // Length and checksum agree with the payload, which just ends early
+    assert_rejected(&state_with_payload(&payload[..payload.len() - 1]), "truncated payload");
+}
+
+#[test]
+fn trailing_payload_is_rejected() {
+    let data = running(100).save_state();
+    let mut payload = payload(&data).to_vec();
+    payload.push(0);
+
+    assert_rejected(&state_with_payload(&payload), "trailing payload data");
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    let data = running(100).save_state();
+    let payload = payload(&data).to_vec();
+
+    // The payload ends with the generator, its state and 7 bytes of quirks, load/store second
+    let quirks = payload.len() - 7;
+    let mut bad = payload.clone();
+    bad[quirks + 1] = 3;
+    assert_rejected(&state_with_payload(&bad), "unknown load/store quirk");
+
+    let algorithm = quirks - 8 - 1;
+    let mut bad = payload;
+    bad[algorithm] = 9;
+    assert_rejected(&state_with_payload(&bad), "unknown random number generator");
+}
@@ -0,0 +1,128 @@
+use chip8_emu::emulator::Emulator;
+use chip8_emu::trace::{parse_range, TraceFormat, Tracer};
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Trace lines in both formats, address ranges given to --trace-range, and a tracer writing into
+// a buffer the test reads back, with and without ring mode.
+
+/// ld ve, 5   call 0x208   jp 0x204   add v1, 1   ret
+const PROGRAM: [u8; 10] = [0x6e, 0x05, 0x22, 0x06, 0x12, 0x04, 0x71, 0x01, 0x00, 0xee];
+
+/// Output shared between a tracer and the test
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(String::from).collect()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn machine() -> Emulator {
+    let mut emu = Emulator::new();
+    emu.memory.load_bytes(&PROGRAM).unwrap();
+
+    emu
+}
+
+/// Trace `steps` instructions of the program, returns the tracer's output
+fn trace(steps: usize, setup: impl FnOnce(&mut Tracer)) -> (Buffer, Tracer) {
+    let buffer = Buffer::default();
+    let mut tracer = Tracer::new(Box::new(buffer.clone()));
+    setup(&mut tracer);
+
+    let mut emu = machine();
+    for _ in 0..steps {
+        tracer.record(&emu).unwrap();
+        emu.step().unwrap();
+    }
+
+    (buffer, tracer)
+}
+
+#[test]
+fn line_formats() {
+    let mut emu = machine();
+    emu.step().unwrap();
+    emu.step().unwrap();
+    emu.registers.i = 0x123;
+    emu.registers.dt = 0x3c;
+
+    assert_eq!(
+        TraceFormat::Default.line(&emu),
+        "0206 7101 add v1, 0x1          v0=00 v1=00 v2=00 v3=00 v4=00 v5=00 v6=00 v7=00 \
+         v8=00 v9=00 va=00 vb=00 vc=00 vd=00 ve=05 vf=00 i=0123 sp=1 dt=3c st=00"
+    );
+    assert_eq!(
+        TraceFormat::Registers.line(&emu),
+        "PC:0206 OP:7101 V:00 00 00 00 00 00 00 00 00 00 00 00 00 00 05 00 I:0123 SP:1 DT:3C ST:00"
+    );
+}
+
+#[test]
+fn format_names() {
+    assert_eq!("default".parse(), Ok(TraceFormat::Default));
+    assert_eq!("Registers".parse(), Ok(TraceFormat::Registers));
+
+    let err = "regs".parse::<TraceFormat>().unwrap_err();
+    assert_eq!(err.to_string(), "unknown trace format 'regs'");
+}
+
+#[test]
+fn ranges() {
+    assert_eq!(parse_range("200-2ff"), Some(0x200..=0x2ff));
+    assert_eq!(parse_range("0x200 - 0x2FF"), Some(0x200..=0x2ff));
+    assert_eq!(parse_range("0-ffff"), Some(0..=0xffff));
+    assert_eq!(parse_range("300-300"), Some(0x300..=0x300));
+
+    for invalid in ["", "200", "200-", "-2ff", "2ff-200", "200-10000", "200-2fg", "200-2ff-300"] {
+        assert_eq!(parse_range(invalid), None, "{}", invalid);
+    }
+}
+
+#[test]
+fn every_instruction_is_written() {
+    let (buffer, _) = trace(6, |_| ());
+    let pcs: Vec<String> = buffer.lines().iter().map(|line| line[..4].to_string()).collect();
+
+    assert_eq!(pcs, ["0200", "0202", "0206", "0208", "0204", "0204"]);
+}
+
+#[test]
+fn range_limits_the_traced_addresses() {
+    let (buffer, _) = trace(6, |tracer| {
+        tracer.format = TraceFormat::Registers;
+        tracer.range = parse_range("206-208");
+    });
+    let pcs: Vec<String> = buffer.lines().iter().map(|line| line[3..7].to_string()).collect();
+
+    assert_eq!(pcs, ["0206", "0208"]);
+}
+
+#[test]
+fn ring_mode_writes_the_last_lines_on_crash() {
+    let (buffer, mut tracer) = trace(6, |tracer| tracer.keep_last(3));
+    assert!(buffer.lines().is_empty());
+
+    tracer.crash().unwrap();
+    let pcs: Vec<String> = buffer.lines().iter().map(|line| line[..4].to_string()).collect();
+    assert_eq!(pcs, ["0208", "0204", "0204"]);
+
+    // The ring is emptied
+    tracer.crash().unwrap();
+    assert_eq!(buffer.lines().len(), 3);
+}
@@ -0,0 +1,198 @@
+use chip8_emu::emulator::Emulator;
+use chip8_emu::quirks::Quirks;
+use chip8_emu::rng::Rng;
+use chip8_emu::trace::TraceFormat;
+use chip8_emu::tracediff::{compare_traces, lockstep, Divergence, TraceState};
+
+// Trace lines parsed back into machine state, logs compared line by line, and small programs run
+// in lockstep with quirks that make them behave differently.
+
+const BRIX: &[u8] = include_bytes!("../games/BRIX");
+
+fn machine(program: &[u8], quirks: Quirks) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::new(3);
+    emu.quirks = quirks;
+    emu.memory.load_bytes(program).unwrap();
+
+    emu
+}
+
+/// Trace of the first `steps` instructions of BRIX in `format`
+fn brix_trace(format: TraceFormat, steps: usize) -> String {
+    let mut emu = machine(BRIX, Quirks::vip());
+    let mut trace = String::new();
+
+    for _ in 0..steps {
+        trace.push_str(&format.line(&emu));
+        trace.push('\n');
+        emu.step().unwrap();
+    }
+
+    trace
+}
+
+/// Replace line `n` of `trace`
+fn replace_line(trace: &str, n: usize, line: &str) -> String {
+    let mut lines: Vec<&str> = trace.lines().collect();
+    lines[n] = line;
+
+    lines.join("\n")
+}
+
+#[test]
+fn lines_parse_back_into_the_traced_state() {
+    let mut emu = machine(BRIX, Quirks::vip());
+
+    for _ in 0..500 {
+        let state = TraceState::capture(&emu);
+        assert_eq!(TraceState::parse(&TraceFormat::Default.line(&emu)), Some(state));
+        assert_eq!(TraceState::parse(&TraceFormat::Registers.line(&emu)), Some(state));
+        emu.step().unwrap();
+    }
+}
+
+#[test]
+fn other_lines_are_not_traces() {
+    let line = TraceFormat::Default.line(&machine(BRIX, Quirks::vip()));
+    let registers = TraceFormat::Registers.line(&machine(BRIX, Quirks::vip()));
+
+    let broken = [
+        String::new(),
+        "hello world".to_string(),
+        line.replace(" st=00", ""),
+        line.replace("v3=00", "v3=zz"),
+        line.replace("v3=00", "vg=00"),
+        line.replacen("0200", "02g0", 1),
+        registers.replace(" ST:00", ""),
+        registers.replace("SP:0", "SP:x"),
+    ];
+
+    for line in broken {
+        assert_eq!(TraceState::parse(&line), None, "{}", line);
+    }
+}
+
+#[test]
+fn identical_traces_match() {
+    let trace = brix_trace(TraceFormat::Default, 500);
+
+    assert_eq!(compare_traces(&trace, &trace, 5), None);
+    assert_eq!(compare_traces("", "", 5), None);
+
+    // Blank lines are ignored, other output must be the same
+    let with_output = format!("game over\n\n{}", trace);
+    assert_eq!(compare_traces(&with_output, &format!("game over\n{}\n\n", trace), 5), None);
+}
+
+#[test]
+fn formats_can_be_mixed() {
+    let default = brix_trace(TraceFormat::Default, 500);
+    let registers = brix_trace(TraceFormat::Registers, 500);
+
+    assert_eq!(compare_traces(&default, &registers, 5), None);
+}
+
+#[test]
+fn first_differing_line_is_reported_with_context() {
+    let trace = brix_trace(TraceFormat::Default, 100);
+    let lines: Vec<&str> = trace.lines().collect();
+
+    let mut state = TraceState::parse(lines[40]).unwrap();
+    state.v[3] = 0xaa;
+    state.i = 0x999;
+    let changed = format!(
+        "{:04x} {:04x} whatever it is {} i={:04x} sp={} dt={:02x} st={:02x}",
+        state.pc,
+        state.opcode,
+        (0..16).map(|x| format!("v{:x}={:02x}", x, state.v[x])).collect::<Vec<_>>().join(" "),
+        state.i,
+        state.sp,
+        state.dt,
+        state.st
+    );
+
+    let divergence = compare_traces(&trace, &replace_line(&trace, 40, &changed), 3).unwrap();
+    assert_eq!(divergence.index, 40);
+    assert_eq!(divergence.context_a, lines[37..=40]);
+    assert_eq!(divergence.context_b, [lines[37], lines[38], lines[39], changed.as_str()]);
+    assert_eq!(divergence.differences[0], format!("v3: {:02x} != aa", TraceState::parse(lines[40]).unwrap().v[3]));
+    assert_eq!(divergence.differences[1], format!("i: {:04x} != 0999", TraceState::parse(lines[40]).unwrap().i));
+
+    // Without context only the differing line is kept
+    let divergence = compare_traces(&trace, &replace_line(&trace, 40, &changed), 0).unwrap();
+    assert_eq!(divergence.context_a, [lines[40]]);
+}
+
+#[test]
+fn differing_text_and_short_traces_diverge() {
+    let trace = brix_trace(TraceFormat::Registers, 20);
+    let lines: Vec<&str> = trace.lines().collect();
+
+    let divergence = compare_traces(&trace, &replace_line(&trace, 7, "crashed"), 2).unwrap();
+    assert_eq!((divergence.index, divergence.differences), (7, vec!["lines differ".to_string()]));
+
+    let short = lines[..12].join("\n");
+    let divergence = compare_traces(&short, &trace, 2).unwrap();
+    assert_eq!(divergence.index, 12);
+    assert_eq!(divergence.context_a.last().unwrap(), "<end of trace>");
+    assert_eq!(divergence.differences, ["trace a ends after 12 lines"]);
+
+    let divergence = compare_traces(&trace, &short, 2).unwrap();
+    assert_eq!(divergence.differences, ["trace b ends after 12 lines"]);
+}
+
+#[test]
+fn divergence_display() {
+    let divergence = Divergence {
+        index: 2,
+        context_a: vec!["one".to_string(), "two".to_string(), "three".to_string()],
+        context_b: vec!["one".to_string(), "two".to_string(), "3".to_string()],
+        differences: vec!["v1: 00 != 01".to_string()],
+    };
+
+    assert_eq!(divergence.to_string(), "runs diverge after 2 instructions\n  one\n  two\na three\nb 3\n  v1: 00 != 01\n");
+}
+
+#[test]
+fn lockstep_with_the_same_quirks_matches() {
+    let mut a = machine(BRIX, Quirks::vip());
+    let mut b = machine(BRIX, Quirks::vip());
+
+    assert_eq!(lockstep(&mut a, &mut b, 1000, 5), None);
+    assert_eq!(TraceState::capture(&a), TraceState::capture(&b));
+}
+
+#[test]
+fn lockstep_finds_the_quirk_that_matters() {
+    // ld v1, 5   ld v2, 3   shr v1, v2   jp 0x206
+    let program = [0x61, 0x05, 0x62, 0x03, 0x81, 0x26, 0x12, 0x06];
+
+    let mut a = machine(&program, Quirks::default());
+    let mut b = machine(&program, Quirks { shift_vy: true, ..Quirks::default() });
+    let divergence = lockstep(&mut a, &mut b, 100, 1).unwrap();
+
+    assert_eq!(divergence.index, 3);
+    assert_eq!(divergence.differences, ["v1: 02 != 01"]);
+    assert_eq!(divergence.context_a.len(), 2);
+    assert!(divergence.context_a[0].starts_with("0204 8126 shr v1"), "{}", divergence.context_a[0]);
+
+    // Quirks the program does not depend on make no difference
+    let mut a = machine(&program, Quirks::default());
+    let mut b = machine(&program, Quirks { wrap: true, jump_vx: true, ..Quirks::default() });
+    assert_eq!(lockstep(&mut a, &mut b, 100, 1), None);
+}
+
+#[test]
+fn lockstep_ends_when_both_stop_the_same_way() {
+    // ld v0, 1   exit
+    let mut a = machine(&[0x60, 0x01, 0x00, 0xfd], Quirks::default());
+    let mut b = machine(&[0x60, 0x01, 0x00, 0xfd], Quirks::vip());
+    assert_eq!(lockstep(&mut a, &mut b, 100, 1), None);
+    assert!(a.exited && b.exited);
+
+    // ld v0, 1   unknown opcode
+    let mut a = machine(&[0x60, 0x01, 0xff, 0xff], Quirks::default());
+    let mut b = machine(&[0x60, 0x01, 0xff, 0xff], Quirks::default());
+    assert_eq!(lockstep(&mut a, &mut b, 100, 1), None);
+}